@@ -0,0 +1,117 @@
+//! Reading of the rom files, optionally compressed in a zip or gzip archive.
+
+use flate2::read::GzDecoder;
+use std::error::Error;
+use std::fs;
+use std::fs::File;
+use std::io::{BufReader, Read, Seek};
+use std::path::Path;
+use zip::ZipArchive;
+
+const ZIP_EXTENSION: &str = "zip";
+const GZIP_EXTENSION: &str = "gz";
+const ROM_EXTENSIONS: [&str; 2] = ["gb", "gbc"];
+
+/// Extensions of the files that can be opened as a rom.
+pub const SUPPORTED_EXTENSIONS: [&str; 5] = ["gb", "gbc", "gba", ZIP_EXTENSION, GZIP_EXTENSION];
+
+/// Reads the rom stored in the file, zip and gzip archives are decompressed.
+/// In a zip archive, `entry` selects the rom and defaults to the first gb or gbc file.
+pub fn read_rom(path: &Path, entry: Option<&str>) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut rom = Vec::new();
+    match get_extension(path).as_deref() {
+        Some(ZIP_EXTENSION) => {
+            let mut archive = open_zip(path)?;
+            let name = match entry {
+                Some(name) => name.to_string(),
+                None => get_zip_roms(&archive)
+                    .into_iter()
+                    .next()
+                    .ok_or_else(|| format!("No gb or gbc file in {:?}", path))?,
+            };
+            archive.by_name(&name)?.read_to_end(&mut rom)?;
+        }
+        Some(GZIP_EXTENSION) => {
+            GzDecoder::new(BufReader::new(File::open(path)?)).read_to_end(&mut rom)?;
+        }
+        _ => rom = fs::read(path)?,
+    }
+    Ok(rom)
+}
+
+/// Returns the names of the gb and gbc files of a zip archive, in the archive order.
+/// Other files have a single rom and return an empty list.
+pub fn list_archive_roms(path: &Path) -> Result<Vec<String>, Box<dyn Error>> {
+    match get_extension(path).as_deref() {
+        Some(ZIP_EXTENSION) => Ok(get_zip_roms(&open_zip(path)?)),
+        _ => Ok(Vec::new()),
+    }
+}
+
+fn open_zip(path: &Path) -> Result<ZipArchive<BufReader<File>>, Box<dyn Error>> {
+    Ok(ZipArchive::new(BufReader::new(File::open(path)?))?)
+}
+
+fn get_zip_roms<R: Read + Seek>(archive: &ZipArchive<R>) -> Vec<String> {
+    (0..archive.len())
+        .filter_map(|index| archive.name_for_index(index))
+        .filter(|name| {
+            get_extension(Path::new(name))
+                .is_some_and(|extension| ROM_EXTENSIONS.contains(&extension.as_str()))
+        })
+        .map(String::from)
+        .collect()
+}
+
+fn get_extension(path: &Path) -> Option<String> {
+    path.extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+    use std::path::PathBuf;
+    use zip::write::SimpleFileOptions;
+    use zip::ZipWriter;
+
+    fn get_temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("archive_test_{}_{}", std::process::id(), name))
+    }
+
+    #[test]
+    fn zip_rom_defaults_to_first_gb_file() {
+        let path = get_temp_path("roms.zip");
+        let mut writer = ZipWriter::new(File::create(&path).unwrap());
+        for (name, data) in [("readme.txt", [0]), ("first.gb", [1]), ("second.GBC", [2])] {
+            writer
+                .start_file(name, SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(&data).unwrap();
+        }
+        writer.finish().unwrap();
+
+        assert_eq!(
+            list_archive_roms(&path).unwrap(),
+            vec!["first.gb".to_string(), "second.GBC".to_string()]
+        );
+        assert_eq!(read_rom(&path, None).unwrap(), vec![1]);
+        assert_eq!(read_rom(&path, Some("second.GBC")).unwrap(), vec![2]);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn gzip_rom_is_decompressed() {
+        let path = get_temp_path("rom.gb.gz");
+        let mut encoder = GzEncoder::new(File::create(&path).unwrap(), Compression::default());
+        encoder.write_all(&[1, 2, 3]).unwrap();
+        encoder.finish().unwrap();
+
+        assert_eq!(read_rom(&path, None).unwrap(), vec![1, 2, 3]);
+        assert!(list_archive_roms(&path).unwrap().is_empty());
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,148 @@
+//! Image sources for the sensor of the Game Boy Camera.
+
+use std::fs::File;
+use std::io::{BufRead, BufReader, Seek};
+use std::path::{Path, PathBuf};
+
+/// Resolution of the M64282FP sensor used by the Game Boy Camera.
+/// Information from: https://gbdev.io/pandocs/Gameboy_Camera.html
+pub const SENSOR_WIDTH: usize = 128;
+pub const SENSOR_HEIGHT: usize = 112;
+
+/// Provides the light seen by the camera sensor.
+pub trait CameraImageSource: Send {
+    /// Returns the brightness of the SENSOR_WIDTH x SENSOR_HEIGHT pixels, row by row.
+    /// 0 is black and 255 is white.
+    fn capture(&mut self) -> Vec<u8>;
+}
+
+/// Selects the image source of the camera when loading a cartridge.
+#[derive(Clone, Debug, Default)]
+pub enum CameraSource {
+    #[default]
+    TestPattern,
+    Png(PathBuf),
+}
+
+impl CameraSource {
+    pub fn create(&self) -> Result<Box<dyn CameraImageSource>, String> {
+        match self {
+            Self::TestPattern => Ok(Box::new(TestPatternSource {})),
+            Self::Png(path) => Ok(Box::new(PngImageSource::open(path)?)),
+        }
+    }
+}
+
+/// Horizontal gradient crossed by a checkerboard, it exercises all the dithering thresholds.
+pub struct TestPatternSource {}
+
+impl CameraImageSource for TestPatternSource {
+    fn capture(&mut self) -> Vec<u8> {
+        let mut pixels = Vec::with_capacity(SENSOR_WIDTH * SENSOR_HEIGHT);
+        for y in 0..SENSOR_HEIGHT {
+            for x in 0..SENSOR_WIDTH {
+                let gradient = (x * 255 / (SENSOR_WIDTH - 1)) as u8;
+                let is_dark_square = (x / 16 + y / 16) % 2 == 1;
+                pixels.push(if is_dark_square {
+                    gradient / 2
+                } else {
+                    gradient
+                });
+            }
+        }
+        pixels
+    }
+}
+
+/// Still image loaded from a PNG file, converted to grayscale and scaled to the sensor size.
+pub struct PngImageSource {
+    pixels: Vec<u8>,
+}
+
+impl PngImageSource {
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let file = File::open(path).map_err(|e| format!("Could not open {:?}: {:?}", path, e))?;
+        Self::decode(BufReader::new(file))
+    }
+
+    pub fn decode(reader: impl BufRead + Seek) -> Result<Self, String> {
+        let mut decoder = png::Decoder::new(reader);
+        decoder.set_transformations(png::Transformations::normalize_to_color8());
+        let mut reader = decoder
+            .read_info()
+            .map_err(|e| format!("Invalid png image: {:?}", e))?;
+        let size = reader
+            .output_buffer_size()
+            .ok_or("The png image is too large")?;
+        let mut buffer = vec![0; size];
+        let info = reader
+            .next_frame(&mut buffer)
+            .map_err(|e| format!("Invalid png image: {:?}", e))?;
+
+        let samples = info.color_type.samples();
+        let (width, height) = (info.width as usize, info.height as usize);
+        let mut pixels = Vec::with_capacity(SENSOR_WIDTH * SENSOR_HEIGHT);
+        for y in 0..SENSOR_HEIGHT {
+            for x in 0..SENSOR_WIDTH {
+                // Nearest neighbor scaling.
+                let offset = (y * height / SENSOR_HEIGHT) * info.line_size
+                    + (x * width / SENSOR_WIDTH) * samples;
+                pixels.push(get_luminance(&buffer[offset..offset + samples]));
+            }
+        }
+        Ok(Self { pixels })
+    }
+}
+
+impl CameraImageSource for PngImageSource {
+    fn capture(&mut self) -> Vec<u8> {
+        self.pixels.clone()
+    }
+}
+
+/// Converts a grayscale or RGB sample, with an optional alpha channel, to a brightness.
+fn get_luminance(sample: &[u8]) -> u8 {
+    match sample {
+        [gray] | [gray, _] => *gray,
+        [red, green, blue, ..] => {
+            ((*red as u32 * 299 + *green as u32 * 587 + *blue as u32 * 114) / 1000) as u8
+        }
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn png_image_is_scaled_to_sensor_size() {
+        let mut data = Vec::new();
+        {
+            let mut encoder = png::Encoder::new(&mut data, 2, 1);
+            encoder.set_color(png::ColorType::Rgb);
+            encoder.set_depth(png::BitDepth::Eight);
+            let mut writer = encoder.write_header().unwrap();
+            writer
+                .write_image_data(&[255, 255, 255, 255, 0, 0])
+                .unwrap();
+        }
+
+        let pixels = PngImageSource::decode(Cursor::new(data)).unwrap().capture();
+
+        assert_eq!(pixels.len(), SENSOR_WIDTH * SENSOR_HEIGHT);
+        assert_eq!(pixels[0], 255);
+        assert_eq!(pixels[SENSOR_WIDTH - 1], 76);
+        assert_eq!(pixels[(SENSOR_HEIGHT - 1) * SENSOR_WIDTH], 255);
+    }
+
+    #[test]
+    fn test_pattern_covers_the_sensor() {
+        let pixels = TestPatternSource {}.capture();
+
+        assert_eq!(pixels.len(), SENSOR_WIDTH * SENSOR_HEIGHT);
+        assert_eq!(pixels[0], 0);
+        assert_eq!(pixels[SENSOR_WIDTH - 1], 127);
+    }
+}
@@ -1,13 +1,23 @@
-use crate::memory::mbc::interface::MemoryBankController;
+use crate::archive::read_rom;
+use crate::camera::CameraSource;
+use crate::memory::mbc::huc1::HuC1BankController;
+use crate::memory::mbc::huc3::HuC3BankController;
+use crate::memory::mbc::interface::{MemoryBankController, ADDRESS_NINTENDO_LOGO, NINTENDO_LOGO};
 use crate::memory::mbc::mbc1::MBC1BankController;
+use crate::memory::mbc::mbc2::MBC2BankController;
 use crate::memory::mbc::mbc3::MBC3BankController;
+pub use crate::memory::mbc::mbc3::RtcMode;
+use crate::memory::mbc::mbc7::MBC7BankController;
+use crate::memory::mbc::mmm01::MMM01BankController;
 use crate::memory::mbc::no_controller::NoMemoryBankController;
+use crate::memory::mbc::pocket_camera::PocketCameraBankController;
+use crate::patch::{apply_patch, find_patch};
+use log::info;
 use macros::AddEnumName;
 use std::fmt::{Display, Formatter};
-use std::fs::File;
-use std::io::{BufReader, Read};
-use std::path::Path;
-use std::{error, fmt};
+use std::io::{ErrorKind, Read};
+use std::path::{Path, PathBuf};
+use std::{error, fmt, fs};
 use crate::memory::mbc::mbc5::MBC5BankController;
 
 /// Information from: https://gbdev.io/pandocs/The_Cartridge_Header.html#the-cartridge-header
@@ -15,38 +25,152 @@ const CARTRIDGE_HEADER_SIZE: usize = 0x14F + 1;
 const ADDRESS_TITLE: usize = 0x134;
 const SIZE_TITLE: usize = 0x10;
 const ADDRESS_CGB_FLAG: usize = 0x143;
+const ADDRESS_NEW_LICENSEE_CODE: usize = 0x144;
+const ADDRESS_SGB_FLAG: usize = 0x146;
 const ADDRESS_CARTRIDGE_TYPE: usize = 0x147;
 const ADDRESS_ROM_SIZE: usize = 0x148;
 const ADDRESS_RAM_SIZE: usize = 0x149;
+const ADDRESS_DESTINATION_CODE: usize = 0x14A;
+const ADDRESS_OLD_LICENSEE_CODE: usize = 0x14B;
+const ADDRESS_MASK_ROM_VERSION: usize = 0x14C;
 const ADDRESS_HEADER_CHECKSUM: usize = 0x14D;
+const ADDRESS_GLOBAL_CHECKSUM: usize = 0x14E;
+/// The old licensee code value telling that the new licensee code is used instead.
+const USE_NEW_LICENSEE_CODE: u8 = 0x33;
+const ROM_BANK_SIZE: usize = 0x4000;
+const SAVE_FILE_EXTENSION: &str = "sav";
+/// MMM01 cartridges boot from the menu stored in the last 32 KiB of the ROM.
+const MMM01_MENU_SIZE: usize = 0x8000;
+
+/// Options applied when loading a cartridge.
+#[derive(Clone, Debug, Default)]
+pub struct CartridgeOptions {
+    pub rtc_mode: RtcMode,
+    pub camera_source: CameraSource,
+    /// Rom to load from a zip archive, the first gb or gbc file when None.
+    pub archive_entry: Option<String>,
+    /// IPS, BPS or UPS patch applied to the rom, when None a patch named like the rom is used.
+    pub patch_path: Option<PathBuf>,
+}
 
 pub fn load_cartridge(path: &Path) -> Result<Cartridge, Box<dyn error::Error>> {
-    let file = File::open(path)?;
-    let mut reader = BufReader::new(file);
+    load_cartridge_with_options(path, &CartridgeOptions::default())
+}
 
-    let mut cartridge_header = [0u8; CARTRIDGE_HEADER_SIZE];
-    reader.read_exact(&mut cartridge_header)?;
+pub fn load_cartridge_with_options(
+    path: &Path,
+    options: &CartridgeOptions,
+) -> Result<Cartridge, Box<dyn error::Error>> {
+    // The battery save of a compressed rom is stored next to the archive.
+    let rom = read_rom(path, options.archive_entry.as_deref())?;
+    create_cartridge(&rom, Some(path), options)
+}
+
+/// Applies the patch of the options or the one found next to the rom.
+fn patch_rom(
+    rom: &[u8],
+    rom_path: Option<&Path>,
+    options: &CartridgeOptions,
+) -> Result<Option<Vec<u8>>, Box<dyn error::Error>> {
+    let patch_path = options
+        .patch_path
+        .clone()
+        .or_else(|| rom_path.and_then(find_patch));
+    let Some(patch_path) = patch_path else {
+        return Ok(None);
+    };
+    let patch = fs::read(&patch_path)?;
+    let rom = apply_patch(rom, &patch)
+        .map_err(|e| format!("Could not apply the patch {:?}: {}", patch_path, e))?;
+    info!("Applied the patch {:?}", patch_path);
+    Ok(Some(rom))
+}
 
-    let title = get_title(&cartridge_header)?;
-    let cgb_flag = get_cgb_flag(&cartridge_header);
-    let info = get_cartridge_info(&cartridge_header)?;
-    let rom_info = get_rom_size(&cartridge_header)?;
-    let ram_info = get_ram_size(&cartridge_header)?;
-    let header_checksum = get_header_checksum(&cartridge_header);
-    let computed_header_checksum = compute_header_checksum(&cartridge_header);
-    let memory_controller = get_memory_controller(&info, &mut reader, &rom_info, &ram_info)?;
+/// Creates the cartridge, the battery save is stored next to the rom path if there is one.
+fn create_cartridge(
+    rom: &[u8],
+    rom_path: Option<&Path>,
+    options: &CartridgeOptions,
+) -> Result<Cartridge, Box<dyn error::Error>> {
+    // The header is read from the patched rom.
+    let patched_rom = patch_rom(rom, rom_path, options)?;
+    let rom = patched_rom.as_deref().unwrap_or(rom);
+    let header = CartridgeHeader::from_rom(rom)?;
+    let cartridge_header = get_header_bytes(rom);
+
+    let title = get_title(cartridge_header)?;
+    let info = header
+        .info
+        .clone()
+        .ok_or_else(|| format!("Unknown cartridge type value {:?}", header.cartridge_type))?;
+    let rom_info = header
+        .rom_info
+        .clone()
+        .ok_or_else(|| format!("Unknown rom size value {:?}", header.rom_size_code))?;
+    let ram_info = header
+        .ram_info
+        .clone()
+        .ok_or_else(|| format!("Unknown ram size value {:?}", header.ram_size_code))?;
+    let mut memory_controller = get_memory_controller(&info, rom, &rom_info, &ram_info, options)?;
+
+    let save_path = rom_path
+        .filter(|_| info.battery)
+        .map(|path| path.with_extension(SAVE_FILE_EXTENSION));
+    if let Some(save_path) = &save_path {
+        load_battery_save(save_path, memory_controller.as_mut())?;
+    }
 
     Ok(Cartridge {
         title,
-        cgb_flag,
+        cgb_flag: header.cgb_flag,
         info,
         rom_info,
         ram_info,
-        valid_header_checksum: header_checksum == computed_header_checksum,
+        valid_header_checksum: header.valid_header_checksum,
+        header,
         memory_controller,
+        save_path,
     })
 }
 
+/// Returns the header describing the cartridge, the rom must be larger than the header.
+fn get_header_bytes(rom: &[u8]) -> &[u8] {
+    find_mmm01_menu_header(rom).unwrap_or(&rom[..CARTRIDGE_HEADER_SIZE])
+}
+
+/// The header in bank 0 of a MMM01 compilation describes the first game, the cartridge is
+/// described by the header of the menu, at the end of the ROM.
+/// Information from: https://gbdev.io/pandocs/MMM01.html
+fn find_mmm01_menu_header(rom: &[u8]) -> Option<&[u8]> {
+    let start = rom
+        .len()
+        .checked_sub(MMM01_MENU_SIZE)
+        .filter(|start| *start > 0)?;
+    let header = rom.get(start..start + CARTRIDGE_HEADER_SIZE)?;
+    is_mmm01_header(header).then_some(header)
+}
+
+fn is_mmm01_header(header: &[u8]) -> bool {
+    has_nintendo_logo(header) && matches!(header[ADDRESS_CARTRIDGE_TYPE], 0x0B..=0x0D)
+}
+
+fn has_nintendo_logo(header: &[u8]) -> bool {
+    header[ADDRESS_NINTENDO_LOGO..ADDRESS_NINTENDO_LOGO + NINTENDO_LOGO.len()] == NINTENDO_LOGO
+}
+
+/// Loads the battery save file if it exists, a missing file means that the game was never saved.
+fn load_battery_save(
+    save_path: &Path,
+    memory_controller: &mut dyn MemoryBankController,
+) -> Result<(), Box<dyn error::Error>> {
+    match fs::read(save_path) {
+        Ok(data) => memory_controller.load_battery(&data),
+        Err(error) if error.kind() == ErrorKind::NotFound => {}
+        Err(error) => return Err(error.into()),
+    }
+    Ok(())
+}
+
 pub struct Cartridge {
     pub title: String,
     pub cgb_flag: CGBFlag,
@@ -54,7 +178,126 @@ pub struct Cartridge {
     pub rom_info: ROMSizeInfo,
     pub ram_info: RAMSizeInfo,
     pub valid_header_checksum: bool,
+    /// All the header fields, read from the rom the cartridge was created from.
+    pub header: CartridgeHeader,
     pub memory_controller: Box<dyn MemoryBankController>,
+    /// Path of the battery save file, None if the cartridge has no battery or no rom path.
+    pub save_path: Option<PathBuf>,
+}
+
+impl Cartridge {
+    /// Creates a cartridge from a rom in memory, its battery save is not stored.
+    pub fn from_bytes(rom: Vec<u8>) -> Result<Self, Box<dyn error::Error>> {
+        Self::from_bytes_with_options(rom, &CartridgeOptions::default())
+    }
+
+    pub fn from_bytes_with_options(
+        rom: Vec<u8>,
+        options: &CartridgeOptions,
+    ) -> Result<Self, Box<dyn error::Error>> {
+        create_cartridge(&rom, None, options)
+    }
+
+    /// Creates a cartridge from the content of the reader after its current position, its battery
+    /// save is not stored.
+    pub fn from_reader(
+        reader: &mut impl Read,
+        options: &CartridgeOptions,
+    ) -> Result<Self, Box<dyn error::Error>> {
+        let mut rom = Vec::new();
+        reader.read_to_end(&mut rom)?;
+        Self::from_bytes_with_options(rom, options)
+    }
+}
+
+/// Header fields read from the raw rom, without creating a memory bank controller or applying
+/// a patch. Unknown type and size codes are kept as their values.
+/// Information from: https://gbdev.io/pandocs/The_Cartridge_Header.html
+#[derive(Clone, Debug)]
+pub struct CartridgeHeader {
+    /// Invalid UTF-8 sequences are replaced.
+    pub title: String,
+    /// The boot ROM locks up if the logo is not the Nintendo one.
+    pub valid_nintendo_logo: bool,
+    pub cgb_flag: CGBFlag,
+    /// The cartridge supports the SGB functions.
+    pub sgb_flag: bool,
+    /// Raw value of the cartridge type, described by `info` when it is known.
+    pub cartridge_type: u8,
+    pub info: Option<CartridgeInfo>,
+    pub rom_size_code: u8,
+    pub rom_info: Option<ROMSizeInfo>,
+    pub ram_size_code: u8,
+    pub ram_info: Option<RAMSizeInfo>,
+    /// Size of the rom, which can differ from the size declared in the header.
+    pub rom_file_size: usize,
+    pub old_licensee_code: u8,
+    /// Two ASCII characters, only used when the old licensee code is 0x33.
+    pub new_licensee_code: String,
+    pub destination: Destination,
+    pub mask_rom_version: u8,
+    pub header_checksum: u8,
+    pub valid_header_checksum: bool,
+    /// Not verified by the Game Boy.
+    pub global_checksum: u16,
+    pub valid_global_checksum: bool,
+}
+
+impl CartridgeHeader {
+    /// Reads the header of the rom, only fails when the rom is too small to contain one.
+    pub fn from_rom(rom: &[u8]) -> Result<Self, String> {
+        if rom.len() < CARTRIDGE_HEADER_SIZE {
+            return Err(format!(
+                "The rom is too small to contain a header: {} bytes",
+                rom.len()
+            ));
+        }
+        let header = get_header_bytes(rom);
+        let header_checksum = get_header_checksum(header);
+        let global_checksum = get_global_checksum(header);
+        Ok(Self {
+            title: String::from_utf8_lossy(get_title_bytes(header)).into_owned(),
+            valid_nintendo_logo: has_nintendo_logo(header),
+            cgb_flag: get_cgb_flag(header),
+            sgb_flag: header[ADDRESS_SGB_FLAG] == 0x03,
+            cartridge_type: header[ADDRESS_CARTRIDGE_TYPE],
+            info: get_cartridge_info(header),
+            rom_size_code: header[ADDRESS_ROM_SIZE],
+            rom_info: get_rom_size(header),
+            ram_size_code: header[ADDRESS_RAM_SIZE],
+            ram_info: get_ram_size(header),
+            rom_file_size: rom.len(),
+            old_licensee_code: header[ADDRESS_OLD_LICENSEE_CODE],
+            new_licensee_code: get_new_licensee_code(header),
+            destination: get_destination(header),
+            mask_rom_version: header[ADDRESS_MASK_ROM_VERSION],
+            header_checksum,
+            valid_header_checksum: header_checksum == compute_header_checksum(header),
+            global_checksum,
+            valid_global_checksum: global_checksum == compute_global_checksum(rom, global_checksum),
+        })
+    }
+
+    /// Returns the licensee code, from the new licensee field when the old one refers to it.
+    pub fn get_licensee_code(&self) -> String {
+        if self.old_licensee_code == USE_NEW_LICENSEE_CODE {
+            self.new_licensee_code.clone()
+        } else {
+            format!("{:02X}", self.old_licensee_code)
+        }
+    }
+
+    /// Returns the rom size declared in the header in bytes, None if the size code is unknown.
+    pub fn get_declared_rom_size(&self) -> Option<usize> {
+        self.rom_info
+            .as_ref()
+            .map(|rom_info| rom_info.num_banks * ROM_BANK_SIZE)
+    }
+
+    /// A rom with an unknown size code is reported as a mismatch.
+    pub fn has_rom_size_mismatch(&self) -> bool {
+        self.get_declared_rom_size() != Some(self.rom_file_size)
+    }
 }
 
 impl Display for Cartridge {
@@ -68,13 +311,17 @@ impl Display for Cartridge {
 }
 
 fn get_title(rom: &[u8]) -> Result<String, String> {
+    String::from_utf8(get_title_bytes(rom).to_vec())
+        .map_err(|e| format!("Title is badly formatted: {:?}", e))
+}
+
+fn get_title_bytes(rom: &[u8]) -> &[u8] {
     let complete_title = &rom[ADDRESS_TITLE..ADDRESS_TITLE + SIZE_TITLE];
     let end = complete_title
         .iter()
         .position(|b| b == &0)
         .unwrap_or(SIZE_TITLE);
-    String::from_utf8(complete_title[..end].to_vec())
-        .map_err(|e| format!("Title is badly formatted: {:?}", e))
+    &complete_title[..end]
 }
 
 #[derive(Clone, Debug, PartialEq, AddEnumName)]
@@ -82,9 +329,14 @@ pub enum MBCType {
     RomOnly,
     MBC1,
     MBC2,
+    MMM01,
     MBC3,
     MBC4,
     MBC5,
+    MBC7,
+    PocketCamera,
+    HuC1,
+    HuC3,
 }
 
 #[allow(dead_code)]
@@ -94,6 +346,7 @@ pub struct CartridgeInfo {
     pub sram: bool,
     pub battery: bool,
     pub rtc: bool,
+    pub rumble: bool,
 }
 
 impl CartridgeInfo {
@@ -103,36 +356,49 @@ impl CartridgeInfo {
             sram,
             battery,
             rtc,
+            rumble: false,
         }
     }
+
+    fn with_rumble(mut self) -> Self {
+        self.rumble = true;
+        self
+    }
 }
 
 /// Information from: https://gbdev.io/pandocs/The_Cartridge_Header.html#0147--cartridge-type
-fn get_cartridge_info(rom: &[u8]) -> Result<CartridgeInfo, String> {
+fn get_cartridge_info(rom: &[u8]) -> Option<CartridgeInfo> {
     match rom[ADDRESS_CARTRIDGE_TYPE] {
-        0x00 => Ok(CartridgeInfo::new(MBCType::RomOnly, false, false, false)), // ROM
-        0x01 => Ok(CartridgeInfo::new(MBCType::MBC1, false, false, false)),    // MBC1
-        0x02 => Ok(CartridgeInfo::new(MBCType::MBC1, true, false, false)),     // MBC1+RAM
-        0x03 => Ok(CartridgeInfo::new(MBCType::MBC1, true, true, false)),      // MBC1+RAM+BATT
-        0x05 => Ok(CartridgeInfo::new(MBCType::MBC2, false, false, false)),    // MBC2
-        0x06 => Ok(CartridgeInfo::new(MBCType::MBC2, false, true, false)),     // MBC2+BATTERY
-        0x08 => Ok(CartridgeInfo::new(MBCType::RomOnly, true, false, false)),  // ROM+RAM
-        0x09 => Ok(CartridgeInfo::new(MBCType::RomOnly, true, true, false)),   // ROM+RAM+BATTERY
-        0x0F => Ok(CartridgeInfo::new(MBCType::MBC3, false, true, true)),      // MBC3+TIMER+BATT
-        0x10 => Ok(CartridgeInfo::new(MBCType::MBC3, true, true, true)), // MBC3+TIMER+RAM+BATT
-        0x11 => Ok(CartridgeInfo::new(MBCType::MBC3, false, false, false)), // MBC3
-        0x12 => Ok(CartridgeInfo::new(MBCType::MBC3, true, false, false)), // MBC3+RAM
-        0x13 => Ok(CartridgeInfo::new(MBCType::MBC3, true, true, false)), // MBC3+RAM+BATT
-        0x15 => Ok(CartridgeInfo::new(MBCType::MBC4, false, false, false)), // MBC3
-        0x16 => Ok(CartridgeInfo::new(MBCType::MBC4, true, false, false)), // MBC3+RAM
-        0x17 => Ok(CartridgeInfo::new(MBCType::MBC4, true, true, false)), // MBC3+RAM+BATT
-        0x19 => Ok(CartridgeInfo::new(MBCType::MBC5, false, false, false)), // MBC5
-        0x1A => Ok(CartridgeInfo::new(MBCType::MBC5, true, false, false)), // MBC5+RAM
-        0x1B => Ok(CartridgeInfo::new(MBCType::MBC5, true, true, false)), // MBC5+RAM+BATT
-        0x1C => Ok(CartridgeInfo::new(MBCType::MBC5, false, false, false)), // MBC5+RUMBLE
-        0x1D => Ok(CartridgeInfo::new(MBCType::MBC5, true, false, false)), // MBC5+RUMBLE+RAM
-        0x1E => Ok(CartridgeInfo::new(MBCType::MBC5, true, true, false)), // MBC5+RUMBLE+RAM+BATT
-        value => Err(format!("Unknown cartridge type value {:?}", value)),
+        0x00 => Some(CartridgeInfo::new(MBCType::RomOnly, false, false, false)), // ROM
+        0x01 => Some(CartridgeInfo::new(MBCType::MBC1, false, false, false)),    // MBC1
+        0x02 => Some(CartridgeInfo::new(MBCType::MBC1, true, false, false)),     // MBC1+RAM
+        0x03 => Some(CartridgeInfo::new(MBCType::MBC1, true, true, false)),      // MBC1+RAM+BATT
+        0x05 => Some(CartridgeInfo::new(MBCType::MBC2, false, false, false)),    // MBC2
+        0x06 => Some(CartridgeInfo::new(MBCType::MBC2, false, true, false)),     // MBC2+BATTERY
+        0x08 => Some(CartridgeInfo::new(MBCType::RomOnly, true, false, false)),  // ROM+RAM
+        0x09 => Some(CartridgeInfo::new(MBCType::RomOnly, true, true, false)),   // ROM+RAM+BATTERY
+        0x0B => Some(CartridgeInfo::new(MBCType::MMM01, false, false, false)),   // MMM01
+        0x0C => Some(CartridgeInfo::new(MBCType::MMM01, true, false, false)),    // MMM01+RAM
+        0x0D => Some(CartridgeInfo::new(MBCType::MMM01, true, true, false)),     // MMM01+RAM+BATT
+        0x0F => Some(CartridgeInfo::new(MBCType::MBC3, false, true, true)),      // MBC3+TIMER+BATT
+        0x10 => Some(CartridgeInfo::new(MBCType::MBC3, true, true, true)), // MBC3+TIMER+RAM+BATT
+        0x11 => Some(CartridgeInfo::new(MBCType::MBC3, false, false, false)), // MBC3
+        0x12 => Some(CartridgeInfo::new(MBCType::MBC3, true, false, false)), // MBC3+RAM
+        0x13 => Some(CartridgeInfo::new(MBCType::MBC3, true, true, false)), // MBC3+RAM+BATT
+        0x15 => Some(CartridgeInfo::new(MBCType::MBC4, false, false, false)), // MBC3
+        0x16 => Some(CartridgeInfo::new(MBCType::MBC4, true, false, false)), // MBC3+RAM
+        0x17 => Some(CartridgeInfo::new(MBCType::MBC4, true, true, false)), // MBC3+RAM+BATT
+        0x19 => Some(CartridgeInfo::new(MBCType::MBC5, false, false, false)), // MBC5
+        0x1A => Some(CartridgeInfo::new(MBCType::MBC5, true, false, false)), // MBC5+RAM
+        0x1B => Some(CartridgeInfo::new(MBCType::MBC5, true, true, false)), // MBC5+RAM+BATT
+        0x1C => Some(CartridgeInfo::new(MBCType::MBC5, false, false, false).with_rumble()), // MBC5+RUMBLE
+        0x1D => Some(CartridgeInfo::new(MBCType::MBC5, true, false, false).with_rumble()), // MBC5+RUMBLE+RAM
+        0x1E => Some(CartridgeInfo::new(MBCType::MBC5, true, true, false).with_rumble()), // MBC5+RUMBLE+RAM+BATT
+        0x22 => Some(CartridgeInfo::new(MBCType::MBC7, false, true, false)), // MBC7+SENSOR+RUMBLE+RAM+BATT
+        0xFC => Some(CartridgeInfo::new(MBCType::PocketCamera, true, true, false)), // POCKET CAMERA
+        0xFE => Some(CartridgeInfo::new(MBCType::HuC3, true, true, true)), // HuC3
+        0xFF => Some(CartridgeInfo::new(MBCType::HuC1, true, true, false)), // HuC1+RAM+BATTERY
+        _ => None,
     }
 }
 
@@ -170,21 +436,21 @@ impl ROMSizeInfo {
 }
 
 /// Information from: https://gbdev.io/pandocs/The_Cartridge_Header.html#0147--cartridge-type
-fn get_rom_size(rom: &[u8]) -> Result<ROMSizeInfo, String> {
+fn get_rom_size(rom: &[u8]) -> Option<ROMSizeInfo> {
     match rom[ADDRESS_ROM_SIZE] {
-        0x00 => Ok(ROMSizeInfo::new(ROMSize::S32KB, 2)),
-        0x01 => Ok(ROMSizeInfo::new(ROMSize::S64KB, 4)),
-        0x02 => Ok(ROMSizeInfo::new(ROMSize::S128KB, 8)),
-        0x03 => Ok(ROMSizeInfo::new(ROMSize::S256KB, 16)),
-        0x04 => Ok(ROMSizeInfo::new(ROMSize::S512KB, 32)),
-        0x05 => Ok(ROMSizeInfo::new(ROMSize::S1MB, 64)),
-        0x06 => Ok(ROMSizeInfo::new(ROMSize::S2MB, 128)),
-        0x07 => Ok(ROMSizeInfo::new(ROMSize::S4MB, 256)),
-        0x08 => Ok(ROMSizeInfo::new(ROMSize::S8MB, 512)),
-        0x52 => Ok(ROMSizeInfo::new(ROMSize::S1MB100KB, 72)),
-        0x53 => Ok(ROMSizeInfo::new(ROMSize::S1MB200KB, 80)),
-        0x54 => Ok(ROMSizeInfo::new(ROMSize::S1MB500KB, 96)),
-        value => Err(format!("Unknown rom size value {:?}", value)),
+        0x00 => Some(ROMSizeInfo::new(ROMSize::S32KB, 2)),
+        0x01 => Some(ROMSizeInfo::new(ROMSize::S64KB, 4)),
+        0x02 => Some(ROMSizeInfo::new(ROMSize::S128KB, 8)),
+        0x03 => Some(ROMSizeInfo::new(ROMSize::S256KB, 16)),
+        0x04 => Some(ROMSizeInfo::new(ROMSize::S512KB, 32)),
+        0x05 => Some(ROMSizeInfo::new(ROMSize::S1MB, 64)),
+        0x06 => Some(ROMSizeInfo::new(ROMSize::S2MB, 128)),
+        0x07 => Some(ROMSizeInfo::new(ROMSize::S4MB, 256)),
+        0x08 => Some(ROMSizeInfo::new(ROMSize::S8MB, 512)),
+        0x52 => Some(ROMSizeInfo::new(ROMSize::S1MB100KB, 72)),
+        0x53 => Some(ROMSizeInfo::new(ROMSize::S1MB200KB, 80)),
+        0x54 => Some(ROMSizeInfo::new(ROMSize::S1MB500KB, 96)),
+        _ => None,
     }
 }
 
@@ -216,15 +482,15 @@ impl RAMSizeInfo {
 }
 
 /// Information from: https://gbdev.io/pandocs/The_Cartridge_Header.html#0147--cartridge-type
-fn get_ram_size(rom: &[u8]) -> Result<RAMSizeInfo, String> {
+fn get_ram_size(rom: &[u8]) -> Option<RAMSizeInfo> {
     match rom[ADDRESS_RAM_SIZE] {
-        0x00 => Ok(RAMSizeInfo::new(RAMSize::NONE, 0)),
-        0x01 => Ok(RAMSizeInfo::new(RAMSize::S2KB, 1)),
-        0x02 => Ok(RAMSizeInfo::new(RAMSize::S8KB, 1)),
-        0x03 => Ok(RAMSizeInfo::new(RAMSize::S32KB, 4)),
-        0x04 => Ok(RAMSizeInfo::new(RAMSize::S128KB, 16)),
-        0x05 => Ok(RAMSizeInfo::new(RAMSize::S64KB, 8)),
-        value => Err(format!("Unknown ram size value {:?}", value)),
+        0x00 => Some(RAMSizeInfo::new(RAMSize::NONE, 0)),
+        0x01 => Some(RAMSizeInfo::new(RAMSize::S2KB, 1)),
+        0x02 => Some(RAMSizeInfo::new(RAMSize::S8KB, 1)),
+        0x03 => Some(RAMSizeInfo::new(RAMSize::S32KB, 4)),
+        0x04 => Some(RAMSizeInfo::new(RAMSize::S128KB, 16)),
+        0x05 => Some(RAMSizeInfo::new(RAMSize::S64KB, 8)),
+        _ => None,
     }
 }
 
@@ -240,23 +506,93 @@ fn compute_header_checksum(rom: &[u8]) -> u8 {
     result
 }
 
+/// The checksum is stored in big endian.
+fn get_global_checksum(rom: &[u8]) -> u16 {
+    u16::from_be_bytes([
+        rom[ADDRESS_GLOBAL_CHECKSUM],
+        rom[ADDRESS_GLOBAL_CHECKSUM + 1],
+    ])
+}
+
+/// Sum of all the bytes of the rom, except the two bytes of the checksum itself.
+/// Information from: https://gbdev.io/pandocs/The_Cartridge_Header.html#014e-014f--global-checksum
+fn compute_global_checksum(rom: &[u8], global_checksum: u16) -> u16 {
+    let sum = rom
+        .iter()
+        .fold(0u16, |sum, value| sum.wrapping_add(*value as u16));
+    global_checksum
+        .to_be_bytes()
+        .iter()
+        .fold(sum, |sum, value| sum.wrapping_sub(*value as u16))
+}
+
+/// Information from: https://gbdev.io/pandocs/The_Cartridge_Header.html#01440145--new-licensee-code
+fn get_new_licensee_code(rom: &[u8]) -> String {
+    rom[ADDRESS_NEW_LICENSEE_CODE..ADDRESS_NEW_LICENSEE_CODE + 2]
+        .iter()
+        .map(|value| *value as char)
+        .collect()
+}
+
+/// Source: https://gbdev.io/pandocs/The_Cartridge_Header.html#014a--destination-code
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Destination {
+    Japan,
+    Overseas,
+    Unknown(u8),
+}
+
+fn get_destination(rom: &[u8]) -> Destination {
+    match rom[ADDRESS_DESTINATION_CODE] {
+        0x00 => Destination::Japan,
+        0x01 => Destination::Overseas,
+        value => Destination::Unknown(value),
+    }
+}
+
 fn get_memory_controller(
     info: &CartridgeInfo,
-    rom_reader: &mut BufReader<File>,
+    rom_data: &[u8],
     rom_info: &ROMSizeInfo,
     ram_info: &RAMSizeInfo,
+    options: &CartridgeOptions,
 ) -> Result<Box<dyn MemoryBankController>, String> {
     match &info.bank_type {
-        MBCType::RomOnly => NoMemoryBankController::create(rom_reader, ram_info.num_banks),
+        MBCType::RomOnly => NoMemoryBankController::create(rom_data, ram_info.num_banks),
         MBCType::MBC1 => {
-            MBC1BankController::create(rom_reader, rom_info.num_banks, ram_info.num_banks)
+            MBC1BankController::create(rom_data, rom_info.num_banks, ram_info.num_banks)
         }
-        MBCType::MBC3 => {
-            MBC3BankController::create(rom_reader, rom_info.num_banks, ram_info.num_banks)
+        MBCType::MBC2 => MBC2BankController::create(rom_data, rom_info.num_banks),
+        MBCType::MMM01 => {
+            MMM01BankController::create(rom_data, rom_info.num_banks, ram_info.num_banks)
         }
-        MBCType::MBC5 => {
-            MBC5BankController::create(rom_reader, rom_info.num_banks, ram_info.num_banks)
+        MBCType::MBC3 => MBC3BankController::create(
+            rom_data,
+            rom_info.num_banks,
+            ram_info.num_banks,
+            info.rtc.then_some(options.rtc_mode),
+        ),
+        MBCType::MBC5 => MBC5BankController::create(
+            rom_data,
+            rom_info.num_banks,
+            ram_info.num_banks,
+            info.rumble,
+        ),
+        MBCType::MBC7 => MBC7BankController::create(rom_data, rom_info.num_banks),
+        MBCType::PocketCamera => PocketCameraBankController::create(
+            rom_data,
+            rom_info.num_banks,
+            options.camera_source.create()?,
+        ),
+        MBCType::HuC1 => {
+            HuC1BankController::create(rom_data, rom_info.num_banks, ram_info.num_banks)
         }
+        MBCType::HuC3 => HuC3BankController::create(
+            rom_data,
+            rom_info.num_banks,
+            ram_info.num_banks,
+            options.rtc_mode,
+        ),
         value => Err(format!("Unsupported bank type {:?}", value.get_name())),
     }
 }
@@ -282,3 +618,105 @@ fn get_cgb_flag(rom: &[u8]) -> CGBFlag {
         _ => CGBFlag::NonCgbCompatible,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_header(cartridge_type: u8) -> [u8; CARTRIDGE_HEADER_SIZE] {
+        let mut header = [0u8; CARTRIDGE_HEADER_SIZE];
+        header[ADDRESS_NINTENDO_LOGO..ADDRESS_NINTENDO_LOGO + NINTENDO_LOGO.len()]
+            .copy_from_slice(&NINTENDO_LOGO);
+        header[ADDRESS_CARTRIDGE_TYPE] = cartridge_type;
+        header
+    }
+
+    #[test]
+    fn mmm01_header_needs_logo_and_mmm01_type() {
+        assert!(is_mmm01_header(&create_header(0x0B)));
+        assert!(is_mmm01_header(&create_header(0x0D)));
+        assert!(!is_mmm01_header(&create_header(0x01)));
+
+        let mut header = create_header(0x0C);
+        header[ADDRESS_NINTENDO_LOGO] = 0;
+        assert!(!is_mmm01_header(&header));
+    }
+
+    #[test]
+    fn truncated_rom_loads_from_bytes_and_readers() {
+        let mut rom = vec![0u8; 2 * 0x4000];
+        rom[..CARTRIDGE_HEADER_SIZE].copy_from_slice(&create_header(0x01));
+        // 128 KiB declared in the header.
+        rom[ADDRESS_ROM_SIZE] = 0x02;
+        rom[0x4000] = 0x42;
+
+        let cartridge = Cartridge::from_bytes(rom.clone()).unwrap();
+        assert_eq!(cartridge.rom_info.num_banks, 8);
+        assert!(cartridge.save_path.is_none());
+
+        // The rom is read from the current position, like in a container with a header before it.
+        let mut container = vec![0xFF; 0x10];
+        container.extend(&rom);
+        let mut reader = std::io::Cursor::new(container);
+        reader.set_position(0x10);
+        let mut cartridge = Cartridge::from_reader(&mut reader, &Default::default()).unwrap();
+        cartridge.memory_controller.write_rom(0x2000, 3);
+        assert_eq!(cartridge.memory_controller.read_rom(0x4000), 0x42);
+    }
+
+    #[test]
+    fn header_fields_and_checksums_are_read() {
+        let mut rom = vec![0u8; 2 * ROM_BANK_SIZE];
+        rom[..CARTRIDGE_HEADER_SIZE].copy_from_slice(&create_header(0x00));
+        rom[ADDRESS_NEW_LICENSEE_CODE..ADDRESS_NEW_LICENSEE_CODE + 2].copy_from_slice(b"01");
+        rom[ADDRESS_SGB_FLAG] = 0x03;
+        rom[ADDRESS_DESTINATION_CODE] = 0x01;
+        rom[ADDRESS_OLD_LICENSEE_CODE] = USE_NEW_LICENSEE_CODE;
+        rom[ADDRESS_MASK_ROM_VERSION] = 0x02;
+        rom[ADDRESS_HEADER_CHECKSUM] = compute_header_checksum(&rom);
+        rom[0x7FFF] = 0xFF;
+        let global_checksum = compute_global_checksum(&rom, 0);
+        rom[ADDRESS_GLOBAL_CHECKSUM..ADDRESS_GLOBAL_CHECKSUM + 2]
+            .copy_from_slice(&global_checksum.to_be_bytes());
+
+        let header = Cartridge::from_bytes(rom.clone()).unwrap().header;
+        assert!(header.valid_nintendo_logo);
+        assert!(header.sgb_flag);
+        assert_eq!(header.get_licensee_code(), "01");
+        assert_eq!(header.destination, Destination::Overseas);
+        assert_eq!(header.mask_rom_version, 0x02);
+        assert!(header.valid_header_checksum);
+        assert!(header.valid_global_checksum);
+        assert!(!header.has_rom_size_mismatch());
+
+        rom[0x7FFF] = 0;
+        rom.truncate(ROM_BANK_SIZE);
+        let header = CartridgeHeader::from_rom(&rom).unwrap();
+        assert!(!header.valid_global_checksum);
+        assert!(header.has_rom_size_mismatch());
+    }
+
+    #[test]
+    fn unknown_codes_are_reported_by_the_header() {
+        let mut rom = vec![0u8; 2 * ROM_BANK_SIZE];
+        rom[..CARTRIDGE_HEADER_SIZE].copy_from_slice(&create_header(0x42));
+        rom[ADDRESS_ROM_SIZE] = 0x20;
+        rom[ADDRESS_RAM_SIZE] = 0x07;
+
+        let header = CartridgeHeader::from_rom(&rom).unwrap();
+        assert_eq!(header.cartridge_type, 0x42);
+        assert!(header.info.is_none());
+        assert_eq!(header.rom_size_code, 0x20);
+        assert!(header.rom_info.is_none());
+        assert_eq!(header.ram_size_code, 0x07);
+        assert!(header.ram_info.is_none());
+        assert!(header.has_rom_size_mismatch());
+
+        assert!(Cartridge::from_bytes(rom).is_err());
+    }
+
+    #[test]
+    fn rom_without_header_is_an_error() {
+        assert!(Cartridge::from_bytes(vec![0; 0x100]).is_err());
+    }
+}
@@ -0,0 +1,253 @@
+//! Human and machine readable reports of the cartridge header.
+
+use crate::cartridge::CartridgeHeader;
+use std::fmt::Write;
+
+/// Returns a report of all the header fields, with the checksums and the rom size verified.
+pub fn format_report(header: &CartridgeHeader) -> String {
+    let mut report = String::new();
+    let mut line = |name: &str, value: String| {
+        writeln!(report, "{:<18}{}", format!("{}:", name), value).unwrap();
+    };
+    line("Title", header.title.clone());
+    line(
+        "Nintendo logo",
+        format_validity(header.valid_nintendo_logo).to_string(),
+    );
+    line("CGB flag", header.cgb_flag.get_name().to_string());
+    line(
+        "SGB flag",
+        (if header.sgb_flag { "yes" } else { "no" }).to_string(),
+    );
+    line(
+        "Cartridge type",
+        format!(
+            "0x{:02X} ({})",
+            header.cartridge_type,
+            get_features(header).join(", ")
+        ),
+    );
+    let declared_rom_size = match (header.get_declared_rom_size(), &header.rom_info) {
+        (Some(size), Some(rom_info)) => format!("{} bytes ({} banks)", size, rom_info.num_banks),
+        _ => format!("unknown (code 0x{:02X})", header.rom_size_code),
+    };
+    line(
+        "ROM size",
+        format!(
+            "{}, the file has {} bytes{}",
+            declared_rom_size,
+            header.rom_file_size,
+            if header.has_rom_size_mismatch() {
+                " (mismatch)"
+            } else {
+                ""
+            }
+        ),
+    );
+    line(
+        "RAM banks",
+        match &header.ram_info {
+            Some(ram_info) => ram_info.num_banks.to_string(),
+            None => format!("unknown (code 0x{:02X})", header.ram_size_code),
+        },
+    );
+    line("Licensee code", header.get_licensee_code());
+    line("Destination", format!("{:?}", header.destination));
+    line("Mask ROM version", header.mask_rom_version.to_string());
+    line(
+        "Header checksum",
+        format!(
+            "0x{:02X} ({})",
+            header.header_checksum,
+            format_validity(header.valid_header_checksum)
+        ),
+    );
+    line(
+        "Global checksum",
+        format!(
+            "0x{:04X} ({})",
+            header.global_checksum,
+            format_validity(header.valid_global_checksum)
+        ),
+    );
+    report
+}
+
+/// Returns the header fields as a JSON object, the fields decoded from unknown codes are null.
+pub fn format_json(header: &CartridgeHeader) -> String {
+    let info = header.info.as_ref();
+    let fields = [
+        ("title", format_json_string(&header.title)),
+        (
+            "valid_nintendo_logo",
+            header.valid_nintendo_logo.to_string(),
+        ),
+        ("cgb_flag", format_json_string(header.cgb_flag.get_name())),
+        ("sgb_flag", header.sgb_flag.to_string()),
+        ("cartridge_type", header.cartridge_type.to_string()),
+        (
+            "bank_type",
+            format_json_option(info.map(|info| format_json_string(info.bank_type.get_name()))),
+        ),
+        ("ram", format_json_option(info.map(|info| info.sram))),
+        ("battery", format_json_option(info.map(|info| info.battery))),
+        ("rtc", format_json_option(info.map(|info| info.rtc))),
+        ("rumble", format_json_option(info.map(|info| info.rumble))),
+        ("rom_size_code", header.rom_size_code.to_string()),
+        (
+            "rom_banks",
+            format_json_option(header.rom_info.as_ref().map(|rom_info| rom_info.num_banks)),
+        ),
+        (
+            "declared_rom_size",
+            format_json_option(header.get_declared_rom_size()),
+        ),
+        ("rom_file_size", header.rom_file_size.to_string()),
+        (
+            "rom_size_mismatch",
+            header.has_rom_size_mismatch().to_string(),
+        ),
+        ("ram_size_code", header.ram_size_code.to_string()),
+        (
+            "ram_banks",
+            format_json_option(header.ram_info.as_ref().map(|ram_info| ram_info.num_banks)),
+        ),
+        (
+            "licensee_code",
+            format_json_string(&header.get_licensee_code()),
+        ),
+        ("old_licensee_code", header.old_licensee_code.to_string()),
+        (
+            "new_licensee_code",
+            format_json_string(&header.new_licensee_code),
+        ),
+        (
+            "destination",
+            format_json_string(&format!("{:?}", header.destination)),
+        ),
+        ("mask_rom_version", header.mask_rom_version.to_string()),
+        ("header_checksum", header.header_checksum.to_string()),
+        (
+            "valid_header_checksum",
+            header.valid_header_checksum.to_string(),
+        ),
+        ("global_checksum", header.global_checksum.to_string()),
+        (
+            "valid_global_checksum",
+            header.valid_global_checksum.to_string(),
+        ),
+    ];
+    let fields: Vec<String> = fields
+        .iter()
+        .map(|(name, value)| format!("  \"{}\": {}", name, value))
+        .collect();
+    format!("{{\n{}\n}}", fields.join(",\n"))
+}
+
+fn get_features(header: &CartridgeHeader) -> Vec<&'static str> {
+    let Some(info) = &header.info else {
+        return vec!["unknown"];
+    };
+    let features = [
+        (info.sram, "RAM"),
+        (info.battery, "battery"),
+        (info.rtc, "RTC"),
+        (info.rumble, "rumble"),
+    ];
+    std::iter::once(info.bank_type.get_name())
+        .chain(
+            features
+                .iter()
+                .filter(|(enabled, _)| *enabled)
+                .map(|(_, name)| *name),
+        )
+        .collect()
+}
+
+fn format_validity(valid: bool) -> &'static str {
+    if valid {
+        "valid"
+    } else {
+        "invalid"
+    }
+}
+
+fn format_json_option(value: Option<impl ToString>) -> String {
+    value.map_or_else(|| "null".to_string(), |value| value.to_string())
+}
+
+fn format_json_string(value: &str) -> String {
+    let mut result = String::from("\"");
+    for character in value.chars() {
+        match character {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            character if character.is_control() => {
+                write!(result, "\\u{:04x}", character as u32).unwrap()
+            }
+            character => result.push(character),
+        }
+    }
+    result.push('"');
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 32 KiB MBC1 rom titled "TEST" without valid checksums.
+    fn create_rom() -> Vec<u8> {
+        let mut rom = vec![0u8; 0x8000];
+        rom[0x134..0x138].copy_from_slice(b"TEST");
+        rom[0x147] = 0x03;
+        rom
+    }
+
+    #[test]
+    fn json_strings_are_escaped() {
+        assert_eq!(format_json_string("A\"B\\C\n"), "\"A\\\"B\\\\C\\u000a\"");
+    }
+
+    #[test]
+    fn report_lists_the_header_fields() {
+        let header = CartridgeHeader::from_rom(&create_rom()).unwrap();
+        let report = format_report(&header);
+
+        assert!(report.contains("Title:            TEST\n"));
+        assert!(report.contains("Nintendo logo:    invalid\n"));
+        assert!(report.contains("Cartridge type:   0x03 (MBC1, RAM, battery)\n"));
+        assert!(
+            report.contains("ROM size:         32768 bytes (2 banks), the file has 32768 bytes\n")
+        );
+        assert!(report.contains("Licensee code:    00\n"));
+    }
+
+    #[test]
+    fn report_shows_unknown_codes_and_size_mismatch() {
+        let mut rom = create_rom();
+        rom[0x147] = 0x42;
+        rom[0x148] = 0x20;
+        rom.truncate(0x4000);
+        let report = format_report(&CartridgeHeader::from_rom(&rom).unwrap());
+
+        assert!(report.contains("Cartridge type:   0x42 (unknown)\n"));
+        assert!(report.contains(
+            "ROM size:         unknown (code 0x20), the file has 16384 bytes (mismatch)\n"
+        ));
+    }
+
+    #[test]
+    fn json_has_one_field_per_line() {
+        let mut rom = create_rom();
+        rom[0x149] = 0x07;
+        let json = format_json(&CartridgeHeader::from_rom(&rom).unwrap());
+
+        assert!(json.starts_with("{\n  \"title\": \"TEST\",\n"));
+        assert!(json.contains("\n  \"bank_type\": \"MBC1\",\n"));
+        assert!(json.contains("\n  \"rom_banks\": 2,\n"));
+        assert!(json.contains("\n  \"ram_size_code\": 7,\n"));
+        assert!(json.contains("\n  \"ram_banks\": null,\n"));
+        assert!(json.ends_with("\n  \"valid_global_checksum\": false\n}"));
+    }
+}
@@ -5,6 +5,9 @@ pub trait Debugger {
 
     /// Function handling the instruction breakpoints.
     fn handle_instruction(&mut self, opcode: u16, state: &mut EmulatorState);
+
+    /// Called once when an illegal opcode locks up the CPU.
+    fn handle_lock_up(&mut self, _state: &mut EmulatorState) {}
 }
 
 pub struct NoOpDebugger;
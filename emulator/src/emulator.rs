@@ -1,16 +1,21 @@
 use crate::cartridge::Cartridge;
 use crate::debugger::{Debugger, NoOpDebugger};
+use crate::fault::EmulationFault;
 use crate::generated::instructions::{get_instruction, ImmediateArgumentType};
-use crate::gui::Gui;
+use crate::gui::Frontend;
 use crate::interrupts::Interrupt;
-use crate::joypad::{InputProvider, JoypadState};
 use crate::memory::argument::Argument;
 use crate::memory::Memory;
+use crate::model::HardwareModel;
+use crate::serial::SerialDevice;
 use crate::state::EmulatorState;
 use crate::statistics::StatisticsRecorder;
 use crate::throttler::Throttler;
-use crate::video::renderer::{Color, Screen};
+use log::error;
 use std::convert::Into;
+use std::fmt::{Display, Formatter};
+use std::panic;
+use std::panic::AssertUnwindSafe;
 use std::sync::mpsc;
 use std::thread;
 use std::thread::JoinHandle;
@@ -18,48 +23,82 @@ use std::thread::JoinHandle;
 pub struct InstructionUpdate {
     pub nb_cycles: u64,
     pub update_frame: bool,
+    /// The CPU locked up while executing the instruction.
+    pub locked_up: bool,
 }
 
+/// Runs the next instruction, a panic while running it is returned as a fault.
+/// The state should not be used anymore after a fault.
 pub fn update_next_instruction(
     state: &mut EmulatorState,
-    gui: &mut impl Gui,
+    frontend: &mut impl Frontend,
+    debugger: &mut impl Debugger,
+) -> Result<InstructionUpdate, EmulationFault> {
+    panic::catch_unwind(AssertUnwindSafe(|| {
+        run_next_instruction(state, frontend, debugger)
+    }))
+    .map_err(EmulationFault::from_panic)
+}
+
+fn run_next_instruction(
+    state: &mut EmulatorState,
+    frontend: &mut impl Frontend,
     debugger: &mut impl Debugger,
 ) -> InstructionUpdate {
     let mut nb_cycles = 0u64;
+    let mut locked_up = false;
 
-    if let Some(interrupt) = state.memory.get_enabled_interrupt() {
-        if state.registers.ime_flag {
-            nb_cycles += handle_interrupt(state, interrupt);
+    if state.registers.locked_up {
+        // The interrupts are ignored and nothing is fetched, the rest of the console keeps running.
+        nb_cycles += 4;
+    } else {
+        if let Some(interrupt) = state.memory.get_enabled_interrupt() {
+            if state.registers.ime_flag {
+                nb_cycles += handle_interrupt(state, interrupt);
+            }
+            state.registers.halted = false;
         }
-        state.registers.halted = false;
-    }
 
-    if !state.registers.halted {
-        nb_cycles += fetch_and_execute(state, debugger);
-    } else {
-        // TODO: add handling when ime_flag is false and halted.
-        nb_cycles += 4; // TODO: confirm the number of cycles to spend during halt
+        if !state.registers.halted {
+            nb_cycles += fetch_and_execute(state, debugger);
+            if state.registers.locked_up {
+                debugger.handle_lock_up(state);
+                locked_up = true;
+            }
+        } else {
+            // TODO: add handling when ime_flag is false and halted.
+            nb_cycles += 4; // TODO: confirm the number of cycles to spend during halt
+        }
     }
 
     state.memory.update(nb_cycles);
+    if let Some(is_on) = state.memory.take_rumble_event() {
+        frontend.set_rumble(is_on);
+    }
     if state.memory.video.should_scanline() {
-        state.renderer.scanline(&state.memory.video, |x, y, color| {
-            gui.write_pixel(x, y, color)
-        });
+        state.renderer.scanline(&state.memory.video);
     }
     let mut update_frame = false;
     if state.memory.video.should_update_frame() {
-        gui.update_frame();
+        match state.memory.sgb.as_mut() {
+            Some(sgb) => {
+                sgb.update_frame(&state.memory.video, state.renderer.get_frame());
+                frontend.update_frame(sgb.get_frame());
+            }
+            None => frontend.update_frame(state.renderer.get_frame()),
+        }
         // Only update the inputs when a frame is completed to avoid polling too often.
-        gui.update_inputs();
+        frontend.update_inputs();
+        state.memory.set_tilt(frontend.get_tilt());
         update_frame = true;
     }
-    if state.memory.joypad.write_state(&gui.get_inputs()) {
+    if state.memory.joypad.write_state(&frontend.get_inputs()) {
         state.memory.set_interrupt_flag(Interrupt::Joypad);
     }
     InstructionUpdate {
         nb_cycles,
         update_frame,
+        locked_up,
     }
 }
 
@@ -126,36 +165,75 @@ fn fetch_and_execute(state: &mut EmulatorState, debugger: &mut impl Debugger) ->
     instruction(&mut state.registers, &mut state.memory, &argument)
 }
 
-pub struct ThreadedEmulator {
-    handle: JoinHandle<()>,
-    sender: mpsc::Sender<Action>,
+/// Events reported by the emulator thread to the frontend.
+#[derive(Debug, Clone, PartialEq)]
+pub enum EmulatorEvent {
+    /// The emulation of the game stopped.
+    Fault(EmulationFault),
+    /// An illegal opcode locked up the CPU, the screen keeps running until the next game starts.
+    CpuLockedUp { opcode: u8, address: u16 },
 }
 
-impl Default for ThreadedEmulator {
+impl Display for EmulatorEvent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Fault(fault) => write!(f, "Emulation stopped: {}", fault),
+            Self::CpuLockedUp { opcode, address } => write!(
+                f,
+                "CPU locked up by the illegal opcode 0x{:02X} at 0x{:04X}",
+                opcode, address
+            ),
+        }
+    }
+}
+
+/// Runs the emulator in its own thread, the frontend is moved to the thread with each game.
+pub struct ThreadedEmulator<F: Frontend + Send + 'static> {
+    handle: Option<JoinHandle<()>>,
+    sender: mpsc::Sender<Action<F>>,
+}
+
+impl<F: Frontend + Send + 'static> Default for ThreadedEmulator<F> {
     fn default() -> Self {
         Self::new()
     }
 }
 
 #[allow(dead_code)]
-impl ThreadedEmulator {
+impl<F: Frontend + Send + 'static> ThreadedEmulator<F> {
     pub fn new() -> Self {
         let (sender, receiver) = mpsc::channel();
         let handle = thread::spawn(move || {
             thread_loop(receiver);
         });
-        Self { handle, sender }
+        Self {
+            handle: Some(handle),
+            sender,
+        }
     }
-    pub fn start(&mut self, cartridge: Cartridge, screen: Box<dyn Screen>) {
-        if !self.handle.is_finished() {
+
+    fn is_running(&self) -> bool {
+        self.handle
+            .as_ref()
+            .is_some_and(|handle| !handle.is_finished())
+    }
+
+    pub fn start(&mut self, cartridge: Cartridge, frontend: F) {
+        let model = HardwareModel::default_for(cartridge.cgb_flag);
+        self.start_with_model(cartridge, model, frontend);
+    }
+
+    /// Starts the game on the given hardware, like a Super Game Boy.
+    pub fn start_with_model(&mut self, cartridge: Cartridge, model: HardwareModel, frontend: F) {
+        if self.is_running() {
             self.sender
-                .send(Action::Start((cartridge, screen)))
+                .send(Action::Start(Box::new((cartridge, model, frontend))))
                 .expect("Channel is invalid");
         }
     }
 
     pub fn pause(&mut self) {
-        if !self.handle.is_finished() {
+        if self.is_running() {
             self.sender
                 .send(Action::Pause())
                 .expect("Channel is invalid");
@@ -163,7 +241,7 @@ impl ThreadedEmulator {
     }
 
     pub fn resume(&mut self) {
-        if !self.handle.is_finished() {
+        if self.is_running() {
             self.sender
                 .send(Action::Resume())
                 .expect("Channel is invalid");
@@ -171,7 +249,7 @@ impl ThreadedEmulator {
     }
 
     pub fn stop(&mut self) -> bool {
-        if !self.handle.is_finished() {
+        if self.is_running() {
             self.sender
                 .send(Action::Stop())
                 .expect("Channel is invalid");
@@ -181,16 +259,27 @@ impl ThreadedEmulator {
         }
     }
 
-    pub fn update_inputs(&mut self, input: JoypadState) {
-        if !self.handle.is_finished() {
+    /// Plugs the device on the link cable, it stays plugged when another game is started.
+    pub fn set_serial_device(&mut self, device: Box<dyn SerialDevice>) {
+        if self.is_running() {
             self.sender
-                .send(Action::Inputs(input))
+                .send(Action::SerialDevice(device))
                 .expect("Channel is invalid");
         }
     }
 }
 
-fn thread_loop(receiver: mpsc::Receiver<Action>) {
+impl<F: Frontend + Send + 'static> Drop for ThreadedEmulator<F> {
+    /// Stops the emulator thread and waits for it so that the battery save gets written.
+    fn drop(&mut self) {
+        if let Some(handle) = self.handle.take() {
+            let _ = self.sender.send(Action::Stop());
+            let _ = handle.join();
+        }
+    }
+}
+
+fn thread_loop<F: Frontend>(receiver: mpsc::Receiver<Action<F>>) {
     let mut debugger = NoOpDebugger::new();
     let mut state = State::default();
     let mut throttler = Throttler::new();
@@ -199,6 +288,7 @@ fn thread_loop(receiver: mpsc::Receiver<Action>) {
         if let Ok(action) = receiver.recv() {
             update_state(&mut state, action)
         } else {
+            save_battery(&state);
             break;
         }
 
@@ -208,14 +298,31 @@ fn thread_loop(receiver: mpsc::Receiver<Action>) {
                 update_state(&mut state, action)
             }
             if state.input.should_quit {
+                save_battery(&state);
                 break 'main;
             }
             if state.input.is_paused || state.emulator.is_none() {
                 break 'running;
             }
-            let (emulator_state, screen) = &mut state.emulator.as_mut().unwrap();
-            let mut gui = GuiMiddleware::new(screen, &state.input);
-            let update = update_next_instruction(emulator_state, &mut gui, &mut debugger);
+            let (emulator_state, frontend) = &mut state.emulator.as_mut().unwrap();
+            let update = match update_next_instruction(emulator_state, frontend, &mut debugger) {
+                Ok(update) => update,
+                Err(fault) => {
+                    error!("Emulation stopped: {}", fault);
+                    frontend.notify(&EmulatorEvent::Fault(fault));
+                    // The link cable stays plugged for the next game.
+                    state.serial_device = Some(emulator_state.memory.take_serial_device());
+                    save_battery(&state);
+                    state.emulator = None;
+                    break 'running;
+                }
+            };
+            if update.locked_up {
+                let address = emulator_state.registers.pc;
+                let opcode = emulator_state.memory.read(address);
+                error!("CPU locked up by opcode 0x{:02X} at 0x{:04X}", opcode, address);
+                frontend.notify(&EmulatorEvent::CpuLockedUp { opcode, address });
+            }
 
             nb_cycles += update.nb_cycles;
             if update.update_frame {
@@ -227,13 +334,22 @@ fn thread_loop(receiver: mpsc::Receiver<Action>) {
     }
 }
 
-fn update_state(state: &mut State, action: Action) {
+fn update_state<F: Frontend>(state: &mut State<F>, action: Action<F>) {
     match action {
-        Action::Start((cartridge, screen)) => {
+        Action::Start(start) => {
+            let (cartridge, model, frontend) = *start;
+            save_battery(state);
             state.input.is_paused = false;
             state.input.should_quit = false;
-            state.input.joypad = Default::default();
-            state.emulator = Some((EmulatorState::new(cartridge), screen))
+            let mut emulator_state = EmulatorState::new_with_model(cartridge, model);
+            if let Some((previous_state, _)) = state.emulator.as_mut() {
+                emulator_state
+                    .memory
+                    .set_serial_device(previous_state.memory.take_serial_device());
+            } else if let Some(device) = state.serial_device.take() {
+                emulator_state.memory.set_serial_device(device);
+            }
+            state.emulator = Some((emulator_state, frontend))
         }
         Action::Pause() => {
             state.input.is_paused = true;
@@ -242,10 +358,19 @@ fn update_state(state: &mut State, action: Action) {
             state.input.is_paused = false;
         }
         Action::Stop() => {
-            state.input.should_quit = false;
+            state.input.should_quit = true;
         }
-        Action::Inputs(inputs) => {
-            state.input.joypad = inputs;
+        Action::SerialDevice(device) => match state.emulator.as_mut() {
+            Some((emulator_state, _)) => emulator_state.memory.set_serial_device(device),
+            None => state.serial_device = Some(device),
+        },
+    }
+}
+
+fn save_battery<F>(state: &State<F>) {
+    if let Some((emulator_state, _)) = &state.emulator {
+        if let Err(e) = emulator_state.save_battery() {
+            error!("Could not write the battery save: {:?}", e);
         }
     }
 }
@@ -254,50 +379,113 @@ fn update_state(state: &mut State, action: Action) {
 struct InputState {
     pub is_paused: bool,
     pub should_quit: bool,
-    pub joypad: JoypadState,
 }
 
-#[derive(Default)]
-struct State {
+struct State<F> {
     pub input: InputState,
-    pub emulator: Option<(EmulatorState, Box<dyn Screen>)>,
+    pub emulator: Option<(EmulatorState, F)>,
+    /// Device plugged on the link cable before a game is started.
+    pub serial_device: Option<Box<dyn SerialDevice>>,
 }
 
-enum Action {
-    Start((Cartridge, Box<dyn Screen>)),
+impl<F> Default for State<F> {
+    fn default() -> Self {
+        Self {
+            input: InputState::default(),
+            emulator: None,
+            serial_device: None,
+        }
+    }
+}
+
+enum Action<F> {
+    /// Boxed, the cartridge is much larger than the other actions.
+    Start(Box<(Cartridge, HardwareModel, F)>),
     Pause(),
     Resume(),
     Stop(),
-    Inputs(JoypadState),
+    SerialDevice(Box<dyn SerialDevice>),
 }
 
-struct GuiMiddleware<'a> {
-    screen: &'a mut Box<dyn Screen>,
-    state: &'a InputState,
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gui::FrameBuffer;
+    use crate::joypad::JoypadState;
+    use std::time::Duration;
 
-impl<'a> GuiMiddleware<'a> {
-    pub fn new(screen: &'a mut Box<dyn Screen>, state: &'a InputState) -> Self {
-        Self { screen, state }
+    struct EventFrontend {
+        events: mpsc::Sender<EmulatorEvent>,
+        panics_on_frame: bool,
     }
-}
 
-impl Screen for GuiMiddleware<'_> {
-    fn write_pixel(&mut self, x: usize, y: usize, color: &Color) {
-        self.screen.write_pixel(x, y, color);
-    }
+    impl Frontend for EventFrontend {
+        fn update_frame(&mut self, _frame: &FrameBuffer) {
+            if self.panics_on_frame {
+                panic!("Frame rejected");
+            }
+        }
+
+        fn update_inputs(&mut self) {}
+
+        fn get_inputs(&self) -> JoypadState {
+            JoypadState::default()
+        }
 
-    fn update_frame(&mut self) {
-        self.screen.update_frame();
+        fn notify(&mut self, event: &EmulatorEvent) {
+            self.events.send(event.clone()).unwrap();
+        }
     }
-}
 
-impl InputProvider for GuiMiddleware<'_> {
-    fn update_inputs(&mut self) {}
+    #[test]
+    fn threaded_emulator_notifies_the_frontend() {
+        let mut rom = vec![0u8; 0x8000];
+        // The illegal opcode 0xD3 locks up the CPU.
+        rom[0x100] = 0xD3;
+        let (sender, receiver) = mpsc::channel();
+        let mut emulator = ThreadedEmulator::new();
+
+        emulator.start(
+            Cartridge::from_bytes(rom).unwrap(),
+            EventFrontend {
+                events: sender,
+                panics_on_frame: false,
+            },
+        );
+
+        let event = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(
+            event,
+            EmulatorEvent::CpuLockedUp {
+                opcode: 0xD3,
+                address: 0x100
+            }
+        );
+    }
 
-    fn get_inputs(&self) -> JoypadState {
-        self.state.joypad.clone()
+    #[test]
+    fn threaded_emulator_reports_panics_as_faults() {
+        let mut rom = vec![0u8; 0x8000];
+        // JR -2
+        rom[0x100..0x102].copy_from_slice(&[0x18, 0xFE]);
+        let (sender, receiver) = mpsc::channel();
+        let mut emulator = ThreadedEmulator::new();
+
+        emulator.start(
+            Cartridge::from_bytes(rom).unwrap(),
+            EventFrontend {
+                events: sender,
+                panics_on_frame: true,
+            },
+        );
+
+        let event = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(
+            event,
+            EmulatorEvent::Fault(EmulationFault::Panic {
+                message: "Frame rejected".to_string()
+            })
+        );
+        assert!(emulator.stop());
     }
 }
-
-impl Gui for GuiMiddleware<'_> {}
@@ -0,0 +1,38 @@
+use std::any::Any;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+
+/// Error caused by the emulated program that stops the emulation instead of the whole process.
+///
+/// The guest behaviours known to crash are emulated like the hardware does: bank numbers are
+/// masked and illegal opcodes lock up the CPU. Any panic left in the emulation of an instruction is
+/// caught and reported as a fault.
+#[derive(Debug, Clone, PartialEq)]
+pub enum EmulationFault {
+    /// The emulator panicked while running an instruction.
+    Panic { message: String },
+}
+
+impl EmulationFault {
+    /// Creates the fault from the payload returned by `catch_unwind`.
+    pub(crate) fn from_panic(payload: Box<dyn Any + Send>) -> Self {
+        let message = match payload.downcast::<String>() {
+            Ok(message) => *message,
+            Err(payload) => payload.downcast_ref::<&str>().map_or_else(
+                || "unknown panic".to_string(),
+                |message| message.to_string(),
+            ),
+        };
+        Self::Panic { message }
+    }
+}
+
+impl Display for EmulationFault {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Panic { message } => write!(f, "Emulator panicked: {}", message),
+        }
+    }
+}
+
+impl Error for EmulationFault {}
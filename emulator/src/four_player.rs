@@ -0,0 +1,394 @@
+//! DMG-07 Four Player Adapter, connecting up to four consoles running in the same process.
+//!
+//! The adapter clocks every transfer: the consoles wait with their external clock and the adapter
+//! shifts one byte with all of them at once.
+//!
+//! During the ping phase, the adapter repeats a 4 bytes packet: 0xFE followed by three status
+//! bytes. The status has a bit per connected player in the upper nibble and the id of the
+//! receiving player in the lower bits. A console answers with ACK1 and ACK2 (0x88) to be marked
+//! as connected, then with the rate and the size of the packets. Player 1 starts the transmission
+//! phase by sending 0xAA, the adapter then sends 0xCC four times.
+//!
+//! During the transmission phase, every console sends its packet of `size` bytes at the start of
+//! a cycle of `4 * size` bytes. The adapter answers with the packets of all the players received
+//! during the previous cycle, player 1 first. Player 1 returns to the ping phase by sending a
+//! whole packet of 0xFF.
+//!
+//! Information from: https://gbdev.io/pandocs/Four_Player_Adapter.html
+
+use crate::debugger::NoOpDebugger;
+use crate::emulator::update_next_instruction;
+use crate::fault::EmulationFault;
+use crate::gui::Frontend;
+use crate::serial::SerialDevice;
+use crate::state::EmulatorState;
+use std::sync::{Arc, Mutex};
+
+pub const MAX_PLAYERS: usize = 4;
+const PING_HEADER: u8 = 0xFE;
+const ACK: u8 = 0x88;
+const START_TRANSMISSION: u8 = 0xAA;
+const TRANSMISSION_HEADER: u8 = 0xCC;
+const RESTART_PING: u8 = 0xFF;
+const PING_PACKET_SIZE: usize = 4;
+const MIN_PACKET_SIZE: usize = 1;
+const MAX_PACKET_SIZE: usize = 4;
+/// The adapter leaves enough time for a console to shift the byte and prepare the next one.
+/// The lower nibble of the rate slows down the transmission phase.
+const CYCLES_PER_BYTE: u64 = 8192;
+const CYCLES_PER_RATE_STEP: u64 = 1024;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AdapterPhase {
+    Ping,
+    /// The adapter sends the 0xCC bytes before the first transmission cycle.
+    StartTransmission,
+    Transmission,
+}
+
+struct AdapterState {
+    phase: AdapterPhase,
+    /// Position of the next byte in the current packet or cycle.
+    position: usize,
+    connected: [bool; MAX_PLAYERS],
+    /// Acknowledgments received during the current ping packet.
+    acks: [u8; MAX_PLAYERS],
+    start_requested: bool,
+    rate: u8,
+    packet_size: usize,
+    /// Packets received during the current transmission cycle, then sent during the next one.
+    received_packets: [[u8; MAX_PACKET_SIZE]; MAX_PLAYERS],
+    sent_packets: [[u8; MAX_PACKET_SIZE]; MAX_PLAYERS],
+    /// Byte offered by each console while it waits for the adapter to clock a transfer.
+    waiting: [Option<u8>; MAX_PLAYERS],
+    /// Byte received by each console from the last transfer.
+    received: [Option<u8>; MAX_PLAYERS],
+}
+
+impl AdapterState {
+    fn new() -> Self {
+        Self {
+            phase: AdapterPhase::Ping,
+            position: 0,
+            connected: [false; MAX_PLAYERS],
+            acks: [0; MAX_PLAYERS],
+            start_requested: false,
+            rate: 0,
+            packet_size: MIN_PACKET_SIZE,
+            received_packets: [[0; MAX_PACKET_SIZE]; MAX_PLAYERS],
+            sent_packets: [[0; MAX_PACKET_SIZE]; MAX_PLAYERS],
+            waiting: [None; MAX_PLAYERS],
+            received: [None; MAX_PLAYERS],
+        }
+    }
+
+    fn get_status(&self, player: usize) -> u8 {
+        let connected = self
+            .connected
+            .iter()
+            .enumerate()
+            .filter(|(_, connected)| **connected)
+            .fold(0, |status, (index, _)| status | (0x10 << index));
+        connected | (player as u8 + 1)
+    }
+
+    /// Returns the byte the adapter sends to the player at the current position.
+    fn get_outgoing(&self, player: usize) -> u8 {
+        match self.phase {
+            AdapterPhase::Ping if self.position == 0 => PING_HEADER,
+            AdapterPhase::Ping => self.get_status(player),
+            AdapterPhase::StartTransmission => TRANSMISSION_HEADER,
+            AdapterPhase::Transmission => {
+                let sender = self.position / self.packet_size;
+                self.sent_packets[sender][self.position % self.packet_size]
+            }
+        }
+    }
+
+    /// Handles the bytes sent by the players, then moves to the next position.
+    fn receive(&mut self, incoming: [u8; MAX_PLAYERS]) {
+        match self.phase {
+            AdapterPhase::Ping => self.receive_ping(incoming),
+            AdapterPhase::StartTransmission => {
+                self.position += 1;
+                if self.position == PING_PACKET_SIZE {
+                    self.position = 0;
+                    self.phase = AdapterPhase::Transmission;
+                }
+            }
+            AdapterPhase::Transmission => self.receive_transmission(incoming),
+        }
+    }
+
+    fn receive_ping(&mut self, incoming: [u8; MAX_PLAYERS]) {
+        if incoming[0] == START_TRANSMISSION {
+            self.start_requested = true;
+        }
+        match self.position {
+            0 => self.acks = incoming,
+            1 => {
+                for (player, ack) in self.acks.iter().enumerate() {
+                    self.connected[player] = *ack == ACK && incoming[player] == ACK;
+                }
+            }
+            2 => self.rate = incoming[0],
+            _ => self.packet_size = (incoming[0] as usize).clamp(MIN_PACKET_SIZE, MAX_PACKET_SIZE),
+        }
+        self.position += 1;
+        if self.position == PING_PACKET_SIZE {
+            self.position = 0;
+            if self.start_requested {
+                self.start_requested = false;
+                self.phase = AdapterPhase::StartTransmission;
+                self.received_packets = [[0; MAX_PACKET_SIZE]; MAX_PLAYERS];
+                self.sent_packets = [[0; MAX_PACKET_SIZE]; MAX_PLAYERS];
+            }
+        }
+    }
+
+    fn receive_transmission(&mut self, incoming: [u8; MAX_PLAYERS]) {
+        if self.position < self.packet_size {
+            for (packet, value) in self.received_packets.iter_mut().zip(incoming) {
+                packet[self.position] = value;
+            }
+        }
+        self.position += 1;
+        if self.position < MAX_PLAYERS * self.packet_size {
+            return;
+        }
+        self.position = 0;
+        let first_packet = &self.received_packets[0][..self.packet_size];
+        if first_packet.iter().all(|value| *value == RESTART_PING) {
+            self.phase = AdapterPhase::Ping;
+        } else {
+            self.sent_packets = self.received_packets;
+        }
+    }
+}
+
+/// The adapter itself, clocking the transfers of the plugged consoles.
+pub struct FourPlayerAdapter {
+    state: Arc<Mutex<AdapterState>>,
+}
+
+/// One of the four ports of the adapter, plugged in the serial port of a console.
+pub struct FourPlayerPort {
+    state: Arc<Mutex<AdapterState>>,
+    player: usize,
+}
+
+/// Creates an adapter and returns it with its four ports, player 1 first.
+pub fn create_four_player_adapter() -> (FourPlayerAdapter, [FourPlayerPort; MAX_PLAYERS]) {
+    let state = Arc::new(Mutex::new(AdapterState::new()));
+    let ports = [0, 1, 2, 3].map(|player| FourPlayerPort {
+        state: state.clone(),
+        player,
+    });
+    (FourPlayerAdapter { state }, ports)
+}
+
+impl FourPlayerAdapter {
+    /// Shifts the next byte with every port. A console that is not waiting for the transfer
+    /// misses the byte, and the adapter receives 0xFF from it.
+    pub fn clock(&mut self) {
+        let mut state = self.state.lock().unwrap();
+        let mut incoming = [0xFF; MAX_PLAYERS];
+        for (player, value) in incoming.iter_mut().enumerate() {
+            if let Some(waiting) = state.waiting[player].take() {
+                *value = waiting;
+                state.received[player] = Some(state.get_outgoing(player));
+            }
+        }
+        state.receive(incoming);
+    }
+
+    /// Returns the number of cycles between two bytes clocked by the adapter.
+    pub fn get_cycles_per_byte(&self) -> u64 {
+        let state = self.state.lock().unwrap();
+        match state.phase {
+            AdapterPhase::Transmission => {
+                CYCLES_PER_BYTE + (state.rate & 0x0F) as u64 * CYCLES_PER_RATE_STEP
+            }
+            _ => CYCLES_PER_BYTE,
+        }
+    }
+
+    pub fn get_phase(&self) -> AdapterPhase {
+        self.state.lock().unwrap().phase
+    }
+
+    /// Returns which players acknowledged the last ping packet.
+    pub fn get_connected_players(&self) -> [bool; MAX_PLAYERS] {
+        self.state.lock().unwrap().connected
+    }
+}
+
+impl SerialDevice for FourPlayerPort {
+    /// The adapter provides the clock, a console clocking a transfer receives nothing.
+    fn exchange(&mut self, _value: u8) -> u8 {
+        0xFF
+    }
+
+    fn poll_external(&mut self, value: u8) -> Option<u8> {
+        let mut state = self.state.lock().unwrap();
+        let received = state.received[self.player].take();
+        if received.is_none() {
+            state.waiting[self.player] = Some(value);
+        }
+        received
+    }
+
+    fn cancel_external(&mut self) {
+        self.state.lock().unwrap().waiting[self.player] = None;
+    }
+}
+
+/// Up to four consoles connected to the adapter, run in lockstep.
+///
+/// The console that is behind always runs the next instruction, and the adapter clocks a byte
+/// once every console reached its next transfer time.
+pub struct FourPlayerSession {
+    pub states: Vec<EmulatorState>,
+    pub adapter: FourPlayerAdapter,
+    cycles: Vec<u64>,
+    next_transfer_cycles: u64,
+    debugger: NoOpDebugger,
+}
+
+impl FourPlayerSession {
+    /// Plugs the consoles in the adapter, player 1 first. Returns an error without 2 to 4 consoles.
+    pub fn new(mut states: Vec<EmulatorState>) -> Result<Self, String> {
+        if !(2..=MAX_PLAYERS).contains(&states.len()) {
+            return Err(format!(
+                "The four player adapter needs 2 to {} consoles, got {}",
+                MAX_PLAYERS,
+                states.len()
+            ));
+        }
+        let (adapter, ports) = create_four_player_adapter();
+        for (state, port) in states.iter_mut().zip(ports) {
+            state.memory.set_serial_device(Box::new(port));
+        }
+        let nb_players = states.len();
+        Ok(Self {
+            states,
+            next_transfer_cycles: adapter.get_cycles_per_byte(),
+            adapter,
+            cycles: vec![0; nb_players],
+            debugger: NoOpDebugger::new(),
+        })
+    }
+
+    /// Returns the number of cycles run by each console.
+    pub fn get_cycles(&self) -> &[u64] {
+        &self.cycles
+    }
+
+    /// Runs the next instruction of the console that is behind, then clocks the adapter if its
+    /// next transfer is due.
+    pub fn step<F: Frontend>(&mut self, frontends: &mut [F]) -> Result<(), EmulationFault> {
+        let (index, _) = self
+            .cycles
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, cycles)| **cycles)
+            .unwrap();
+        let update = update_next_instruction(
+            &mut self.states[index],
+            &mut frontends[index],
+            &mut self.debugger,
+        )?;
+        self.cycles[index] += update.nb_cycles;
+
+        if *self.cycles.iter().min().unwrap() >= self.next_transfer_cycles {
+            self.adapter.clock();
+            self.next_transfer_cycles += self.adapter.get_cycles_per_byte();
+        }
+        Ok(())
+    }
+
+    /// Runs every console for at least the given number of cycles.
+    pub fn run_for<F: Frontend>(
+        &mut self,
+        nb_cycles: u64,
+        frontends: &mut [F],
+    ) -> Result<(), EmulationFault> {
+        let target = self.cycles.iter().min().unwrap() + nb_cycles;
+        while self.cycles.iter().any(|cycles| *cycles < target) {
+            self.step(frontends)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Clocks one byte with every port answering `values`, returns the bytes they received.
+    fn clock(
+        adapter: &mut FourPlayerAdapter,
+        ports: &mut [FourPlayerPort; MAX_PLAYERS],
+        values: [u8; MAX_PLAYERS],
+    ) -> [Option<u8>; MAX_PLAYERS] {
+        for (port, value) in ports.iter_mut().zip(values) {
+            assert_eq!(port.poll_external(value), None);
+        }
+        adapter.clock();
+        let mut received = [None; MAX_PLAYERS];
+        for ((port, value), received) in ports.iter_mut().zip(values).zip(received.iter_mut()) {
+            *received = port.poll_external(value);
+        }
+        received
+    }
+
+    #[test]
+    fn ping_packets_report_the_connected_players() {
+        let (mut adapter, mut ports) = create_four_player_adapter();
+        let answers = [[ACK, ACK, 0xFF, 0xFF], [ACK, 0x00, ACK, 0xFF]];
+        let mut received = vec![];
+        for _ in 0..2 {
+            for answer in answers {
+                received.push(clock(&mut adapter, &mut ports, answer));
+            }
+            for _ in 0..2 {
+                received.push(clock(&mut adapter, &mut ports, [0x00, 0xFF, 0xFF, 0xFF]));
+            }
+        }
+
+        assert_eq!(received[0], [Some(PING_HEADER); MAX_PLAYERS]);
+        assert_eq!(received[1][2], Some(0x03));
+        // Only player 1 sent both acknowledgments in the first packet.
+        assert_eq!(received[5][0], Some(0x11));
+        assert_eq!(received[6][3], Some(0x14));
+        assert_eq!(adapter.get_connected_players(), [true, false, false, false]);
+    }
+
+    #[test]
+    fn transmission_sends_the_packets_of_the_previous_cycle() {
+        let (mut adapter, mut ports) = create_four_player_adapter();
+        for value in [START_TRANSMISSION, START_TRANSMISSION, 0x00, 0x02] {
+            clock(&mut adapter, &mut ports, [value, ACK, ACK, ACK]);
+        }
+        assert_eq!(adapter.get_phase(), AdapterPhase::StartTransmission);
+        for _ in 0..PING_PACKET_SIZE {
+            let received = clock(&mut adapter, &mut ports, [0; MAX_PLAYERS]);
+            assert_eq!(received, [Some(TRANSMISSION_HEADER); MAX_PLAYERS]);
+        }
+        assert_eq!(adapter.get_phase(), AdapterPhase::Transmission);
+
+        let packets = [[0x10, 0x20, 0x30, 0x40], [0x11, 0x21, 0x31, 0x41]];
+        for packet in packets {
+            clock(&mut adapter, &mut ports, packet);
+        }
+        for _ in 2..8 {
+            clock(&mut adapter, &mut ports, [0; MAX_PLAYERS]);
+        }
+        let received: Vec<_> = (0..8)
+            .map(|_| clock(&mut adapter, &mut ports, [RESTART_PING; MAX_PLAYERS])[2])
+            .collect();
+        let expected = [0x10, 0x11, 0x20, 0x21, 0x30, 0x31, 0x40, 0x41].map(Some);
+        assert_eq!(received, expected);
+        assert_eq!(adapter.get_phase(), AdapterPhase::Ping);
+    }
+}
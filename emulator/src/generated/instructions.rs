@@ -2626,12 +2626,14 @@ pub fn jp_0d2(registers: &mut Registers, _memory: &mut dyn Memory, argument: &Ar
 
 /// 0xd3 UNKNOWN
 pub fn unknown_0d3(
-    _registers: &mut Registers,
+    registers: &mut Registers,
     _memory: &mut dyn Memory,
     _argument: &Argument,
 ) -> u64 {
     trace!("0xd3 UNKNOWN");
-    panic!("Unknown opcode 0xD3");
+    trace!("registers: (AF: 0x{:04X}, BC: 0x{:04X}, DE: 0x{:04X}, HL: 0x{:04X}, SP: 0x{:04X}, PC: 0x{:04X})",registers.get_af(), registers.get_bc(), registers.get_de(), registers.get_hl(), registers.sp, registers.pc);
+    registers.locked_up = true;
+    return 4u64;
 }
 
 /// 0xd4 CALL NC a16
@@ -2740,12 +2742,14 @@ pub fn jp_0da(registers: &mut Registers, _memory: &mut dyn Memory, argument: &Ar
 
 /// 0xdb UNKNOWN
 pub fn unknown_0db(
-    _registers: &mut Registers,
+    registers: &mut Registers,
     _memory: &mut dyn Memory,
     _argument: &Argument,
 ) -> u64 {
     trace!("0xdb UNKNOWN");
-    panic!("Unknown opcode 0xDB");
+    trace!("registers: (AF: 0x{:04X}, BC: 0x{:04X}, DE: 0x{:04X}, HL: 0x{:04X}, SP: 0x{:04X}, PC: 0x{:04X})",registers.get_af(), registers.get_bc(), registers.get_de(), registers.get_hl(), registers.sp, registers.pc);
+    registers.locked_up = true;
+    return 4u64;
 }
 
 /// 0xdc CALL C a16
@@ -2770,12 +2774,14 @@ pub fn call_0dc(registers: &mut Registers, memory: &mut dyn Memory, argument: &A
 
 /// 0xdd UNKNOWN
 pub fn unknown_0dd(
-    _registers: &mut Registers,
+    registers: &mut Registers,
     _memory: &mut dyn Memory,
     _argument: &Argument,
 ) -> u64 {
     trace!("0xdd UNKNOWN");
-    panic!("Unknown opcode 0xDD");
+    trace!("registers: (AF: 0x{:04X}, BC: 0x{:04X}, DE: 0x{:04X}, HL: 0x{:04X}, SP: 0x{:04X}, PC: 0x{:04X})",registers.get_af(), registers.get_bc(), registers.get_de(), registers.get_hl(), registers.sp, registers.pc);
+    registers.locked_up = true;
+    return 4u64;
 }
 
 /// 0xde SBC A d8
@@ -2847,22 +2853,26 @@ pub fn ldspecial_0e2(
 
 /// 0xe3 UNKNOWN
 pub fn unknown_0e3(
-    _registers: &mut Registers,
+    registers: &mut Registers,
     _memory: &mut dyn Memory,
     _argument: &Argument,
 ) -> u64 {
     trace!("0xe3 UNKNOWN");
-    panic!("Unknown opcode 0xE3");
+    trace!("registers: (AF: 0x{:04X}, BC: 0x{:04X}, DE: 0x{:04X}, HL: 0x{:04X}, SP: 0x{:04X}, PC: 0x{:04X})",registers.get_af(), registers.get_bc(), registers.get_de(), registers.get_hl(), registers.sp, registers.pc);
+    registers.locked_up = true;
+    return 4u64;
 }
 
 /// 0xe4 UNKNOWN
 pub fn unknown_0e4(
-    _registers: &mut Registers,
+    registers: &mut Registers,
     _memory: &mut dyn Memory,
     _argument: &Argument,
 ) -> u64 {
     trace!("0xe4 UNKNOWN");
-    panic!("Unknown opcode 0xE4");
+    trace!("registers: (AF: 0x{:04X}, BC: 0x{:04X}, DE: 0x{:04X}, HL: 0x{:04X}, SP: 0x{:04X}, PC: 0x{:04X})",registers.get_af(), registers.get_bc(), registers.get_de(), registers.get_hl(), registers.sp, registers.pc);
+    registers.locked_up = true;
+    return 4u64;
 }
 
 /// 0xe5 PUSH HL
@@ -2936,32 +2946,38 @@ pub fn ld_0ea(registers: &mut Registers, memory: &mut dyn Memory, argument: &Arg
 
 /// 0xeb UNKNOWN
 pub fn unknown_0eb(
-    _registers: &mut Registers,
+    registers: &mut Registers,
     _memory: &mut dyn Memory,
     _argument: &Argument,
 ) -> u64 {
     trace!("0xeb UNKNOWN");
-    panic!("Unknown opcode 0xEB");
+    trace!("registers: (AF: 0x{:04X}, BC: 0x{:04X}, DE: 0x{:04X}, HL: 0x{:04X}, SP: 0x{:04X}, PC: 0x{:04X})",registers.get_af(), registers.get_bc(), registers.get_de(), registers.get_hl(), registers.sp, registers.pc);
+    registers.locked_up = true;
+    return 4u64;
 }
 
 /// 0xec UNKNOWN
 pub fn unknown_0ec(
-    _registers: &mut Registers,
+    registers: &mut Registers,
     _memory: &mut dyn Memory,
     _argument: &Argument,
 ) -> u64 {
     trace!("0xec UNKNOWN");
-    panic!("Unknown opcode 0xEC");
+    trace!("registers: (AF: 0x{:04X}, BC: 0x{:04X}, DE: 0x{:04X}, HL: 0x{:04X}, SP: 0x{:04X}, PC: 0x{:04X})",registers.get_af(), registers.get_bc(), registers.get_de(), registers.get_hl(), registers.sp, registers.pc);
+    registers.locked_up = true;
+    return 4u64;
 }
 
 /// 0xed UNKNOWN
 pub fn unknown_0ed(
-    _registers: &mut Registers,
+    registers: &mut Registers,
     _memory: &mut dyn Memory,
     _argument: &Argument,
 ) -> u64 {
     trace!("0xed UNKNOWN");
-    panic!("Unknown opcode 0xED");
+    trace!("registers: (AF: 0x{:04X}, BC: 0x{:04X}, DE: 0x{:04X}, HL: 0x{:04X}, SP: 0x{:04X}, PC: 0x{:04X})",registers.get_af(), registers.get_bc(), registers.get_de(), registers.get_hl(), registers.sp, registers.pc);
+    registers.locked_up = true;
+    return 4u64;
 }
 
 /// 0xee XOR d8
@@ -3034,12 +3050,14 @@ pub fn di_0f3(registers: &mut Registers, _memory: &mut dyn Memory, _argument: &A
 
 /// 0xf4 UNKNOWN
 pub fn unknown_0f4(
-    _registers: &mut Registers,
+    registers: &mut Registers,
     _memory: &mut dyn Memory,
     _argument: &Argument,
 ) -> u64 {
     trace!("0xf4 UNKNOWN");
-    panic!("Unknown opcode 0xF4");
+    trace!("registers: (AF: 0x{:04X}, BC: 0x{:04X}, DE: 0x{:04X}, HL: 0x{:04X}, SP: 0x{:04X}, PC: 0x{:04X})",registers.get_af(), registers.get_bc(), registers.get_de(), registers.get_hl(), registers.sp, registers.pc);
+    registers.locked_up = true;
+    return 4u64;
 }
 
 /// 0xf5 PUSH AF
@@ -3123,22 +3141,26 @@ pub fn ei_0fb(registers: &mut Registers, _memory: &mut dyn Memory, _argument: &A
 
 /// 0xfc UNKNOWN
 pub fn unknown_0fc(
-    _registers: &mut Registers,
+    registers: &mut Registers,
     _memory: &mut dyn Memory,
     _argument: &Argument,
 ) -> u64 {
     trace!("0xfc UNKNOWN");
-    panic!("Unknown opcode 0xFC");
+    trace!("registers: (AF: 0x{:04X}, BC: 0x{:04X}, DE: 0x{:04X}, HL: 0x{:04X}, SP: 0x{:04X}, PC: 0x{:04X})",registers.get_af(), registers.get_bc(), registers.get_de(), registers.get_hl(), registers.sp, registers.pc);
+    registers.locked_up = true;
+    return 4u64;
 }
 
 /// 0xfd UNKNOWN
 pub fn unknown_0fd(
-    _registers: &mut Registers,
+    registers: &mut Registers,
     _memory: &mut dyn Memory,
     _argument: &Argument,
 ) -> u64 {
     trace!("0xfd UNKNOWN");
-    panic!("Unknown opcode 0xFD");
+    trace!("registers: (AF: 0x{:04X}, BC: 0x{:04X}, DE: 0x{:04X}, HL: 0x{:04X}, SP: 0x{:04X}, PC: 0x{:04X})",registers.get_af(), registers.get_bc(), registers.get_de(), registers.get_hl(), registers.sp, registers.pc);
+    registers.locked_up = true;
+    return 4u64;
 }
 
 /// 0xfe CP d8
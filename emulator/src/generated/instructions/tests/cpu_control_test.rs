@@ -82,3 +82,23 @@ fn test_ei() {
     assert_eq!(register, expected);
     assert_eq!(argument_type, ImmediateArgumentType::None);
 }
+
+#[test]
+fn test_illegal_opcodes_lock_up() {
+    for opcode in [0xD3, 0xDB, 0xDD, 0xE3, 0xE4, 0xEB, 0xEC, 0xED, 0xF4, 0xFC, 0xFD] {
+        let mut register = Registers::new();
+        let mut memory = FakeMemory::new();
+        let argument = Argument::new_empty();
+        let mut expected = register.clone();
+
+        let (instruction, argument_type) = get_instruction(opcode);
+        let nb_cycle = instruction(&mut register, &mut memory, &argument);
+
+        assert_eq!(nb_cycle, 4);
+
+        // The program counter stays on the opcode.
+        expected.locked_up = true;
+        assert_eq!(register, expected);
+        assert_eq!(argument_type, ImmediateArgumentType::None);
+    }
+}
@@ -1,6 +1,7 @@
 mod adc_test;
 mod add_test;
 mod and_tests;
+#[allow(clippy::precedence)]
 mod bit_operations_test;
 mod call_test;
 mod cp_test;
@@ -18,8 +19,10 @@ mod push_test;
 mod ret_test;
 mod rotate_test;
 mod rst_test;
+#[allow(clippy::unnecessary_cast)]
 mod sbc_test;
 mod shift_test;
+#[allow(clippy::unnecessary_cast)]
 mod sub_test;
 mod swap_test;
 mod xor_tests;
@@ -1,22 +1,39 @@
-use crate::cartridge::load_cartridge;
-use crate::emulator::ThreadedEmulator;
-use crate::joypad::JoypadState;
-use crate::video::renderer::{Color, Screen, SCREEN_HEIGHT, SCREEN_WIDTH};
+use crate::archive::{list_archive_roms, SUPPORTED_EXTENSIONS};
+use crate::cartridge::{load_cartridge_with_options, CartridgeOptions};
+use crate::emulator::{EmulatorEvent, ThreadedEmulator};
+use crate::gui::{FrameBuffer, Frontend};
+use crate::joypad::{JoypadState, Tilt};
+use crate::model::HardwareModel;
+use crate::printer::GameBoyPrinter;
+use crate::serial::NoSerialDevice;
+use crate::socket_link::{LinkMode, SocketLinkCable};
+use crate::video::renderer::{SCREEN_HEIGHT, SCREEN_WIDTH};
 use eframe::egui;
-use eframe::egui::{Color32, ColorImage, Key, TextureOptions};
-use std::path::PathBuf;
-use std::sync::Arc;
+use eframe::egui::{Color32, ColorImage, InputState, Key, Rect, TextureOptions, Vec2};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
 use std::thread;
 use std::thread::JoinHandle;
 
-pub fn run_emulator() -> eframe::Result {
+/// Maximum offset in points of the screen when the rumble motor is on.
+const RUMBLE_SHAKE_OFFSET: f32 = 3.0;
+/// Number of screen shake direction changes per second.
+const RUMBLE_SHAKE_FREQUENCY: f64 = 30.0;
+const DEFAULT_LINK_ADDRESS: &str = "127.0.0.1:8765";
+/// Directory of the Game Boy Printer images, relative to the working directory.
+const PRINTS_DIRECTORY: &str = "prints";
+
+/// Runs the emulator window, the link cable is opened when the window starts if requested.
+pub fn run_emulator(link: Option<LinkMode>) -> eframe::Result {
     let options = eframe::NativeOptions {
         ..Default::default()
     };
     eframe::run_native(
         "GameBoy emulator",
         options,
-        Box::new(|_| Ok(Box::new(GBEmulatorApp::new()))),
+        Box::new(|_| Ok(Box::new(GBEmulatorApp::new(link)))),
     )
 }
 
@@ -27,20 +44,56 @@ enum AppDialogAnswer {
     OpenFile(Option<PathBuf>),
 }
 
+/// Link cable being opened in the background, without blocking the window.
+struct PendingLink {
+    handle: JoinHandle<io::Result<SocketLinkCable>>,
+    is_cancelled: Arc<AtomicBool>,
+}
+
 #[derive()]
 struct GBEmulatorApp {
     texture: Option<egui::TextureHandle>,
-    emulator: ThreadedEmulator,
+    emulator: ThreadedEmulator<AppFrontend>,
     dialog: Option<JoinHandle<AppDialogAnswer>>,
+    is_rumble_on: Arc<AtomicBool>,
+    /// Inputs read by the frontend of the emulator thread once per frame.
+    inputs: Arc<Mutex<AppInputs>>,
+    event_sender: mpsc::Sender<EmulatorEvent>,
+    events: mpsc::Receiver<EmulatorEvent>,
+    screen_rect: Option<Rect>,
+    event: Option<EmulatorEvent>,
+    /// Zip archive with several roms waiting for the user to choose one.
+    archive_roms: Option<(PathBuf, Vec<String>)>,
+    link: Option<PendingLink>,
+    link_address: String,
+    link_status: String,
+    /// Runs the games supporting it on a Super Game Boy.
+    use_super_game_boy: bool,
 }
 
 impl GBEmulatorApp {
-    fn new() -> Self {
-        Self {
+    fn new(link: Option<LinkMode>) -> Self {
+        let (event_sender, events) = mpsc::channel();
+        let mut app = Self {
             texture: Default::default(),
             emulator: ThreadedEmulator::new(),
             dialog: None,
+            is_rumble_on: Default::default(),
+            inputs: Default::default(),
+            event_sender,
+            events,
+            screen_rect: None,
+            event: None,
+            archive_roms: None,
+            link: None,
+            link_address: DEFAULT_LINK_ADDRESS.to_string(),
+            link_status: "Not connected".to_string(),
+            use_super_game_boy: false,
+        };
+        if let Some(link) = link {
+            app.open_link_cable(link);
         }
+        app
     }
 }
 
@@ -49,11 +102,19 @@ impl eframe::App for GBEmulatorApp {
         if self
             .dialog
             .as_ref()
-            .map_or(false, |handle| handle.is_finished())
+            .is_some_and(|handle| handle.is_finished())
         {
             let handle = self.dialog.take().unwrap();
             self.handle_dialog_answer(handle);
         }
+        if self
+            .link
+            .as_ref()
+            .is_some_and(|link| link.handle.is_finished())
+        {
+            let link = self.link.take().unwrap();
+            self.handle_link_answer(link);
+        }
 
         ctx.input(|state| {
             let joypad = JoypadState {
@@ -66,11 +127,18 @@ impl eframe::App for GBEmulatorApp {
                 select: state.modifiers.shift,
                 start: state.key_down(Key::Enter),
             };
-            self.emulator.update_inputs(joypad);
+            let tilt = self.get_tilt(state);
+            *self.inputs.lock().unwrap() = AppInputs { joypad, tilt };
         });
 
+        if let Ok(event) = self.events.try_recv() {
+            self.event = Some(event);
+        }
+
         self.add_top_panel(ctx);
         self.add_central_panel(ctx);
+        self.add_event_window(ctx);
+        self.add_archive_window(ctx);
     }
 }
 
@@ -83,6 +151,7 @@ impl GBEmulatorApp {
 
             egui::menu::bar(ui, |ui| {
                 ui.menu_button("File", |ui| self.menu_file_buttons(ui));
+                ui.menu_button("Link", |ui| self.menu_link_buttons(ui));
                 ui.add_space(16.0);
 
                 egui::widgets::global_theme_preference_buttons(ui);
@@ -101,7 +170,10 @@ impl GBEmulatorApp {
             });
 
             if let Some(texture) = self.texture.as_ref() {
-                ui.image((texture.id(), ui.available_size()));
+                let rect = ui.available_rect_before_wrap();
+                let rect = rect.translate(self.get_rumble_offset(ctx));
+                egui::Image::new((texture.id(), rect.size())).paint_at(ui, rect);
+                self.screen_rect = Some(rect);
             }
 
             // TODO: Maybe only request repaint when the image changed
@@ -109,16 +181,177 @@ impl GBEmulatorApp {
         });
     }
 
+    fn add_event_window(&mut self, ctx: &egui::Context) {
+        let Some(event) = self.event.as_ref() else {
+            return;
+        };
+        let mut is_closed = false;
+        egui::Window::new("Emulator")
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label(event.to_string());
+                is_closed = ui.button("Ok").clicked();
+            });
+        if is_closed {
+            self.event = None;
+        }
+    }
+
+    fn add_archive_window(&mut self, ctx: &egui::Context) {
+        let Some((path, roms)) = self.archive_roms.as_ref() else {
+            return;
+        };
+        let mut selected = None;
+        let mut is_cancelled = false;
+        egui::Window::new("Select ROM")
+            .collapsible(false)
+            .show(ctx, |ui| {
+                for rom in roms {
+                    if ui.button(rom).clicked() {
+                        selected = Some(rom.clone());
+                    }
+                }
+                ui.separator();
+                is_cancelled = ui.button("Cancel").clicked();
+            });
+        if let Some(rom) = selected {
+            let path = path.clone();
+            self.archive_roms = None;
+            let options = CartridgeOptions {
+                archive_entry: Some(rom),
+                ..Default::default()
+            };
+            self.start_cartridge(&path, &options);
+        } else if is_cancelled {
+            self.archive_roms = None;
+        }
+    }
+
     fn menu_file_buttons(&mut self, ui: &mut egui::Ui) {
         if ui.button("Open Rom").clicked() && self.dialog.is_none() {
             self.dialog = Some(thread::spawn(|| {
                 let answer = rfd::FileDialog::new()
                     .set_title("Select ROM to Play")
-                    .add_filter("ROM", &["gb", "gbc", "gba"])
+                    .add_filter("ROM", &SUPPORTED_EXTENSIONS)
                     .pick_file();
                 AppDialogAnswer::OpenFile(answer)
             }));
         }
+        ui.checkbox(&mut self.use_super_game_boy, "Super Game Boy");
+    }
+
+    fn menu_link_buttons(&mut self, ui: &mut egui::Ui) {
+        ui.label(&self.link_status);
+        ui.horizontal(|ui| {
+            ui.label("Address");
+            ui.text_edit_singleline(&mut self.link_address);
+        });
+        if self.link.is_some() {
+            if ui.button("Cancel").clicked() {
+                self.cancel_link_cable();
+                ui.close_menu();
+            }
+        } else {
+            if ui.button("Listen").clicked() {
+                self.open_link_cable(LinkMode::Listen(self.link_address.clone()));
+                ui.close_menu();
+            }
+            if ui.button("Connect").clicked() {
+                self.open_link_cable(LinkMode::Connect(self.link_address.clone()));
+                ui.close_menu();
+            }
+        }
+        ui.separator();
+        if ui.button("Plug Game Boy Printer").clicked() {
+            self.cancel_link_cable();
+            let printer = GameBoyPrinter::new(Path::new(PRINTS_DIRECTORY));
+            self.emulator.set_serial_device(Box::new(printer));
+            self.link_status = format!("Game Boy Printer saving to {:?}", PRINTS_DIRECTORY);
+            ui.close_menu();
+        }
+        if ui.button("Disconnect").clicked() {
+            self.cancel_link_cable();
+            self.emulator
+                .set_serial_device(Box::new(NoSerialDevice::default()));
+            self.link_status = "Not connected".to_string();
+            ui.close_menu();
+        }
+    }
+
+    /// Opens the link cable in the background, the window stays usable until the other emulator
+    /// connects.
+    fn open_link_cable(&mut self, link: LinkMode) {
+        if self.link.is_some() {
+            return;
+        }
+        self.link_status = match &link {
+            LinkMode::Listen(address) => format!("Waiting for a connection on {}", address),
+            LinkMode::Connect(address) => format!("Connecting to {}", address),
+        };
+        let is_cancelled = Arc::new(AtomicBool::new(false));
+        let thread_is_cancelled = is_cancelled.clone();
+        self.link = Some(PendingLink {
+            handle: thread::spawn(move || link.open(&thread_is_cancelled)),
+            is_cancelled,
+        });
+    }
+
+    /// Stops waiting for the link cable. A connection opened after the cancellation is dropped
+    /// when the background thread ends.
+    fn cancel_link_cable(&mut self) {
+        if let Some(link) = self.link.take() {
+            link.is_cancelled.store(true, Ordering::Relaxed);
+            self.link_status = "Not connected".to_string();
+        }
+    }
+
+    fn handle_link_answer(&mut self, link: PendingLink) {
+        match link.handle.join() {
+            Ok(Ok(cable)) => {
+                self.link_status = "Connected".to_string();
+                self.emulator.set_serial_device(Box::new(cable));
+            }
+            Ok(Err(e)) => self.link_status = format!("Link cable error: {}", e),
+            Err(_) => self.link_status = "Link cable error".to_string(),
+        }
+    }
+
+    /// The tilt is controlled with the I, J, K, L keys or by dragging the mouse on the screen.
+    fn get_tilt(&self, state: &InputState) -> Tilt {
+        let mut tilt = Tilt::default();
+        if let (Some(rect), Some(position)) = (self.screen_rect, state.pointer.interact_pos()) {
+            if state.pointer.primary_down() && rect.contains(position) {
+                let offset = (position - rect.center()) / (rect.size() / 2.0);
+                tilt = Tilt {
+                    x: offset.x,
+                    y: offset.y,
+                };
+            }
+        }
+        if state.key_down(Key::J) {
+            tilt.x = -1.0;
+        }
+        if state.key_down(Key::L) {
+            tilt.x = 1.0;
+        }
+        if state.key_down(Key::I) {
+            tilt.y = -1.0;
+        }
+        if state.key_down(Key::K) {
+            tilt.y = 1.0;
+        }
+        tilt
+    }
+
+    /// Shakes the screen horizontally while the rumble motor is on.
+    fn get_rumble_offset(&self, ctx: &egui::Context) -> Vec2 {
+        if !self.is_rumble_on.load(Ordering::Relaxed) {
+            return Vec2::ZERO;
+        }
+        let step = ctx.input(|state| (state.time * RUMBLE_SHAKE_FREQUENCY) as i64);
+        let direction = if step % 2 == 0 { 1.0 } else { -1.0 };
+        Vec2::new(direction * RUMBLE_SHAKE_OFFSET, 0.0)
     }
 
     fn is_dialog_open(&self) -> bool {
@@ -127,38 +360,84 @@ impl GBEmulatorApp {
 
     fn handle_dialog_answer(&mut self, handle: JoinHandle<AppDialogAnswer>) {
         if let Ok(AppDialogAnswer::OpenFile(Some(path))) = handle.join() {
-            if let (Some(handle), Ok(cartridge)) =
-                (&self.texture, load_cartridge(path.as_path()))
-            {
-                self.emulator
-                    .start(cartridge, Box::new(AppScreen::new(handle.clone())));
+            match list_archive_roms(&path) {
+                Ok(roms) if roms.len() > 1 => self.archive_roms = Some((path, roms)),
+                _ => self.start_cartridge(&path, &CartridgeOptions::default()),
             }
         };
     }
+
+    fn start_cartridge(&mut self, path: &Path, options: &CartridgeOptions) {
+        if let (Some(handle), Ok(cartridge)) =
+            (&self.texture, load_cartridge_with_options(path, options))
+        {
+            self.is_rumble_on.store(false, Ordering::Relaxed);
+            self.event = None;
+            // The CGB games run in CGB mode, even when they also support the Super Game Boy.
+            let model = if self.use_super_game_boy
+                && cartridge.header.sgb_flag
+                && !cartridge.cgb_flag.use_cgb()
+            {
+                HardwareModel::Sgb
+            } else {
+                HardwareModel::default_for(cartridge.cgb_flag)
+            };
+            let frontend = AppFrontend {
+                texture: handle.clone(),
+                is_rumble_on: self.is_rumble_on.clone(),
+                shared_inputs: self.inputs.clone(),
+                inputs: AppInputs::default(),
+                events: self.event_sender.clone(),
+            };
+            self.emulator.start_with_model(cartridge, model, frontend);
+        }
+    }
+}
+
+#[derive(Clone, Default)]
+struct AppInputs {
+    joypad: JoypadState,
+    tilt: Tilt,
 }
 
-struct AppScreen {
-    image: Arc<ColorImage>,
+/// Frontend moved to the emulator thread, sharing the screen texture, the inputs, the rumble and
+/// the events with the window.
+struct AppFrontend {
     texture: egui::TextureHandle,
+    is_rumble_on: Arc<AtomicBool>,
+    shared_inputs: Arc<Mutex<AppInputs>>,
+    inputs: AppInputs,
+    events: mpsc::Sender<EmulatorEvent>,
 }
 
-impl Screen for AppScreen {
-    fn write_pixel(&mut self, x: usize, y: usize, color: &Color) {
-        Arc::make_mut(&mut self.image)[(x, y)] =
-            Color32::from_rgba_unmultiplied(color.red, color.green, color.blue, color.alpha);
+impl Frontend for AppFrontend {
+    fn update_frame(&mut self, frame: &FrameBuffer) {
+        let image = ColorImage::from_rgba_unmultiplied(
+            [frame.get_width(), frame.get_height()],
+            frame.get_rgba(),
+        );
+        self.texture.set(image, TextureOptions::NEAREST);
     }
 
-    fn update_frame(&mut self) {
-        self.texture.set(self.image.clone(), TextureOptions::NEAREST);
+    fn update_inputs(&mut self) {
+        self.inputs = self.shared_inputs.lock().unwrap().clone();
     }
-}
 
-impl AppScreen {
-    fn new(texture: egui::TextureHandle) -> Self {
-        Self {
-            image: default_gb_image(),
-            texture,
-        }
+    fn get_inputs(&self) -> JoypadState {
+        self.inputs.joypad.clone()
+    }
+
+    fn get_tilt(&self) -> Tilt {
+        self.inputs.tilt
+    }
+
+    fn set_rumble(&mut self, is_on: bool) {
+        self.is_rumble_on.store(is_on, Ordering::Relaxed);
+    }
+
+    fn notify(&mut self, event: &EmulatorEvent) {
+        // The window may already be closed, nobody is left to report the event to.
+        let _ = self.events.send(event.clone());
     }
 }
 
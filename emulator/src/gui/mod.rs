@@ -1,44 +1,107 @@
-use crate::joypad::{InputProvider, JoypadState};
-use crate::video::renderer::{Color, Screen};
+use crate::emulator::EmulatorEvent;
+use crate::gui::script::TiltScript;
+use crate::joypad::JoypadState;
+use log::info;
 
 pub mod eframe;
+pub mod script;
 #[cfg(feature = "sdl2-ui")]
 pub(crate) mod sdl2;
 
-pub trait Gui: Screen + InputProvider {}
+pub use crate::joypad::Tilt;
+pub use crate::video::framebuffer::FrameBuffer;
+pub use crate::video::renderer::Color;
 
-pub struct NoOpGui {}
+/// Stereo sample, each channel in the range [-1.0, 1.0].
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct AudioSample {
+    pub left: f32,
+    pub right: f32,
+}
+
+/// Everything the emulator exchanges with the outside world: video frames, audio samples, inputs,
+/// rumble and status notifications.
+///
+/// The core only talks to this trait, so a new frontend only has to implement it. Only the video
+/// and the inputs are mandatory.
+pub trait Frontend {
+    /// Receives each frame once it is complete. The frame is 160x144, except the Super Game Boy
+    /// frame which is 256x224 with its border.
+    fn update_frame(&mut self, frame: &FrameBuffer);
+
+    /// Receives the samples played since the last call.
+    /// The sound controller does not generate samples yet, so nothing is sent for now.
+    fn queue_audio(&mut self, _samples: &[AudioSample]) {}
+
+    /// Polls the inputs, called once per frame.
+    fn update_inputs(&mut self);
+
+    fn get_inputs(&self) -> JoypadState;
+
+    /// Returns the tilt of the cartridge, only used by cartridges with an accelerometer.
+    fn get_tilt(&self) -> Tilt {
+        Tilt::default()
+    }
+
+    /// Called each time the rumble motor of the cartridge is turned on or off.
+    /// Information from: https://gbdev.io/pandocs/MBC5.html#rumble
+    fn set_rumble(&mut self, _is_on: bool) {}
+
+    /// Called when the emulation stops or the CPU locks up.
+    fn notify(&mut self, _event: &EmulatorEvent) {}
+}
+
+pub struct NoOpFrontend {
+    nb_frames: u64,
+    tilt_script: Option<TiltScript>,
+}
 
-impl NoOpGui {
+impl NoOpFrontend {
     pub fn new() -> Self {
-        Self {}
+        Self {
+            nb_frames: 0,
+            tilt_script: None,
+        }
+    }
+
+    /// Drives the cartridge accelerometer with the given script.
+    pub fn with_tilt_script(mut self, tilt_script: TiltScript) -> Self {
+        self.tilt_script = Some(tilt_script);
+        self
     }
 }
 
-impl Default for NoOpGui {
+impl Default for NoOpFrontend {
     fn default() -> Self {
         Self::new()
     }
 }
 
-impl Screen for NoOpGui {
-    fn write_pixel(&mut self, _x: usize, _y: usize, _color: &Color) {
+impl Frontend for NoOpFrontend {
+    fn update_frame(&mut self, _frame: &FrameBuffer) {
 
     }
 
-    fn update_frame(&mut self) {
+    fn update_inputs(&mut self) {
+        self.nb_frames += 1;
+    }
 
+    fn get_inputs(&self) -> JoypadState {
+        JoypadState::default()
     }
-}
 
+    fn get_tilt(&self) -> Tilt {
+        self.tilt_script
+            .as_ref()
+            .map(|script| script.get_tilt(self.nb_frames))
+            .unwrap_or_default()
+    }
 
-impl InputProvider for NoOpGui {
-    fn update_inputs(&mut self) {
+    fn set_rumble(&mut self, is_on: bool) {
+        info!("Rumble motor {}", if is_on { "on" } else { "off" });
     }
 
-    fn get_inputs(&self) -> JoypadState {
-        JoypadState::default()
+    fn notify(&mut self, event: &EmulatorEvent) {
+        info!("{}", event);
     }
 }
-
-impl Gui for NoOpGui {}
\ No newline at end of file
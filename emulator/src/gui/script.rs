@@ -0,0 +1,73 @@
+//! Scripted inputs for headless runs.
+
+use crate::joypad::Tilt;
+
+/// Tilt applied to the cartridge accelerometer, frame by frame.
+///
+/// The script contains one `frame x y` entry per line, sorted by frame. The tilt of an entry is
+/// kept until the next one. Empty lines and lines starting with `#` are ignored.
+#[derive(Debug, Clone, Default)]
+pub struct TiltScript {
+    entries: Vec<(u64, Tilt)>,
+}
+
+impl TiltScript {
+    pub fn parse(script: &str) -> Result<Self, String> {
+        let mut entries: Vec<(u64, Tilt)> = Vec::new();
+        for (index, line) in script.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let entry = parse_entry(line)
+                .ok_or_else(|| format!("Invalid tilt entry at line {}: {:?}", index + 1, line))?;
+            if entries.last().is_some_and(|(frame, _)| *frame > entry.0) {
+                return Err(format!("Tilt entries are not sorted at line {}", index + 1));
+            }
+            entries.push(entry);
+        }
+        Ok(Self { entries })
+    }
+
+    /// Returns the tilt of the last entry starting at or before the given frame.
+    pub fn get_tilt(&self, frame: u64) -> Tilt {
+        let index = self.entries.partition_point(|(start, _)| *start <= frame);
+        index
+            .checked_sub(1)
+            .map(|index| self.entries[index].1)
+            .unwrap_or_default()
+    }
+}
+
+fn parse_entry(line: &str) -> Option<(u64, Tilt)> {
+    let mut values = line.split_whitespace();
+    let frame = values.next()?.parse().ok()?;
+    let x = values.next()?.parse().ok()?;
+    let y = values.next()?.parse().ok()?;
+    if values.next().is_some() {
+        return None;
+    }
+    Some((frame, Tilt { x, y }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tilt_is_kept_until_next_entry() {
+        let script = TiltScript::parse("# frame x y\n10 0.5 0\n\n20 -1 0.25\n").unwrap();
+
+        assert_eq!(script.get_tilt(0), Tilt::default());
+        assert_eq!(script.get_tilt(10), Tilt { x: 0.5, y: 0.0 });
+        assert_eq!(script.get_tilt(19), Tilt { x: 0.5, y: 0.0 });
+        assert_eq!(script.get_tilt(500), Tilt { x: -1.0, y: 0.25 });
+    }
+
+    #[test]
+    fn invalid_scripts_are_rejected() {
+        assert!(TiltScript::parse("10 0.5").is_err());
+        assert!(TiltScript::parse("10 0.5 0 1").is_err());
+        assert!(TiltScript::parse("20 0 0\n10 0 0").is_err());
+    }
+}
@@ -1,9 +1,10 @@
-use crate::gui::Gui;
-use crate::joypad::{InputProvider, JoypadState};
-use crate::video::renderer::{Color, Screen, SCREEN_HEIGHT, SCREEN_WIDTH};
+use crate::gui::{FrameBuffer, Frontend};
+use crate::joypad::JoypadState;
+use crate::sgb::{SGB_SCREEN_HEIGHT, SGB_SCREEN_WIDTH};
+use crate::video::renderer::{SCREEN_HEIGHT, SCREEN_WIDTH};
 use sdl2::event::Event;
 use sdl2::keyboard::Keycode;
-use sdl2::pixels::PixelFormatEnum::ARGB8888;
+use sdl2::pixels::PixelFormatEnum;
 use sdl2::rect::Rect;
 use sdl2::render::{Texture, TextureCreator, WindowCanvas};
 use sdl2::video::WindowContext;
@@ -12,8 +13,6 @@ use sdl2::{EventPump, Sdl};
 const INIT_SCALE: u32 = 3;
 
 const NB_BYTES_PER_PIXEL: usize = 4;
-const SCREEN_WIDTH_BYTES: usize = (SCREEN_WIDTH as usize) * NB_BYTES_PER_PIXEL;
-const NB_PIXELS: usize = ((SCREEN_WIDTH * SCREEN_HEIGHT) as usize) * NB_BYTES_PER_PIXEL;
 
 pub struct Sdl2Gui<'a> {
     // TODO: split graphic and input into two sub structs
@@ -37,19 +36,11 @@ impl<'a> Sdl2Gui<'a> {
     }
 }
 
-impl<'a> Gui for Sdl2Gui<'a> {}
-
-impl<'a> Screen for Sdl2Gui<'a> {
-    fn write_pixel(&mut self, x: usize, y: usize, color: &Color) {
-        self.screen.write_pixel(x, y, color);
-    }
-
-    fn update_frame(&mut self) {
-        self.screen.update();
+impl<'a> Frontend for Sdl2Gui<'a> {
+    fn update_frame(&mut self, frame: &FrameBuffer) {
+        self.screen.update(frame);
     }
-}
 
-impl<'a> InputProvider for Sdl2Gui<'a> {
     fn update_inputs(&mut self) {
         for event in self.events.poll_iter() {
             match event {
@@ -84,6 +75,10 @@ impl<'a> InputProvider for Sdl2Gui<'a> {
     fn get_inputs(&self) -> JoypadState {
         self.joypad.clone()
     }
+
+    fn set_rumble(&mut self, is_on: bool) {
+        log::info!("Rumble motor {}", if is_on { "on" } else { "off" });
+    }
 }
 
 pub struct Sdl2Context {
@@ -99,7 +94,6 @@ impl Sdl2Context {
 struct Sdl2Screen<'a> {
     canvas: &'a mut WindowCanvas,
     texture: Texture<'a>,
-    pixels: [u8; NB_PIXELS],
     screen: Rect,
 }
 
@@ -108,26 +102,31 @@ impl<'a> Sdl2Screen<'a> {
         Self {
             canvas,
             texture,
-            pixels: [0; NB_PIXELS],
             screen: Rect::new(0, 0, SCREEN_WIDTH, SCREEN_HEIGHT),
         }
     }
 
-    fn write_pixel(&mut self, x: usize, y: usize, color: &Color) {
-        let start_position = y * SCREEN_WIDTH_BYTES + x * NB_BYTES_PER_PIXEL;
-        self.pixels[start_position] = color.blue;
-        self.pixels[start_position + 1] = color.green;
-        self.pixels[start_position + 2] = color.red;
-        self.pixels[start_position + 3] = color.alpha;
-    }
-
-    fn update(&mut self) {
+    /// The texture is large enough for the Super Game Boy frame, only the area of the current
+    /// frame is used. The window is resized when the frame size changes.
+    fn update(&mut self, frame: &FrameBuffer) {
+        let (width, height) = (frame.get_width() as u32, frame.get_height() as u32);
+        if self.screen.size() != (width, height) {
+            self.screen = Rect::new(0, 0, width, height);
+            self.canvas
+                .window_mut()
+                .set_size(width * INIT_SCALE, height * INIT_SCALE)
+                .unwrap();
+        }
         self.canvas.clear();
         self.texture
-            .update(self.screen, &self.pixels, SCREEN_WIDTH_BYTES)
+            .update(
+                self.screen,
+                frame.get_rgba(),
+                frame.get_width() * NB_BYTES_PER_PIXEL,
+            )
             .unwrap();
         self.canvas
-            .copy(&self.texture, None, Some(self.screen))
+            .copy(&self.texture, Some(self.screen), Some(self.screen))
             .unwrap();
         self.canvas.present()
     }
@@ -170,7 +169,11 @@ impl Sdl2GuiProvider {
     pub fn get(&mut self) -> Sdl2Gui {
         let texture = self
             .texture_creator
-            .create_texture_streaming(ARGB8888, SCREEN_WIDTH, SCREEN_HEIGHT)
+            .create_texture_streaming(
+                PixelFormatEnum::RGBA32,
+                SGB_SCREEN_WIDTH as u32,
+                SGB_SCREEN_HEIGHT as u32,
+            )
             .unwrap();
         Sdl2Gui::new(
             &mut self.canvas,
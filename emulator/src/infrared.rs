@@ -0,0 +1,332 @@
+//! Infrared communication, used by the CGB RP register and the HuC1/HuC3 cartridges.
+//!
+//! Information from: https://gbdev.io/pandocs/CGB_Registers.html#ff56--rp-cgb-mode-only-infrared-communications-port
+
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+/// Device facing the infrared LED and receiver of the emulated hardware.
+pub trait InfraredDevice: Send {
+    /// Called each time the emitting LED is turned on or off.
+    fn set_led(&mut self, is_on: bool);
+
+    /// Returns true while the device emits light toward the receiver.
+    fn is_receiving_light(&self) -> bool;
+
+    /// Called before the LED is checked, with the number of cycles since the last call.
+    /// Only called when the LED changed, unless the device is timed.
+    fn update(&mut self, _nb_cycles: u64) {}
+
+    /// Returns true if the device has to be updated after each instruction: it counts the cycles
+    /// or the received light can change while the LED stays the same.
+    fn is_timed(&self) -> bool {
+        false
+    }
+}
+
+/// Nothing faces the infrared port, no light is ever received.
+#[derive(Default)]
+pub struct NoInfraredDevice {}
+
+impl InfraredDevice for NoInfraredDevice {
+    fn set_led(&mut self, _is_on: bool) {}
+
+    fn is_receiving_light(&self) -> bool {
+        false
+    }
+}
+
+/// Reflects the light of the LED back to the receiver.
+#[derive(Default)]
+pub struct InfraredLoopback {
+    is_led_on: bool,
+}
+
+impl InfraredDevice for InfraredLoopback {
+    fn set_led(&mut self, is_on: bool) {
+        self.is_led_on = is_on;
+    }
+
+    fn is_receiving_light(&self) -> bool {
+        self.is_led_on
+    }
+}
+
+/// One side of two consoles facing each other, each receiving the light of the other LED.
+pub struct InfraredLinkPort {
+    leds: Arc<Mutex<[bool; 2]>>,
+    side: usize,
+}
+
+/// Creates the infrared link and returns the device of each console.
+pub fn create_infrared_link() -> (InfraredLinkPort, InfraredLinkPort) {
+    let leds = Arc::new(Mutex::new([false; 2]));
+    (
+        InfraredLinkPort {
+            leds: leds.clone(),
+            side: 0,
+        },
+        InfraredLinkPort { leds, side: 1 },
+    )
+}
+
+impl InfraredDevice for InfraredLinkPort {
+    fn set_led(&mut self, is_on: bool) {
+        self.leds.lock().unwrap()[self.side] = is_on;
+    }
+
+    fn is_receiving_light(&self) -> bool {
+        self.leds.lock().unwrap()[1 - self.side]
+    }
+
+    fn is_timed(&self) -> bool {
+        true
+    }
+}
+
+/// Changes of the light over time, each with the number of cycles since the signal started.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct InfraredSignal {
+    pub transitions: Vec<(u64, bool)>,
+}
+
+impl InfraredSignal {
+    /// Reads a signal saved with one transition per line: the cycles, then `on` or `off`.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("Unable to read the infrared signal {:?}: {}", path, e))?;
+        Self::parse(&text)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        fs::write(path, self.to_text())
+            .map_err(|e| format!("Unable to write the infrared signal {:?}: {}", path, e))
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let transitions = text
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(
+                |line| match line.split_whitespace().collect::<Vec<_>>()[..] {
+                    [cycles, state @ ("on" | "off")] => cycles
+                        .parse()
+                        .map(|cycles| (cycles, state == "on"))
+                        .map_err(|e| format!("Invalid cycles in {:?}: {}", line, e)),
+                    _ => Err(format!("Invalid infrared transition: {:?}", line)),
+                },
+            )
+            .collect::<Result<_, _>>()?;
+        Ok(Self { transitions })
+    }
+
+    pub fn to_text(&self) -> String {
+        self.transitions
+            .iter()
+            .map(|(cycles, is_on)| format!("{} {}\n", cycles, if *is_on { "on" } else { "off" }))
+            .collect()
+    }
+}
+
+/// Records the changes of the LED, while forwarding them to another device.
+pub struct InfraredRecorder {
+    device: Box<dyn InfraredDevice>,
+    signal: Arc<Mutex<InfraredSignal>>,
+    cycles: u64,
+}
+
+impl InfraredRecorder {
+    pub fn new(device: Box<dyn InfraredDevice>) -> Self {
+        Self {
+            device,
+            signal: Arc::new(Mutex::new(InfraredSignal::default())),
+            cycles: 0,
+        }
+    }
+
+    /// Returns the recorded signal, which keeps growing while the recorder is plugged.
+    pub fn get_signal(&self) -> Arc<Mutex<InfraredSignal>> {
+        self.signal.clone()
+    }
+}
+
+impl InfraredDevice for InfraredRecorder {
+    fn set_led(&mut self, is_on: bool) {
+        self.signal
+            .lock()
+            .unwrap()
+            .transitions
+            .push((self.cycles, is_on));
+        self.device.set_led(is_on);
+    }
+
+    fn is_receiving_light(&self) -> bool {
+        self.device.is_receiving_light()
+    }
+
+    fn update(&mut self, nb_cycles: u64) {
+        self.cycles += nb_cycles;
+        self.device.update(nb_cycles);
+    }
+
+    fn is_timed(&self) -> bool {
+        true
+    }
+}
+
+/// Plays a recorded signal toward the receiver, starting when the device is plugged.
+pub struct InfraredPlayback {
+    signal: InfraredSignal,
+    /// Index of the next transition to play.
+    index: usize,
+    cycles: u64,
+    is_on: bool,
+}
+
+impl InfraredPlayback {
+    pub fn new(signal: InfraredSignal) -> Self {
+        Self {
+            signal,
+            index: 0,
+            cycles: 0,
+            is_on: false,
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.index >= self.signal.transitions.len()
+    }
+}
+
+impl InfraredDevice for InfraredPlayback {
+    fn set_led(&mut self, _is_on: bool) {}
+
+    fn is_receiving_light(&self) -> bool {
+        self.is_on
+    }
+
+    fn update(&mut self, nb_cycles: u64) {
+        self.cycles += nb_cycles;
+        while let Some((cycles, is_on)) = self.signal.transitions.get(self.index) {
+            if *cycles > self.cycles {
+                break;
+            }
+            self.is_on = *is_on;
+            self.index += 1;
+        }
+    }
+
+    fn is_timed(&self) -> bool {
+        true
+    }
+}
+
+/// Connects the LEDs of the console and of the cartridge to the infrared device.
+pub(crate) struct InfraredPort {
+    device: Box<dyn InfraredDevice>,
+    is_timed: bool,
+    is_led_on: bool,
+    /// None until the device is checked for the first time.
+    is_receiving_light: Option<bool>,
+}
+
+impl InfraredPort {
+    pub fn new(device: Box<dyn InfraredDevice>) -> Self {
+        Self {
+            is_timed: device.is_timed(),
+            device,
+            is_led_on: false,
+            is_receiving_light: None,
+        }
+    }
+
+    /// Forwards the elapsed cycles and the LED changes to the device, returns true if the
+    /// received light changed.
+    /// The device is left alone while the LED stays the same, unless it is timed.
+    pub fn update(&mut self, is_led_on: bool, nb_cycles: u64) -> bool {
+        let is_led_changed = is_led_on != self.is_led_on;
+        if !is_led_changed && !self.is_timed && self.is_receiving_light.is_some() {
+            return false;
+        }
+        self.device.update(nb_cycles);
+        if is_led_changed {
+            self.is_led_on = is_led_on;
+            self.device.set_led(is_led_on);
+        }
+        let is_receiving_light = Some(self.device.is_receiving_light());
+        let is_changed = is_receiving_light != self.is_receiving_light;
+        self.is_receiving_light = is_receiving_light;
+        is_changed
+    }
+
+    pub fn is_receiving_light(&self) -> bool {
+        self.is_receiving_light.unwrap_or(false)
+    }
+}
+
+impl Default for InfraredPort {
+    fn default() -> Self {
+        Self::new(Box::new(NoInfraredDevice::default()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn linked_consoles_receive_the_light_of_each_other() {
+        let (first, second) = create_infrared_link();
+        let mut first = InfraredPort::new(Box::new(first));
+        let mut second = InfraredPort::new(Box::new(second));
+
+        second.update(false, 4);
+        assert!(!second.is_receiving_light());
+        first.update(true, 4);
+        assert!(second.update(false, 4));
+        assert!(second.is_receiving_light());
+        first.update(true, 4);
+        assert!(!first.is_receiving_light());
+
+        let mut loopback = InfraredPort::new(Box::new(InfraredLoopback::default()));
+        assert!(loopback.update(true, 4));
+        assert!(loopback.is_receiving_light());
+    }
+
+    #[test]
+    fn untimed_device_is_only_updated_when_the_led_changes() {
+        let mut port = InfraredPort::new(Box::new(InfraredLoopback::default()));
+        assert!(port.update(false, 4));
+        assert!(!port.update(false, 4));
+        assert!(port.update(true, 4));
+        assert!(!port.update(true, 4));
+        assert!(port.is_receiving_light());
+    }
+
+    #[test]
+    fn recorded_signal_is_played_back() {
+        let recorder = InfraredRecorder::new(Box::new(NoInfraredDevice::default()));
+        let signal = recorder.get_signal();
+        let mut port = InfraredPort::new(Box::new(recorder));
+        for is_led_on in [false, true, true, false, true] {
+            port.update(is_led_on, 100);
+        }
+        let signal = signal.lock().unwrap().clone();
+        assert_eq!(
+            signal.transitions,
+            vec![(200, true), (400, false), (500, true)]
+        );
+        assert_eq!(InfraredSignal::parse(&signal.to_text()), Ok(signal.clone()));
+
+        let mut playback = InfraredPlayback::new(signal);
+        let received: Vec<bool> = (0..5)
+            .map(|_| {
+                playback.update(100);
+                playback.is_receiving_light()
+            })
+            .collect();
+        assert_eq!(received, vec![false, true, true, false, true]);
+        assert!(playback.is_finished());
+    }
+}
@@ -19,6 +19,17 @@ pub struct JoypadState {
     pub start: bool,
 }
 
+/// Tilt of the cartridge measured by the MBC7 accelerometer.
+///
+/// Each axis is expressed in fractions of the gravity, in the range [-1.0, 1.0].
+/// Positive values tilt the console to the right and towards the player.
+/// Information from: https://gbdev.io/pandocs/MBC7.html#accelerometer
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Tilt {
+    pub x: f32,
+    pub y: f32,
+}
+
 impl JoypadState {
     pub fn to_u8(&self, action: bool, direction: bool) -> u8 {
         // Start with nothing pressed.
@@ -85,13 +96,6 @@ impl JoypadInput {
     }
 }
 
-pub trait InputProvider {
-    /// Updates the internal state
-    fn update_inputs(&mut self);
-
-    fn get_inputs(&self) -> JoypadState;
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1,11 +1,23 @@
+pub mod archive;
+pub mod camera;
 pub mod cartridge;
+pub mod cartridge_report;
 pub mod emulator;
+pub mod fault;
+pub mod four_player;
 mod generated;
 pub mod gui;
+pub mod infrared;
 mod interrupts;
 mod joypad;
+pub mod link;
 mod memory;
-mod serial;
+pub mod model;
+pub mod patch;
+pub mod printer;
+pub mod serial;
+pub mod sgb;
+pub mod socket_link;
 mod sound;
 mod timer;
 mod video;
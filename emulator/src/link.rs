@@ -0,0 +1,142 @@
+//! Virtual link cable connecting two emulators running in the same process.
+//!
+//! The linked consoles also face each other, so they can use their infrared ports.
+
+use crate::debugger::NoOpDebugger;
+use crate::emulator::update_next_instruction;
+use crate::fault::EmulationFault;
+use crate::gui::Frontend;
+use crate::infrared::create_infrared_link;
+use crate::serial::SerialDevice;
+use crate::state::EmulatorState;
+use std::sync::{Arc, Mutex};
+
+#[derive(Default)]
+struct CableState {
+    /// Byte offered by each side while it waits for the other side to clock a transfer.
+    waiting: [Option<u8>; 2],
+    /// Byte received by each side from a transfer clocked by the other side.
+    received: [Option<u8>; 2],
+}
+
+/// One end of the link cable, plugged in the serial port of a console.
+pub struct LinkCablePort {
+    cable: Arc<Mutex<CableState>>,
+    side: usize,
+}
+
+/// Creates a link cable and returns its two ends.
+pub fn create_link_cable() -> (LinkCablePort, LinkCablePort) {
+    let cable = Arc::new(Mutex::new(CableState::default()));
+    (
+        LinkCablePort {
+            cable: cable.clone(),
+            side: 0,
+        },
+        LinkCablePort { cable, side: 1 },
+    )
+}
+
+impl SerialDevice for LinkCablePort {
+    fn exchange(&mut self, value: u8) -> u8 {
+        let mut cable = self.cable.lock().unwrap();
+        let other = 1 - self.side;
+        // The other side only receives the byte if it is ready for the transfer.
+        match cable.waiting[other].take() {
+            Some(incoming) => {
+                cable.received[other] = Some(value);
+                incoming
+            }
+            None => 0xFF,
+        }
+    }
+
+    fn poll_external(&mut self, value: u8) -> Option<u8> {
+        let mut cable = self.cable.lock().unwrap();
+        let received = cable.received[self.side].take();
+        if received.is_none() {
+            cable.waiting[self.side] = Some(value);
+        }
+        received
+    }
+
+    fn cancel_external(&mut self) {
+        self.cable.lock().unwrap().waiting[self.side] = None;
+    }
+}
+
+/// Two consoles connected by a link cable and facing each other, run in lockstep.
+///
+/// The console that is behind always runs the next instruction, so the clocks of the consoles
+/// never drift apart by more than one instruction.
+pub struct LinkedEmulators {
+    pub states: [EmulatorState; 2],
+    cycles: [u64; 2],
+    debugger: NoOpDebugger,
+}
+
+impl LinkedEmulators {
+    pub fn new(mut first: EmulatorState, mut second: EmulatorState) -> Self {
+        let (first_port, second_port) = create_link_cable();
+        first.memory.set_serial_device(Box::new(first_port));
+        second.memory.set_serial_device(Box::new(second_port));
+        let (first_infrared, second_infrared) = create_infrared_link();
+        first.memory.set_infrared_device(Box::new(first_infrared));
+        second.memory.set_infrared_device(Box::new(second_infrared));
+        Self {
+            states: [first, second],
+            cycles: [0; 2],
+            debugger: NoOpDebugger::new(),
+        }
+    }
+
+    /// Returns the number of cycles run by each console.
+    pub fn get_cycles(&self) -> [u64; 2] {
+        self.cycles
+    }
+
+    /// Runs the next instruction of the console that is behind.
+    pub fn step(&mut self, frontends: &mut [impl Frontend; 2]) -> Result<(), EmulationFault> {
+        let index = if self.cycles[0] <= self.cycles[1] {
+            0
+        } else {
+            1
+        };
+        let update = update_next_instruction(
+            &mut self.states[index],
+            &mut frontends[index],
+            &mut self.debugger,
+        )?;
+        self.cycles[index] += update.nb_cycles;
+        Ok(())
+    }
+
+    /// Runs both consoles for at least the given number of cycles.
+    pub fn run_for(
+        &mut self,
+        nb_cycles: u64,
+        frontends: &mut [impl Frontend; 2],
+    ) -> Result<(), EmulationFault> {
+        let target = self.cycles.iter().min().unwrap() + nb_cycles;
+        while self.cycles.iter().any(|cycles| *cycles < target) {
+            self.step(frontends)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cable_only_delivers_to_a_waiting_side() {
+        let (mut first, mut second) = create_link_cable();
+        assert_eq!(first.exchange(0x12), 0xFF);
+        assert_eq!(second.poll_external(0x34), None);
+
+        assert_eq!(first.exchange(0x56), 0x34);
+        assert_eq!(second.poll_external(0x34), Some(0x56));
+        assert_eq!(second.poll_external(0x34), None);
+    }
+}
@@ -1,9 +1,146 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+use emulator::archive::read_rom;
+use emulator::cartridge::{load_cartridge, CartridgeHeader};
+use emulator::cartridge_report::{format_json, format_report};
+use emulator::debugger::NoOpDebugger;
+use emulator::emulator::update_next_instruction;
+use emulator::four_player::{FourPlayerSession, MAX_PLAYERS};
 use emulator::gui::eframe::run_emulator;
+use emulator::gui::script::TiltScript;
+use emulator::gui::NoOpFrontend;
+use emulator::socket_link::LinkMode;
+use emulator::state::EmulatorState;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
 
-fn main() -> Result<(), eframe::Error> {
+const HEADER_USAGE: &str = "Usage: emulator header [--json] <rom>";
+const FOUR_PLAYER_USAGE: &str = "Usage: emulator four-player [--seconds <seconds>] <rom>";
+const FOUR_PLAYER_DEFAULT_SECONDS: u64 = 10;
+const CYCLES_PER_SECOND: u64 = 1 << 22;
+const RUN_USAGE: &str = "Usage: emulator run [--seconds <seconds>] [--tilt-script <file>] <rom>";
+const RUN_DEFAULT_SECONDS: u64 = 10;
+const LINK_USAGE: &str = "Usage: emulator [--link-listen <address> | --link-connect <address>]";
+
+fn main() -> Result<(), Box<dyn Error>> {
     env_logger::init(); // Log to stderr (if you run with `RUST_LOG=debug`).
-    run_emulator()
+    let arguments: Vec<String> = std::env::args().skip(1).collect();
+    match arguments.first().map(String::as_str) {
+        Some("header") => print_header(&arguments[1..]),
+        Some("four-player") => run_four_player(&arguments[1..]),
+        Some("run") => run_headless(&arguments[1..]),
+        _ => Ok(run_emulator(get_link_mode(&arguments)?)?),
+    }
+}
+
+/// Returns the link cable mode requested with `--link-listen` or `--link-connect`.
+fn get_link_mode(arguments: &[String]) -> Result<Option<LinkMode>, Box<dyn Error>> {
+    let Some(index) = arguments
+        .iter()
+        .position(|argument| argument.starts_with("--link-"))
+    else {
+        return Ok(None);
+    };
+    let address = arguments.get(index + 1).ok_or(LINK_USAGE)?.clone();
+    match arguments[index].as_str() {
+        "--link-listen" => Ok(Some(LinkMode::Listen(address))),
+        "--link-connect" => Ok(Some(LinkMode::Connect(address))),
+        _ => Err(LINK_USAGE.into()),
+    }
 }
 
+/// Prints the report of the cartridge header, as JSON with `--json`.
+///
+/// The header is read from the rom file as it is, without applying a patch, and unknown type or
+/// size codes are reported instead of failing.
+fn print_header(arguments: &[String]) -> Result<(), Box<dyn Error>> {
+    let json = arguments.iter().any(|argument| argument == "--json");
+    let path = arguments
+        .iter()
+        .find(|argument| !argument.starts_with("--"))
+        .ok_or(HEADER_USAGE)?;
+    let rom = read_rom(Path::new(path), None)?;
+    let header = CartridgeHeader::from_rom(&rom)?;
+    if json {
+        println!("{}", format_json(&header));
+    } else {
+        print!("{}", format_report(&header));
+    }
+    Ok(())
+}
+
+/// Runs four instances of the rom connected by the Four Player Adapter, without any window, then
+/// prints the state of the adapter.
+fn run_four_player(arguments: &[String]) -> Result<(), Box<dyn Error>> {
+    let mut path = None;
+    let mut seconds = FOUR_PLAYER_DEFAULT_SECONDS;
+    let mut arguments = arguments.iter();
+    while let Some(argument) = arguments.next() {
+        match argument.as_str() {
+            "--seconds" => {
+                seconds = arguments
+                    .next()
+                    .and_then(|value| value.parse().ok())
+                    .ok_or(FOUR_PLAYER_USAGE)?
+            }
+            _ => path = Some(Path::new(argument)),
+        }
+    }
+    let path = path.ok_or(FOUR_PLAYER_USAGE)?;
+    let states = (0..MAX_PLAYERS)
+        .map(|_| load_cartridge(path).map(EmulatorState::new))
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut session = FourPlayerSession::new(states)?;
+    let mut frontends: Vec<NoOpFrontend> = (0..MAX_PLAYERS).map(|_| NoOpFrontend::new()).collect();
+
+    session.run_for(seconds * CYCLES_PER_SECOND, &mut frontends)?;
+
+    println!("Adapter phase: {:?}", session.adapter.get_phase());
+    for (player, connected) in session.adapter.get_connected_players().iter().enumerate() {
+        let status = if *connected {
+            "connected"
+        } else {
+            "not connected"
+        };
+        println!("Player {}: {}", player + 1, status);
+    }
+    Ok(())
+}
+
+/// Runs the rom without any window, with the cartridge accelerometer driven by a tilt script, then
+/// saves the battery backed memory.
+fn run_headless(arguments: &[String]) -> Result<(), Box<dyn Error>> {
+    let mut path = None;
+    let mut seconds = RUN_DEFAULT_SECONDS;
+    let mut frontend = NoOpFrontend::new();
+    let mut arguments = arguments.iter();
+    while let Some(argument) = arguments.next() {
+        match argument.as_str() {
+            "--seconds" => {
+                seconds = arguments
+                    .next()
+                    .and_then(|value| value.parse().ok())
+                    .ok_or(RUN_USAGE)?
+            }
+            "--tilt-script" => {
+                let script_path = arguments.next().ok_or(RUN_USAGE)?;
+                let script = fs::read_to_string(script_path).map_err(|e| {
+                    format!("Unable to read the tilt script {:?}: {}", script_path, e)
+                })?;
+                frontend = frontend.with_tilt_script(TiltScript::parse(&script)?);
+            }
+            _ => path = Some(Path::new(argument)),
+        }
+    }
+    let path = path.ok_or(RUN_USAGE)?;
+    let mut state = EmulatorState::new(load_cartridge(path)?);
+    let mut debugger = NoOpDebugger::new();
+
+    let mut nb_cycles = 0;
+    while nb_cycles < seconds * CYCLES_PER_SECOND {
+        nb_cycles += update_next_instruction(&mut state, &mut frontend, &mut debugger)?.nb_cycles;
+    }
+    state.save_battery()?;
+    Ok(())
+}
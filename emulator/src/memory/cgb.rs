@@ -3,6 +3,10 @@
 pub const KEY_1_ADDRESS: u16 = 0xFF4D;
 /// Information from: https://gbdev.io/pandocs/CGB_Registers.html#ff56--rp-cgb-mode-only-infrared-communications-port
 pub const INFRARED_CONTROL_ADDRESS: u16 = 0xFF56;
+const INFRARED_LED_BIT: u8 = 0b0000_0001;
+const INFRARED_READ_BIT: u8 = 0b0000_0010;
+const INFRARED_READ_ENABLE_BITS: u8 = 0b1100_0000;
+const INFRARED_UNUSED_BITS: u8 = 0b0011_1100;
 
 pub enum CGBRegisters {
     Cgb(Cgb),
@@ -34,14 +38,39 @@ impl CGBRegisters {
 
     pub fn read_infrared_control(&self) -> u8 {
         match self {
-            Self::Cgb(cgb) => cgb.infrared_control,
+            Self::Cgb(cgb) => {
+                let is_read_enabled =
+                    cgb.infrared_control & INFRARED_READ_ENABLE_BITS == INFRARED_READ_ENABLE_BITS;
+                // The read bit is 0 when light is received.
+                if is_read_enabled && cgb.is_receiving_light {
+                    cgb.infrared_control | INFRARED_UNUSED_BITS
+                } else {
+                    cgb.infrared_control | INFRARED_UNUSED_BITS | INFRARED_READ_BIT
+                }
+            }
             Self::Dmg => 0xFF,
         }
     }
 
     pub fn write_infrared_control(&mut self, value: u8) {
         match self {
-            Self::Cgb(cgb) => cgb.infrared_control = value,
+            Self::Cgb(cgb) => {
+                cgb.infrared_control = value & (INFRARED_LED_BIT | INFRARED_READ_ENABLE_BITS)
+            }
+            Self::Dmg => (),
+        }
+    }
+
+    pub fn is_infrared_led_on(&self) -> bool {
+        match self {
+            Self::Cgb(cgb) => cgb.infrared_control & INFRARED_LED_BIT != 0,
+            Self::Dmg => false,
+        }
+    }
+
+    pub fn set_infrared_received(&mut self, is_receiving_light: bool) {
+        match self {
+            Self::Cgb(cgb) => cgb.is_receiving_light = is_receiving_light,
             Self::Dmg => (),
         }
     }
@@ -51,4 +80,5 @@ impl CGBRegisters {
 pub struct Cgb {
     key_1: u8,
     infrared_control: u8,
+    is_receiving_light: bool,
 }
@@ -1,5 +1,6 @@
+use crate::infrared::{InfraredDevice, InfraredPort};
 use crate::interrupts::{Interrupt, ALL_INTERRUPTS};
-use crate::joypad::{JoypadInput, JOYPAD_INPUT_ADDRESS};
+use crate::joypad::{JoypadInput, Tilt, JOYPAD_INPUT_ADDRESS};
 use crate::memory::cgb::{CGBRegisters, INFRARED_CONTROL_ADDRESS, KEY_1_ADDRESS};
 use crate::memory::mbc::interface::{
     MemoryBankController, EXT_RAM_END_ADDRESS, EXT_RAM_START_ADDRESS, ROM_BANK_1_N_END_ADDRESS,
@@ -10,8 +11,12 @@ use crate::memory::ram::{
     HIGH_RAM_START_ADDRESS, SELECT_WORK_RAM_BANK_ADDRESS, WORK_RAM_END_ADDRESS,
     WORK_RAM_START_ADDRESS,
 };
+use crate::memory::unusable::UnusableMemory;
 use crate::memory::Memory;
-use crate::serial::{SerialTransfer, SERIAL_TRANSFER_END_ADDRESS, SERIAL_TRANSFER_START_ADDRESS};
+use crate::serial::{
+    SerialDevice, SerialTransfer, SERIAL_TRANSFER_END_ADDRESS, SERIAL_TRANSFER_START_ADDRESS,
+};
+use crate::sgb::SuperGameBoy;
 use crate::sound::SoundController;
 use crate::timer::{Timer, TIMER_END_ADDRESS, TIMER_START_ADDRESS};
 use crate::video::controller::{
@@ -34,6 +39,8 @@ pub const AFTER_OAM_DMA_ADDRESS: u16 = OAM_DMA_ADDRESS + 1;
 pub const DISABLE_BOOT_ROM_ADDRESS: u16 = 0xFF50;
 pub const VRAM_DMA_START_ADDRESS: u16 = 0xFF51;
 pub const VRAM_DMA_END_ADDRESS: u16 = 0xFF55;
+/// The OAM DMA transfer copies one byte per machine cycle.
+const OAM_DMA_CYCLES: u64 = 4 * OAM_SIZE as u64;
 
 /// Information from: https://gbdev.io/pandocs/Interrupts.html#interrupts
 pub const INTERRUPT_FLAG_ADDRESS: u16 = 0xFF0F;
@@ -49,14 +56,21 @@ pub struct GBMemory {
     sound: SoundController,
     timer: Timer,
     cgb_registers: CGBRegisters,
+    unusable: UnusableMemory,
+    infrared: InfraredPort,
+    /// Only present when running on a Super Game Boy.
+    pub sgb: Option<SuperGameBoy>,
 
     oam_dma_high_bits: u8, // https://gbdev.io/pandocs/OAM_DMA_Transfer.html?highlight=oam%20dma%20high#ff46--dma-oam-dma-source-address--start
     interrupt_flag: u8,    // https://gbdev.io/pandocs/Interrupts.html#ffff--ie-interrupt-enable
     interrupt_enable: u8,  // https://gbdev.io/pandocs/Interrupts.html#ff0f--if-interrupt-flag
     boot_rom_disabled: u8,
+    /// Cycles left before the OAM DMA transfer ends, the copy itself is done at once.
+    oam_dma_remaining_cycles: u64,
 }
 
 impl GBMemory {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         mbc: Box<dyn MemoryBankController>,
         video: VideoController,
@@ -66,6 +80,7 @@ impl GBMemory {
         sound: SoundController,
         timer: Timer,
         cgb_registers: CGBRegisters,
+        unusable: UnusableMemory,
     ) -> Self {
         Self {
             mbc,
@@ -76,7 +91,11 @@ impl GBMemory {
             sound,
             timer,
             cgb_registers,
+            unusable,
+            infrared: InfraredPort::default(),
+            sgb: None,
             oam_dma_high_bits: 0,
+            oam_dma_remaining_cycles: 0,
             interrupt_flag: 0,
             interrupt_enable: 0,
             boot_rom_disabled: 0,
@@ -92,18 +111,60 @@ impl GBMemory {
 
     /// Update all memory controllers and update interrupt flags
     ///
-    /// Memory controllers: video, timer
+    /// Memory controllers: video, timer, serial port, cartridge and infrared port
     pub fn update(&mut self, nb_cycles: u64) {
+        self.oam_dma_remaining_cycles = self.oam_dma_remaining_cycles.saturating_sub(nb_cycles);
         let mut interrupts = self.video.update(nb_cycles);
         if let Some(interrupt) = self.timer.update(nb_cycles) {
             interrupts.push(interrupt);
         }
+        if let Some(interrupt) = self.serial.update(nb_cycles) {
+            interrupts.push(interrupt);
+        }
 
         // Update interrupt flag
         for interrupt in interrupts {
             self.interrupt_flag = interrupt.set(self.interrupt_flag);
         }
         self.mbc.update(convert_cycles_to_duration(nb_cycles));
+
+        // The console and the cartridge share the infrared light.
+        let is_led_on = self.cgb_registers.is_infrared_led_on() || self.mbc.is_infrared_led_on();
+        if self.infrared.update(is_led_on, nb_cycles) {
+            let is_receiving_light = self.infrared.is_receiving_light();
+            self.cgb_registers.set_infrared_received(is_receiving_light);
+            self.mbc.set_infrared_received(is_receiving_light);
+        }
+    }
+
+    /// Replaces the device plugged on the link cable.
+    pub fn set_serial_device(&mut self, device: Box<dyn SerialDevice>) {
+        self.serial.set_device(device);
+    }
+
+    /// Unplugs the device from the link cable and returns it.
+    pub fn take_serial_device(&mut self) -> Box<dyn SerialDevice> {
+        self.serial.take_device()
+    }
+
+    /// Replaces the device facing the infrared port of the console and the cartridge.
+    pub fn set_infrared_device(&mut self, device: Box<dyn InfraredDevice>) {
+        self.infrared = InfraredPort::new(device);
+    }
+
+    /// Returns the content of the cartridge battery backed memory.
+    pub fn save_battery(&self) -> Vec<u8> {
+        self.mbc.save_battery()
+    }
+
+    /// Returns the new state of the cartridge rumble motor if it changed.
+    pub fn take_rumble_event(&mut self) -> Option<bool> {
+        self.mbc.take_rumble_event()
+    }
+
+    /// Updates the tilt measured by the cartridge accelerometer.
+    pub fn set_tilt(&mut self, tilt: Tilt) {
+        self.mbc.set_tilt(tilt);
     }
 
     pub fn get_enabled_interrupt(&self) -> Option<Interrupt> {
@@ -139,6 +200,12 @@ impl GBMemory {
         for i in 0u16..(OAM_SIZE as u16) {
             self.write(OAM_START_ADDRESS + i, self.read(high_bits + i));
         }
+        self.oam_dma_remaining_cycles = OAM_DMA_CYCLES;
+    }
+
+    /// The OAM bus is busy while the PPU reads it (mode 2 and 3) and during the OAM DMA transfer.
+    fn is_oam_blocked(&self) -> bool {
+        self.video.is_oam_blocked() || self.oam_dma_remaining_cycles > 0
     }
 
     fn read_vram_dma(&self, _address: u16) -> u8 {
@@ -152,7 +219,10 @@ impl GBMemory {
 
     fn read_io(&self, address: u16) -> u8 {
         match address {
-            JOYPAD_INPUT_ADDRESS => self.joypad.read(),
+            JOYPAD_INPUT_ADDRESS => match &self.sgb {
+                Some(sgb) => sgb.read_joypad(self.joypad.read()),
+                None => self.joypad.read(),
+            },
             SERIAL_TRANSFER_START_ADDRESS..=SERIAL_TRANSFER_END_ADDRESS => {
                 self.serial.read(address)
             }
@@ -180,7 +250,12 @@ impl GBMemory {
 
     fn write_io(&mut self, address: u16, value: u8) {
         match address {
-            JOYPAD_INPUT_ADDRESS => self.joypad.write(value),
+            JOYPAD_INPUT_ADDRESS => {
+                self.joypad.write(value);
+                if let Some(sgb) = self.sgb.as_mut() {
+                    sgb.write_joypad(value);
+                }
+            }
             SERIAL_TRANSFER_START_ADDRESS..=SERIAL_TRANSFER_END_ADDRESS => {
                 self.serial.write(address, value)
             }
@@ -215,9 +290,7 @@ impl Memory for GBMemory {
             ECHO_RAM_START_ADDRESS..=ECHO_RAM_END_ADDRESS => self.ram.read_echo_ram(address),
             OAM_START_ADDRESS..=OAM_END_ADDRESS => self.video.read_oam(address),
             NOT_USABLE_START_ADDRESS..=NOT_USABLE_END_ADDRESS => {
-                // TODO: Implement complete behavior
-                // Information from https://gbdev.io/pandocs/Memory_Map.html#fea0-feff-range
-                0
+                self.unusable.read(address, self.is_oam_blocked())
             }
             IO_RANGE_START_ADDRESS..=IO_RANGE_END_ADDRESS => self.read_io(address),
             HIGH_RAM_START_ADDRESS..=HIGH_RAM_END_ADDRESS => self.ram.read_high_ram(address),
@@ -242,8 +315,7 @@ impl Memory for GBMemory {
             }
             OAM_START_ADDRESS..=OAM_END_ADDRESS => self.video.write_oam(address, value),
             NOT_USABLE_START_ADDRESS..=NOT_USABLE_END_ADDRESS => {
-                // TODO: Implement complete behavior
-                // Information from https://gbdev.io/pandocs/Memory_Map.html#fea0-feff-range
+                self.unusable.write(address, value, self.is_oam_blocked())
             }
             IO_RANGE_START_ADDRESS..=IO_RANGE_END_ADDRESS => self.write_io(address, value),
             HIGH_RAM_START_ADDRESS..=HIGH_RAM_END_ADDRESS => {
@@ -253,3 +325,37 @@ impl Memory for GBMemory {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::mbc::no_controller::NoMemoryBankController;
+    use crate::model::HardwareModel;
+
+    fn create_memory() -> GBMemory {
+        GBMemory::new(
+            NoMemoryBankController::create(&[0u8; 0x8000], 0).unwrap(),
+            VideoController::new(),
+            RamController::new(),
+            JoypadInput::new(),
+            SerialTransfer::new(false),
+            SoundController::default(),
+            Timer::new(),
+            CGBRegisters::new(false),
+            UnusableMemory::new(HardwareModel::Dmg),
+        )
+    }
+
+    #[test]
+    fn unusable_range_is_blocked_during_oam_dma() {
+        let mut memory = create_memory();
+        assert_eq!(memory.read(NOT_USABLE_START_ADDRESS), 0x00);
+
+        memory.write(OAM_DMA_ADDRESS, 0xC0);
+        assert_eq!(memory.read(NOT_USABLE_START_ADDRESS), 0xFF);
+        memory.update(OAM_DMA_CYCLES - 4);
+        assert_eq!(memory.read(NOT_USABLE_START_ADDRESS), 0xFF);
+        memory.update(4);
+        assert_eq!(memory.read(NOT_USABLE_START_ADDRESS), 0x00);
+    }
+}
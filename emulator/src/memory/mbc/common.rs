@@ -1,27 +1,84 @@
 use crate::memory::mbc::interface::{RAM_BANK_SIZE, ROM_BANK_SIZE};
-use std::fs::File;
-use std::io::{BufReader, Read, Seek};
+use crate::memory::OPEN_BUS_VALUE;
+use log::warn;
+use std::cmp::Ordering;
 
 pub struct RomRamBanks {
     pub rom: Vec<u8>,
     pub ram: Vec<u8>,
 }
 
+/// Copies the rom data in the number of banks declared by the header.
+/// A truncated rom is mirrored when its size is a power of 2, like on a cartridge with fewer
+/// address lines, otherwise it is padded with the open bus value. The data after the declared
+/// banks is ignored.
 pub fn get_rom_ram_banks(
-    rom_reader: &mut BufReader<File>,
+    rom_data: &[u8],
     num_rom_banks: usize,
     num_ram_banks: usize,
-) -> Result<RomRamBanks, String> {
-    rom_reader
-        .rewind()
-        .map_err(|e| format!("Could not rewind the reader {:?}", e))?;
-    let mut rom = vec![0; num_rom_banks * ROM_BANK_SIZE];
-    rom_reader
-        .read_exact(&mut rom)
-        .map_err(|e| format!("Could not get the rom data {:?}", e))?;
-
-    Ok(RomRamBanks {
+) -> RomRamBanks {
+    let size = num_rom_banks * ROM_BANK_SIZE;
+    let mut rom = vec![OPEN_BUS_VALUE; size];
+    match rom_data.len().cmp(&size) {
+        Ordering::Equal => rom.copy_from_slice(rom_data),
+        Ordering::Greater => {
+            warn!(
+                "The rom has {} bytes but its header declares {} bytes, the extra data is ignored",
+                rom_data.len(),
+                size
+            );
+            rom.copy_from_slice(&rom_data[..size]);
+        }
+        Ordering::Less if rom_data.len().is_power_of_two() => {
+            warn!(
+                "The rom has {} bytes but its header declares {} bytes, the data is mirrored",
+                rom_data.len(),
+                size
+            );
+            for chunk in rom.chunks_mut(rom_data.len()) {
+                chunk.copy_from_slice(rom_data);
+            }
+        }
+        Ordering::Less => {
+            warn!(
+                "The rom has {} bytes but its header declares {} bytes, the data is padded",
+                rom_data.len(),
+                size
+            );
+            rom[..rom_data.len()].copy_from_slice(rom_data);
+        }
+    }
+
+    RomRamBanks {
         rom,
         ram: vec![0; num_ram_banks * RAM_BANK_SIZE],
-    })
+    }
+}
+
+/// Copies the save data in the ram, extra data is ignored and missing data is left untouched.
+pub fn load_ram(ram: &mut [u8], data: &[u8]) {
+    let size = ram.len().min(data.len());
+    ram[..size].copy_from_slice(&data[..size]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn truncated_rom_is_mirrored_or_padded() {
+        let RomRamBanks { rom, .. } = get_rom_ram_banks(&vec![0x42; ROM_BANK_SIZE], 4, 0);
+        assert_eq!(rom.len(), 4 * ROM_BANK_SIZE);
+        assert!(rom.iter().all(|value| *value == 0x42));
+
+        let RomRamBanks { rom, .. } = get_rom_ram_banks(&vec![0x42; ROM_BANK_SIZE + 1], 4, 0);
+        assert_eq!(rom[ROM_BANK_SIZE], 0x42);
+        assert_eq!(rom[ROM_BANK_SIZE + 1], OPEN_BUS_VALUE);
+    }
+
+    #[test]
+    fn oversized_rom_is_cut() {
+        let RomRamBanks { rom, .. } = get_rom_ram_banks(&vec![0x42; 3 * ROM_BANK_SIZE], 2, 1);
+        assert_eq!(rom.len(), 2 * ROM_BANK_SIZE);
+    }
 }
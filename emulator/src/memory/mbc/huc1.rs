@@ -0,0 +1,160 @@
+use crate::memory::mbc::common::{get_rom_ram_banks, load_ram, RomRamBanks};
+use crate::memory::mbc::interface::{
+    MemoryBankController, EXT_RAM_START_ADDRESS, RAM_BANK_SIZE, ROM_BANK_SIZE,
+};
+use crate::memory::OPEN_BUS_VALUE;
+use std::time::Duration;
+
+/// Information from: https://gbdev.io/pandocs/HuC1.html
+const IR_SELECT_VALUE: u8 = 0x0E;
+/// The IR register reads 0xC0 when no light is seen and 0xC1 otherwise.
+const IR_NO_LIGHT_VALUE: u8 = 0xC0;
+const IR_LIGHT_BIT: u8 = 0b1;
+const IR_LED_BIT: u8 = 0b1;
+const ROM_BANK_NUMBER_MASK: u8 = 0b11_1111;
+const RAM_BANK_NUMBER_MASK: u8 = 0b11;
+
+pub struct HuC1BankController {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+
+    mask_rom_bank_number: usize,
+    rom_bank_number: u8,
+    ram_bank_number: u8,
+
+    is_ir_selected: bool,
+    is_led_on: bool,
+    is_receiving_light: bool,
+}
+
+impl HuC1BankController {
+    pub fn create(
+        rom_data: &[u8],
+        num_rom_banks: usize,
+        num_ram_banks: usize,
+    ) -> Result<Box<dyn MemoryBankController>, String> {
+        let RomRamBanks { rom, ram } = get_rom_ram_banks(rom_data, num_rom_banks, num_ram_banks);
+
+        Ok(Box::new(Self::new(rom, ram, num_rom_banks)))
+    }
+
+    fn new(rom: Vec<u8>, ram: Vec<u8>, num_rom_banks: usize) -> Self {
+        Self {
+            rom,
+            ram,
+            // The num_rom_banks is always a power of 2.
+            // This make the number of rom banks - 1, the mask for the rom bank number.
+            mask_rom_bank_number: num_rom_banks.saturating_sub(1),
+            rom_bank_number: 1,
+            ram_bank_number: 0,
+            is_ir_selected: false,
+            is_led_on: false,
+            is_receiving_light: false,
+        }
+    }
+
+    fn get_ext_ram_relative_address(&self, address: u16) -> usize {
+        let relative_address = (address - EXT_RAM_START_ADDRESS) as usize
+            + (self.ram_bank_number as usize * RAM_BANK_SIZE);
+        relative_address % self.ram.len()
+    }
+}
+
+impl MemoryBankController for HuC1BankController {
+    fn write_rom(&mut self, address: u16, value: u8) {
+        match address {
+            // RAM / IR select, any value other than 0x0E maps the RAM.
+            0x0000..=0x1FFF => {
+                self.is_ir_selected = value == IR_SELECT_VALUE;
+            }
+            // ROM bank number, bank 0 is not translated to 1.
+            0x2000..=0x3FFF => {
+                self.rom_bank_number = value & ROM_BANK_NUMBER_MASK;
+            }
+            // RAM bank number.
+            0x4000..=0x5FFF => {
+                self.ram_bank_number = value & RAM_BANK_NUMBER_MASK;
+            }
+            _ => { /* Nothing to do */ }
+        }
+    }
+
+    fn read_rom(&self, address: u16) -> u8 {
+        match address {
+            0x0000..=0x3FFF => self.rom[address as usize],
+            0x4000..=0x7FFF => {
+                let rom_bank_number = self.rom_bank_number as usize & self.mask_rom_bank_number;
+                self.rom[rom_bank_number * ROM_BANK_SIZE + (address as usize & 0x3FFF)]
+            }
+            _ => OPEN_BUS_VALUE,
+        }
+    }
+
+    fn write_ext_ram(&mut self, address: u16, value: u8) {
+        if self.is_ir_selected {
+            self.is_led_on = value & IR_LED_BIT != 0;
+            return;
+        }
+        if self.ram.is_empty() {
+            return;
+        }
+        let relative_address = self.get_ext_ram_relative_address(address);
+        self.ram[relative_address] = value;
+    }
+
+    fn read_ext_ram(&self, address: u16) -> u8 {
+        if self.is_ir_selected {
+            return if self.is_receiving_light {
+                IR_NO_LIGHT_VALUE | IR_LIGHT_BIT
+            } else {
+                IR_NO_LIGHT_VALUE
+            };
+        }
+        if self.ram.is_empty() {
+            return OPEN_BUS_VALUE;
+        }
+        self.ram[self.get_ext_ram_relative_address(address)]
+    }
+
+    fn update(&mut self, _duration: Duration) {
+        // Nothing to do.
+    }
+
+    fn save_battery(&self) -> Vec<u8> {
+        self.ram.clone()
+    }
+
+    fn load_battery(&mut self, data: &[u8]) {
+        load_ram(&mut self.ram, data);
+    }
+
+    fn is_infrared_led_on(&self) -> bool {
+        self.is_led_on
+    }
+
+    fn set_infrared_received(&mut self, is_receiving_light: bool) {
+        self.is_receiving_light = is_receiving_light;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ir_register_replaces_ram_when_selected() {
+        let mut controller = HuC1BankController::new(vec![], vec![0; RAM_BANK_SIZE], 2);
+        controller.write_ext_ram(0xA000, 0x42);
+
+        controller.write_rom(0x0000, IR_SELECT_VALUE);
+        assert_eq!(controller.read_ext_ram(0xA000), 0xC0);
+        controller.set_infrared_received(true);
+        assert_eq!(controller.read_ext_ram(0xA000), 0xC1);
+
+        controller.write_ext_ram(0xA000, 0x01);
+        assert!(controller.is_infrared_led_on());
+
+        controller.write_rom(0x0000, 0x0A);
+        assert_eq!(controller.read_ext_ram(0xA000), 0x42);
+    }
+}
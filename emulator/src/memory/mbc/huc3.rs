@@ -0,0 +1,431 @@
+//! Information from: https://gbdev.io/pandocs/HuC3.html
+//!
+//! The RTC of the HuC3 is a small micro controller driven by commands. It has an internal memory
+//! of 256 nibbles, where the time is copied to or from, and which also holds the settings of the
+//! alarm and of the tone generator.
+
+use crate::memory::mbc::common::{get_rom_ram_banks, load_ram, RomRamBanks};
+use crate::memory::mbc::interface::{
+    MemoryBankController, EXT_RAM_START_ADDRESS, RAM_BANK_SIZE, ROM_BANK_SIZE,
+};
+use crate::memory::mbc::mbc3::RtcMode;
+use crate::memory::OPEN_BUS_VALUE;
+use log::info;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Values written to 0000-1FFF to select what is mapped at A000-BFFF.
+const MODE_RAM_READ_ONLY: u8 = 0x0;
+const MODE_RAM: u8 = 0xA;
+const MODE_RTC_COMMAND: u8 = 0xB;
+const MODE_RTC_RESPONSE: u8 = 0xC;
+const MODE_RTC_SEMAPHORE: u8 = 0xD;
+const MODE_IR: u8 = 0xE;
+const MODE_MASK: u8 = 0x0F;
+
+const ROM_BANK_NUMBER_MASK: u8 = 0b111_1111;
+const RAM_BANK_NUMBER_MASK: u8 = 0b11;
+
+const IR_NO_LIGHT_VALUE: u8 = 0xC0;
+const IR_LIGHT_BIT: u8 = 0b1;
+const IR_LED_BIT: u8 = 0b1;
+
+/// Commands are written as `0cccaaaa`, `ccc` being the command and `aaaa` its argument.
+const COMMAND_READ: u8 = 0x1;
+const COMMAND_WRITE: u8 = 0x3;
+const COMMAND_SET_ADDRESS_LOW: u8 = 0x4;
+const COMMAND_SET_ADDRESS_HIGH: u8 = 0x5;
+const COMMAND_EXTENDED: u8 = 0x6;
+const EXTENDED_LATCH_TIME: u8 = 0x0;
+const EXTENDED_SET_TIME: u8 = 0x1;
+const EXTENDED_STATUS: u8 = 0x2;
+const EXTENDED_TONE: u8 = 0xE;
+/// Writing the semaphore with the bit 0 cleared executes the command, it reads 1 when done.
+const SEMAPHORE_EXECUTE_BIT: u8 = 0b1;
+const SEMAPHORE_READY_VALUE: u8 = 0xFF;
+
+const RTC_MEMORY_SIZE: usize = 0x100;
+const RTC_MEMORY_MINUTES_ADDRESS: usize = 0x00;
+const RTC_MEMORY_DAYS_ADDRESS: usize = 0x03;
+const RTC_MEMORY_TONE_ADDRESS: usize = 0x26;
+const RTC_MEMORY_TONE_ENABLE_ADDRESS: usize = 0x27;
+/// Minutes and days are both 12 bits counters, stored as 3 nibbles.
+const COUNTER_NIBBLES: usize = 3;
+const DAY_COUNTER_SIZE: u64 = 0x1000;
+const MINUTES_PER_DAY: u64 = 24 * 60;
+
+/// The battery save contains the cartridge RAM, the RTC memory (one nibble per byte), the minutes
+/// and days counters (4 bytes each) and the unix timestamp of the save (8 bytes).
+const RTC_SAVE_SIZE: usize = RTC_MEMORY_SIZE + 4 + 4 + 8;
+
+pub struct HuC3BankController {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+
+    mask_rom_bank_number: usize,
+    rom_bank_number: u8,
+    ram_bank_number: u8,
+    mode: u8,
+
+    rtc: HuC3Rtc,
+
+    is_led_on: bool,
+    is_receiving_light: bool,
+}
+
+impl HuC3BankController {
+    pub fn create(
+        rom_data: &[u8],
+        num_rom_banks: usize,
+        num_ram_banks: usize,
+        rtc_mode: RtcMode,
+    ) -> Result<Box<dyn MemoryBankController>, String> {
+        let RomRamBanks { rom, ram } = get_rom_ram_banks(rom_data, num_rom_banks, num_ram_banks);
+
+        Ok(Box::new(Self::new(rom, ram, num_rom_banks, rtc_mode)))
+    }
+
+    fn new(rom: Vec<u8>, ram: Vec<u8>, num_rom_banks: usize, rtc_mode: RtcMode) -> Self {
+        Self {
+            rom,
+            ram,
+            // The num_rom_banks is always a power of 2.
+            // This make the number of rom banks - 1, the mask for the rom bank number.
+            mask_rom_bank_number: num_rom_banks.saturating_sub(1),
+            rom_bank_number: 1,
+            ram_bank_number: 0,
+            mode: MODE_RAM_READ_ONLY,
+            rtc: HuC3Rtc::new(rtc_mode),
+            is_led_on: false,
+            is_receiving_light: false,
+        }
+    }
+
+    fn get_ext_ram_relative_address(&self, address: u16) -> usize {
+        let relative_address = (address - EXT_RAM_START_ADDRESS) as usize
+            + (self.ram_bank_number as usize * RAM_BANK_SIZE);
+        relative_address % self.ram.len()
+    }
+}
+
+impl MemoryBankController for HuC3BankController {
+    fn write_rom(&mut self, address: u16, value: u8) {
+        match address {
+            // Selects what is mapped at A000-BFFF.
+            0x0000..=0x1FFF => {
+                self.mode = value & MODE_MASK;
+            }
+            0x2000..=0x3FFF => {
+                self.rom_bank_number = value & ROM_BANK_NUMBER_MASK;
+            }
+            0x4000..=0x5FFF => {
+                self.ram_bank_number = value & RAM_BANK_NUMBER_MASK;
+            }
+            _ => { /* Nothing to do */ }
+        }
+    }
+
+    fn read_rom(&self, address: u16) -> u8 {
+        match address {
+            0x0000..=0x3FFF => self.rom[address as usize],
+            0x4000..=0x7FFF => {
+                let rom_bank_number = self.rom_bank_number as usize & self.mask_rom_bank_number;
+                self.rom[rom_bank_number * ROM_BANK_SIZE + (address as usize & 0x3FFF)]
+            }
+            _ => OPEN_BUS_VALUE,
+        }
+    }
+
+    fn write_ext_ram(&mut self, address: u16, value: u8) {
+        match self.mode {
+            MODE_RAM if !self.ram.is_empty() => {
+                let relative_address = self.get_ext_ram_relative_address(address);
+                self.ram[relative_address] = value;
+            }
+            MODE_RTC_COMMAND => self.rtc.write_command(value),
+            MODE_RTC_SEMAPHORE if value & SEMAPHORE_EXECUTE_BIT == 0 => self.rtc.execute_command(),
+            MODE_IR => self.is_led_on = value & IR_LED_BIT != 0,
+            _ => { /* Nothing to do */ }
+        }
+    }
+
+    fn read_ext_ram(&self, address: u16) -> u8 {
+        match self.mode {
+            MODE_RAM_READ_ONLY | MODE_RAM if !self.ram.is_empty() => {
+                self.ram[self.get_ext_ram_relative_address(address)]
+            }
+            MODE_RTC_RESPONSE => self.rtc.read_response(),
+            // Commands are executed instantly, the RTC is always ready.
+            MODE_RTC_SEMAPHORE => SEMAPHORE_READY_VALUE,
+            MODE_IR if self.is_receiving_light => IR_NO_LIGHT_VALUE | IR_LIGHT_BIT,
+            MODE_IR => IR_NO_LIGHT_VALUE,
+            _ => OPEN_BUS_VALUE,
+        }
+    }
+
+    fn update(&mut self, duration: Duration) {
+        self.rtc.add_time(duration);
+    }
+
+    fn save_battery(&self) -> Vec<u8> {
+        let mut data = self.ram.clone();
+        data.extend(self.rtc.save(SystemTime::now()));
+        data
+    }
+
+    fn load_battery(&mut self, data: &[u8]) {
+        load_ram(&mut self.ram, data);
+        if data.len() > self.ram.len() {
+            self.rtc.load(&data[self.ram.len()..], SystemTime::now());
+        }
+    }
+
+    fn is_infrared_led_on(&self) -> bool {
+        self.is_led_on
+    }
+
+    fn set_infrared_received(&mut self, is_receiving_light: bool) {
+        self.is_receiving_light = is_receiving_light;
+    }
+}
+
+struct HuC3Rtc {
+    memory: [u8; RTC_MEMORY_SIZE],
+    address: u8,
+    command: u8,
+    response: u8,
+
+    minutes: u64,
+    days: u64,
+    sub_minute: Duration,
+    mode: RtcMode,
+
+    tone: Option<u8>,
+}
+
+impl HuC3Rtc {
+    fn new(mode: RtcMode) -> Self {
+        Self {
+            memory: [0; RTC_MEMORY_SIZE],
+            address: 0,
+            command: 0,
+            response: 0,
+            minutes: 0,
+            days: 0,
+            sub_minute: Duration::ZERO,
+            mode,
+            tone: None,
+        }
+    }
+
+    fn write_command(&mut self, value: u8) {
+        self.command = value & 0x7F;
+    }
+
+    /// The response contains the last command and its result.
+    fn read_response(&self) -> u8 {
+        (self.command & 0xF0) | self.response
+    }
+
+    fn execute_command(&mut self) {
+        let argument = self.command & 0x0F;
+        match self.command >> 4 {
+            COMMAND_READ => {
+                self.response = self.memory[self.address as usize];
+                self.address = self.address.wrapping_add(1);
+            }
+            COMMAND_WRITE => {
+                self.memory[self.address as usize] = argument;
+                self.address = self.address.wrapping_add(1);
+            }
+            COMMAND_SET_ADDRESS_LOW => self.address = (self.address & 0xF0) | argument,
+            COMMAND_SET_ADDRESS_HIGH => self.address = (self.address & 0x0F) | (argument << 4),
+            COMMAND_EXTENDED => self.execute_extended_command(argument),
+            _ => { /* Nothing to do */ }
+        }
+    }
+
+    fn execute_extended_command(&mut self, argument: u8) {
+        match argument {
+            EXTENDED_LATCH_TIME => {
+                self.write_counter(RTC_MEMORY_MINUTES_ADDRESS, self.minutes);
+                self.write_counter(RTC_MEMORY_DAYS_ADDRESS, self.days);
+            }
+            EXTENDED_SET_TIME => {
+                self.minutes = self.read_counter(RTC_MEMORY_MINUTES_ADDRESS) % MINUTES_PER_DAY;
+                self.days = self.read_counter(RTC_MEMORY_DAYS_ADDRESS);
+                self.sub_minute = Duration::ZERO;
+            }
+            EXTENDED_STATUS => self.response = 1,
+            EXTENDED_TONE => self.update_tone(),
+            _ => { /* Nothing to do */ }
+        }
+    }
+
+    /// The tone generator plays the tone selected in the RTC memory while it is enabled.
+    /// There is no audio output, the changes are only logged.
+    fn update_tone(&mut self) {
+        let tone = (self.memory[RTC_MEMORY_TONE_ENABLE_ADDRESS] & 1 == 1)
+            .then_some(self.memory[RTC_MEMORY_TONE_ADDRESS]);
+        if tone != self.tone {
+            match tone {
+                Some(tone) => info!("HuC3 tone generator playing tone {}", tone),
+                None => info!("HuC3 tone generator stopped"),
+            }
+            self.tone = tone;
+        }
+    }
+
+    fn read_counter(&self, address: usize) -> u64 {
+        self.memory[address..address + COUNTER_NIBBLES]
+            .iter()
+            .rev()
+            .fold(0, |counter, nibble| {
+                (counter << 4) | (*nibble & 0x0F) as u64
+            })
+    }
+
+    fn write_counter(&mut self, address: usize, value: u64) {
+        for (index, nibble) in self.memory[address..address + COUNTER_NIBBLES]
+            .iter_mut()
+            .enumerate()
+        {
+            *nibble = ((value >> (4 * index)) & 0x0F) as u8;
+        }
+    }
+
+    fn add_time(&mut self, duration: Duration) {
+        let total = self.sub_minute + duration;
+        let elapsed_minutes = total.as_secs() / 60;
+        self.sub_minute = total - Duration::from_secs(elapsed_minutes * 60);
+
+        let minutes = self.minutes + elapsed_minutes;
+        self.minutes = minutes % MINUTES_PER_DAY;
+        self.days = (self.days + minutes / MINUTES_PER_DAY) % DAY_COUNTER_SIZE;
+    }
+
+    fn save(&self, now: SystemTime) -> Vec<u8> {
+        let timestamp = now
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or(0);
+        let mut data = Vec::with_capacity(RTC_SAVE_SIZE);
+        data.extend(self.memory);
+        data.extend((self.minutes as u32).to_le_bytes());
+        data.extend((self.days as u32).to_le_bytes());
+        data.extend(timestamp.to_le_bytes());
+        data
+    }
+
+    fn load(&mut self, data: &[u8], now: SystemTime) {
+        if data.len() < RTC_SAVE_SIZE {
+            return;
+        }
+        let (memory, counters) = data.split_at(RTC_MEMORY_SIZE);
+        for (cell, value) in self.memory.iter_mut().zip(memory) {
+            *cell = value & 0x0F;
+        }
+        self.minutes =
+            u32::from_le_bytes(counters[0..4].try_into().unwrap()) as u64 % MINUTES_PER_DAY;
+        self.days =
+            u32::from_le_bytes(counters[4..8].try_into().unwrap()) as u64 % DAY_COUNTER_SIZE;
+        self.sub_minute = Duration::ZERO;
+
+        if self.mode == RtcMode::WallClock {
+            let timestamp = u64::from_le_bytes(counters[8..16].try_into().unwrap());
+            let saved_time = UNIX_EPOCH + Duration::from_secs(timestamp);
+            if let Ok(elapsed) = now.duration_since(saved_time) {
+                self.add_time(elapsed);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_controller() -> HuC3BankController {
+        HuC3BankController::new(vec![], vec![0; RAM_BANK_SIZE], 2, RtcMode::Emulated)
+    }
+
+    fn send_command(controller: &mut HuC3BankController, command: u8, argument: u8) {
+        controller.write_rom(0x0000, MODE_RTC_COMMAND);
+        controller.write_ext_ram(0xA000, (command << 4) | argument);
+        controller.write_rom(0x0000, MODE_RTC_SEMAPHORE);
+        controller.write_ext_ram(0xA000, 0xFE);
+    }
+
+    fn read_counter(controller: &mut HuC3BankController, address: u8) -> u16 {
+        send_command(controller, COMMAND_SET_ADDRESS_LOW, address & 0x0F);
+        send_command(controller, COMMAND_SET_ADDRESS_HIGH, address >> 4);
+        let mut counter = 0;
+        for index in 0..COUNTER_NIBBLES {
+            send_command(controller, COMMAND_READ, 0);
+            controller.write_rom(0x0000, MODE_RTC_RESPONSE);
+            counter |= ((controller.read_ext_ram(0xA000) & 0x0F) as u16) << (4 * index);
+        }
+        counter
+    }
+
+    #[test]
+    fn ram_is_only_writable_in_ram_mode() {
+        let mut controller = create_controller();
+        controller.write_ext_ram(0xA000, 0x42);
+        assert_eq!(controller.read_ext_ram(0xA000), 0x00);
+
+        controller.write_rom(0x0000, MODE_RAM);
+        controller.write_ext_ram(0xA000, 0x42);
+        controller.write_rom(0x0000, MODE_RAM_READ_ONLY);
+        assert_eq!(controller.read_ext_ram(0xA000), 0x42);
+    }
+
+    #[test]
+    fn latched_time_is_read_through_commands() {
+        let mut controller = create_controller();
+        controller.update(Duration::from_secs((2 * MINUTES_PER_DAY + 75) * 60 + 30));
+
+        send_command(&mut controller, COMMAND_EXTENDED, EXTENDED_LATCH_TIME);
+        assert_eq!(read_counter(&mut controller, 0x00), 75);
+        assert_eq!(read_counter(&mut controller, 0x03), 2);
+    }
+
+    #[test]
+    fn time_is_set_from_written_memory() {
+        let mut controller = create_controller();
+        send_command(&mut controller, COMMAND_SET_ADDRESS_LOW, 0);
+        send_command(&mut controller, COMMAND_SET_ADDRESS_HIGH, 0);
+        for nibble in [0xF, 0x5, 0x0, 0x1, 0x0, 0x0] {
+            send_command(&mut controller, COMMAND_WRITE, nibble);
+        }
+        send_command(&mut controller, COMMAND_EXTENDED, EXTENDED_SET_TIME);
+        controller.update(Duration::from_secs(60));
+
+        send_command(&mut controller, COMMAND_EXTENDED, EXTENDED_LATCH_TIME);
+        assert_eq!(read_counter(&mut controller, 0x00), 0x60);
+        assert_eq!(read_counter(&mut controller, 0x03), 1);
+    }
+
+    #[test]
+    fn ir_mode_reports_received_light() {
+        let mut controller = create_controller();
+        controller.write_rom(0x0000, MODE_IR);
+        assert_eq!(controller.read_ext_ram(0xA000), 0xC0);
+
+        controller.set_infrared_received(true);
+        assert_eq!(controller.read_ext_ram(0xA000), 0xC1);
+
+        controller.write_ext_ram(0xA000, 0x01);
+        assert!(controller.is_infrared_led_on());
+    }
+
+    #[test]
+    fn battery_save_round_trip() {
+        let mut controller = create_controller();
+        controller.update(Duration::from_secs(3 * 60));
+
+        let mut loaded = create_controller();
+        loaded.load_battery(&controller.save_battery());
+        send_command(&mut loaded, COMMAND_EXTENDED, EXTENDED_LATCH_TIME);
+        assert_eq!(read_counter(&mut loaded, 0x00), 3);
+    }
+}
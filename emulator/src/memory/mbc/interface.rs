@@ -1,3 +1,4 @@
+use crate::joypad::Tilt;
 use std::time::Duration;
 
 pub const ROM_START_ADDRESS: u16 = 0x0000;
@@ -8,6 +9,14 @@ pub const EXT_RAM_START_ADDRESS: u16 = 0xA000;
 pub const EXT_RAM_END_ADDRESS: u16 = 0xBFFF;
 pub const ROM_BANK_SIZE: usize = 0x4000;
 pub const RAM_BANK_SIZE: usize = 0x2000;
+/// The logo is checked in the cartridge header and in the header of each game of a multicart.
+pub const ADDRESS_NINTENDO_LOGO: usize = 0x104;
+/// Information from: https://gbdev.io/pandocs/The_Cartridge_Header.html#0104-0133--nintendo-logo
+pub const NINTENDO_LOGO: [u8; 48] = [
+    0xCE, 0xED, 0x66, 0x66, 0xCC, 0x0D, 0x00, 0x0B, 0x03, 0x73, 0x00, 0x83, 0x00, 0x0C, 0x00, 0x0D,
+    0x00, 0x08, 0x11, 0x1F, 0x88, 0x89, 0x00, 0x0E, 0xDC, 0xCC, 0x6E, 0xE6, 0xDD, 0xDD, 0xD9, 0x99,
+    0xBB, 0xBB, 0x67, 0x63, 0x6E, 0x0E, 0xEC, 0xCC, 0xDD, 0xDC, 0x99, 0x9F, 0xBB, 0xB9, 0x33, 0x3E,
+];
 
 pub trait MemoryBankController: Send {
     /// This method handles the writes for addresses in range [0x0000, 0x7FFFF]
@@ -24,4 +33,28 @@ pub trait MemoryBankController: Send {
 
     /// This method should be called after each instruction to ensure that the RTC registers are updated appropriately.
     fn update(&mut self, duration: Duration);
+
+    /// Returns the content of the battery backed memory to store in the save file.
+    fn save_battery(&self) -> Vec<u8> {
+        Vec::new()
+    }
+
+    /// Restores the battery backed memory from the content of a save file.
+    fn load_battery(&mut self, _data: &[u8]) {}
+
+    /// Returns the new state of the rumble motor if it changed since the last call.
+    fn take_rumble_event(&mut self) -> Option<bool> {
+        None
+    }
+
+    /// Updates the tilt measured by the cartridge accelerometer.
+    fn set_tilt(&mut self, _tilt: Tilt) {}
+
+    /// Returns true if the cartridge infrared LED is on.
+    fn is_infrared_led_on(&self) -> bool {
+        false
+    }
+
+    /// Updates whether the cartridge infrared receiver sees light.
+    fn set_infrared_received(&mut self, _is_receiving_light: bool) {}
 }
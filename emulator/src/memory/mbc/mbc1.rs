@@ -1,15 +1,26 @@
-use crate::memory::mbc::common::{get_rom_ram_banks, RomRamBanks};
-use crate::memory::mbc::interface::{MemoryBankController, EXT_RAM_START_ADDRESS, RAM_BANK_SIZE};
-use std::fs::File;
-use std::io::BufReader;
-use std::ops::Shl;
+use crate::memory::mbc::common::{get_rom_ram_banks, load_ram, RomRamBanks};
+use crate::memory::mbc::interface::{
+    MemoryBankController, ADDRESS_NINTENDO_LOGO, EXT_RAM_START_ADDRESS, NINTENDO_LOGO,
+    RAM_BANK_SIZE, ROM_BANK_SIZE,
+};
+use crate::memory::OPEN_BUS_VALUE;
 use std::time::Duration;
 
+/// Number of bits of the rom bank number register used for the bank number.
+/// Information from: https://gbdev.io/pandocs/MBC1.html#20003fff--rom-bank-number-write-only
+const ROM_BANK_NUMBER_BITS: u8 = 5;
+/// Multicart cartridges (MBC1M) wire the upper bits one bit lower.
+/// Information from: https://gbdev.io/pandocs/MBC1.html#mbc1m-1-mib-multi-game-compilation-carts
+const MULTICART_ROM_BANK_NUMBER_BITS: u8 = 4;
+const MULTICART_NUM_ROM_BANKS: usize = 64;
+const MULTICART_GAME_NUM_ROM_BANKS: usize = 0x10;
+
 pub struct MBC1BankController {
     rom: Vec<u8>,
     ram: Vec<u8>,
 
-    mask_rom_bank_number: u8,
+    mask_rom_bank_number: usize,
+    rom_bank_number_bits: u8,
     rom_bank_number: u8,
     ram_bank_number_or_rom_upper_bits: u8,
 
@@ -19,29 +30,70 @@ pub struct MBC1BankController {
 
 impl MBC1BankController {
     pub fn create(
-        rom_reader: &mut BufReader<File>,
+        rom_data: &[u8],
         num_rom_banks: usize,
         num_ram_banks: usize,
     ) -> Result<Box<dyn MemoryBankController>, String> {
-        let RomRamBanks { rom, ram } = get_rom_ram_banks(rom_reader, num_rom_banks, num_ram_banks)?;
+        let RomRamBanks { rom, ram } = get_rom_ram_banks(rom_data, num_rom_banks, num_ram_banks);
+
+        Ok(Box::new(Self::new(rom, ram, num_rom_banks)))
+    }
 
-        let max_rom_bank_number = num_rom_banks.saturating_sub(1) as u8;
+    fn new(rom: Vec<u8>, ram: Vec<u8>, num_rom_banks: usize) -> Self {
+        let rom_bank_number_bits = if is_multicart(&rom, num_rom_banks) {
+            MULTICART_ROM_BANK_NUMBER_BITS
+        } else {
+            ROM_BANK_NUMBER_BITS
+        };
 
-        Ok(Box::new(Self {
+        Self {
             rom,
             ram,
             // The num_rom_banks is always a power of 2.
             // This make the number of rom banks - 1, the mask for the rom bank number.
-            mask_rom_bank_number: max_rom_bank_number,
+            mask_rom_bank_number: num_rom_banks.saturating_sub(1),
+            rom_bank_number_bits,
             rom_bank_number: 1,
             ram_bank_number_or_rom_upper_bits: 0,
 
             is_ram_enabled: false,
             is_advanced_banking_mode_enabled: false,
-        }))
+        }
+    }
+
+    fn get_upper_rom_bank_bits(&self) -> usize {
+        (self.ram_bank_number_or_rom_upper_bits as usize) << self.rom_bank_number_bits
+    }
+
+    /// Information from: https://gbdev.io/pandocs/MBC1.html#00003fff--rom-bank-x0-read-only
+    fn get_rom_bank_0_number(&self) -> usize {
+        if self.is_advanced_banking_mode_enabled {
+            self.get_upper_rom_bank_bits() & self.mask_rom_bank_number
+        } else {
+            0
+        }
+    }
+
+    /// Information from: https://gbdev.io/pandocs/MBC1.html#40007fff--rom-bank-01-7f-read-only
+    fn get_rom_bank_n_number(&self) -> usize {
+        let lower_bits_mask = (1usize << self.rom_bank_number_bits) - 1;
+        let lower_bits = self.rom_bank_number as usize & lower_bits_mask;
+        (self.get_upper_rom_bank_bits() | lower_bits) & self.mask_rom_bank_number
     }
 }
 
+/// MBC1M cartridges are 1 MiB compilations where each game starts with its own header.
+///
+/// Detection is done by looking for the Nintendo logo in the header of the second game.
+/// Information from: https://gbdev.io/pandocs/MBC1.html#mbc1m-1-mib-multi-game-compilation-carts
+fn is_multicart(rom: &[u8], num_rom_banks: usize) -> bool {
+    if num_rom_banks != MULTICART_NUM_ROM_BANKS {
+        return false;
+    }
+    let logo_address = MULTICART_GAME_NUM_ROM_BANKS * ROM_BANK_SIZE + ADDRESS_NINTENDO_LOGO;
+    rom.get(logo_address..logo_address + NINTENDO_LOGO.len()) == Some(&NINTENDO_LOGO[..])
+}
+
 impl MemoryBankController for MBC1BankController {
     fn write_rom(&mut self, address: u16, value: u8) {
         match address {
@@ -51,11 +103,12 @@ impl MemoryBankController for MBC1BankController {
             }
             // https://gbdev.io/pandocs/MBC1.html#20003fff--rom-bank-number-write-only
             0x2000..=0x3FFF => {
+                // The 0 -> 1 translation uses the full 5 bits, even for multicart cartridges.
                 let mut raw_rom_bank_number = value & 0b1_1111;
                 if raw_rom_bank_number == 0 {
                     raw_rom_bank_number = 1;
                 }
-                self.rom_bank_number = raw_rom_bank_number & self.mask_rom_bank_number;
+                self.rom_bank_number = raw_rom_bank_number;
             }
             // https://gbdev.io/pandocs/MBC1.html#40005fff--ram-bank-number--or--upper-bits-of-rom-bank-number-write-only
             0x4000..=0x5FFF => {
@@ -72,25 +125,14 @@ impl MemoryBankController for MBC1BankController {
     fn read_rom(&self, address: u16) -> u8 {
         match address {
             0x0000..=0x3FFF => {
-                // https://gbdev.io/pandocs/MBC1.html#00003fff
-                let address = if self.is_advanced_banking_mode_enabled {
-                    address as usize + (self.ram_bank_number_or_rom_upper_bits as usize).shl(19)
-                } else {
-                    address as usize
-                };
-                self.rom[address]
+                let bank_address = self.get_rom_bank_0_number() * ROM_BANK_SIZE;
+                self.rom[bank_address + address as usize]
             }
             0x4000..=0x7FFF => {
-                // https://gbdev.io/pandocs/MBC1.html#40007fff
-                let address = (address as usize & 0x3FFF) + (self.rom_bank_number as usize).shl(14);
-                let address = if self.is_advanced_banking_mode_enabled {
-                    address + (self.ram_bank_number_or_rom_upper_bits as usize).shl(19)
-                } else {
-                    address
-                };
-                self.rom[address]
+                let bank_address = self.get_rom_bank_n_number() * ROM_BANK_SIZE;
+                self.rom[bank_address + (address as usize & 0x3FFF)]
             }
-            _ => 0,
+            _ => OPEN_BUS_VALUE,
         }
     }
 
@@ -100,27 +142,37 @@ impl MemoryBankController for MBC1BankController {
         if !self.is_ram_enabled || self.ram.is_empty() {
             return;
         }
-        self.ram[get_ext_ram_relative_address(
+        let relative_address = get_ext_ram_relative_address(
             address,
             self.ram_bank_number_or_rom_upper_bits,
             self.is_advanced_banking_mode_enabled,
-        )] = value;
+        ) % self.ram.len();
+        self.ram[relative_address] = value;
     }
 
     fn read_ext_ram(&self, address: u16) -> u8 {
-        if !self.is_ram_enabled {
-            return 0xFF;
+        if !self.is_ram_enabled || self.ram.is_empty() {
+            return OPEN_BUS_VALUE;
         }
-        self.ram[get_ext_ram_relative_address(
+        let relative_address = get_ext_ram_relative_address(
             address,
             self.ram_bank_number_or_rom_upper_bits,
             self.is_advanced_banking_mode_enabled,
-        )]
+        ) % self.ram.len();
+        self.ram[relative_address]
     }
 
     fn update(&mut self, _duration: Duration) {
         // Nothing to do.
     }
+
+    fn save_battery(&self) -> Vec<u8> {
+        self.ram.clone()
+    }
+
+    fn load_battery(&mut self, data: &[u8]) {
+        load_ram(&mut self.ram, data);
+    }
 }
 
 fn get_ext_ram_relative_address(
@@ -134,3 +186,93 @@ fn get_ext_ram_relative_address(
         (absolute_address - EXT_RAM_START_ADDRESS) as usize
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_rom(num_rom_banks: usize) -> Vec<u8> {
+        let mut rom = vec![0u8; num_rom_banks * ROM_BANK_SIZE];
+        for bank in 0..num_rom_banks {
+            rom[bank * ROM_BANK_SIZE] = bank as u8;
+        }
+        rom
+    }
+
+    fn create_multicart_rom() -> Vec<u8> {
+        let mut rom = create_rom(MULTICART_NUM_ROM_BANKS);
+        for game in 0..4 {
+            let logo_address =
+                game * MULTICART_GAME_NUM_ROM_BANKS * ROM_BANK_SIZE + ADDRESS_NINTENDO_LOGO;
+            rom[logo_address..logo_address + NINTENDO_LOGO.len()].copy_from_slice(&NINTENDO_LOGO);
+        }
+        rom
+    }
+
+    #[test]
+    fn upper_bits_select_rom_bank_in_both_modes() {
+        let mut controller = MBC1BankController::new(create_rom(128), vec![], 128);
+
+        controller.write_rom(0x2000, 0x02);
+        controller.write_rom(0x4000, 0x01);
+        assert_eq!(controller.read_rom(0x4000), 0x22);
+        assert_eq!(controller.read_rom(0x0000), 0x00);
+
+        controller.write_rom(0x6000, 0x01);
+        assert_eq!(controller.read_rom(0x4000), 0x22);
+        assert_eq!(controller.read_rom(0x0000), 0x20);
+    }
+
+    #[test]
+    fn rom_bank_number_is_masked_by_rom_size() {
+        let mut controller = MBC1BankController::new(create_rom(8), vec![], 8);
+
+        controller.write_rom(0x2000, 0x1A);
+        controller.write_rom(0x4000, 0x03);
+        assert_eq!(controller.read_rom(0x4000), 0x02);
+
+        controller.write_rom(0x6000, 0x01);
+        assert_eq!(controller.read_rom(0x0000), 0x00);
+    }
+
+    #[test]
+    fn multicart_is_detected_from_second_game_logo() {
+        assert!(is_multicart(
+            &create_multicart_rom(),
+            MULTICART_NUM_ROM_BANKS
+        ));
+        assert!(!is_multicart(
+            &create_rom(MULTICART_NUM_ROM_BANKS),
+            MULTICART_NUM_ROM_BANKS
+        ));
+    }
+
+    #[test]
+    fn multicart_uses_4_bits_bank_number() {
+        let mut controller =
+            MBC1BankController::new(create_multicart_rom(), vec![], MULTICART_NUM_ROM_BANKS);
+
+        controller.write_rom(0x2000, 0x12);
+        controller.write_rom(0x4000, 0x01);
+        assert_eq!(controller.read_rom(0x4000), 0x12);
+
+        controller.write_rom(0x4000, 0x02);
+        controller.write_rom(0x6000, 0x01);
+        assert_eq!(controller.read_rom(0x0000), 0x20);
+        assert_eq!(controller.read_rom(0x4000), 0x22);
+    }
+
+    #[test]
+    fn multicart_bank_zero_translation_uses_5_bits() {
+        let mut controller =
+            MBC1BankController::new(create_multicart_rom(), vec![], MULTICART_NUM_ROM_BANKS);
+
+        // 0x10 is not translated to 1 since the check is done on 5 bits, it maps to bank 0 of the game.
+        controller.write_rom(0x2000, 0x10);
+        controller.write_rom(0x4000, 0x03);
+        assert_eq!(controller.read_rom(0x4000), 0x30);
+
+        controller.write_rom(0x2000, 0x00);
+        assert_eq!(controller.read_rom(0x4000), 0x31);
+    }
+}
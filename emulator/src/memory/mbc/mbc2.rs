@@ -0,0 +1,181 @@
+use crate::memory::mbc::common::{get_rom_ram_banks, RomRamBanks};
+use crate::memory::mbc::interface::{
+    MemoryBankController, EXT_RAM_START_ADDRESS, ROM_BANK_0_END_ADDRESS,
+    ROM_BANK_1_N_END_ADDRESS, ROM_BANK_1_N_START_ADDRESS, ROM_BANK_SIZE,
+};
+use crate::memory::OPEN_BUS_VALUE;
+use std::time::Duration;
+
+/// Information from: https://gbdev.io/pandocs/MBC2.html#a000a1ff--built-in-ram
+const BUILT_IN_RAM_SIZE: usize = 0x200;
+const BUILT_IN_RAM_ADDRESS_MASK: u16 = 0x1FF;
+const BUILT_IN_RAM_UNUSED_BITS: u8 = 0xF0;
+/// Information from: https://gbdev.io/pandocs/MBC2.html#00003fff--ram-enable-rom-bank-number-write-only
+const REGISTER_SELECT_BIT: u16 = 1 << 8;
+const ROM_BANK_NUMBER_MASK: u8 = 0x0F;
+
+pub struct MBC2BankController {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+
+    mask_rom_bank_number: u8,
+    rom_bank_number: u8,
+    is_ram_enabled: bool,
+}
+
+impl MBC2BankController {
+    pub fn create(
+        rom_data: &[u8],
+        num_rom_banks: usize,
+    ) -> Result<Box<dyn MemoryBankController>, String> {
+        // The RAM is built in the controller, the cartridge header declares no RAM for MBC2.
+        let RomRamBanks { rom, .. } = get_rom_ram_banks(rom_data, num_rom_banks, 0);
+
+        Ok(Box::new(Self::new(rom, num_rom_banks)))
+    }
+
+    fn new(rom: Vec<u8>, num_rom_banks: usize) -> Self {
+        Self {
+            rom,
+            ram: vec![0; BUILT_IN_RAM_SIZE],
+            // The num_rom_banks is always a power of 2.
+            // This make the number of rom banks - 1, the mask for the rom bank number.
+            mask_rom_bank_number: (num_rom_banks.saturating_sub(1) as u8) & ROM_BANK_NUMBER_MASK,
+            rom_bank_number: 1,
+            is_ram_enabled: false,
+        }
+    }
+}
+
+impl MemoryBankController for MBC2BankController {
+    fn write_rom(&mut self, address: u16, value: u8) {
+        match address {
+            // https://gbdev.io/pandocs/MBC2.html#00003fff--ram-enable-rom-bank-number-write-only
+            0x0000..=ROM_BANK_0_END_ADDRESS if address & REGISTER_SELECT_BIT == 0 => {
+                self.is_ram_enabled = value & 0x0F == 0x0A;
+            }
+            0x0000..=ROM_BANK_0_END_ADDRESS => {
+                let mut raw_rom_bank_number = value & ROM_BANK_NUMBER_MASK;
+                if raw_rom_bank_number == 0 {
+                    raw_rom_bank_number = 1;
+                }
+                self.rom_bank_number = raw_rom_bank_number & self.mask_rom_bank_number;
+            }
+            _ => { /* Nothing to do */ }
+        }
+    }
+
+    fn read_rom(&self, address: u16) -> u8 {
+        match address {
+            // https://gbdev.io/pandocs/MBC2.html#00003fff--rom-bank-0-read-only
+            0x0000..=ROM_BANK_0_END_ADDRESS => self.rom[address as usize],
+            // https://gbdev.io/pandocs/MBC2.html#40007fff--rom-bank-01-0f-read-only
+            ROM_BANK_1_N_START_ADDRESS..=ROM_BANK_1_N_END_ADDRESS => {
+                let relative_address = (address - ROM_BANK_1_N_START_ADDRESS) as usize;
+                self.rom[relative_address + self.rom_bank_number as usize * ROM_BANK_SIZE]
+            }
+            _ => OPEN_BUS_VALUE,
+        }
+    }
+
+    fn write_ext_ram(&mut self, address: u16, value: u8) {
+        if !self.is_ram_enabled {
+            return;
+        }
+        // Only the lower 4 bits are stored: https://gbdev.io/pandocs/MBC2.html#a000a1ff--built-in-ram
+        self.ram[get_built_in_ram_address(address)] = value & !BUILT_IN_RAM_UNUSED_BITS;
+    }
+
+    fn read_ext_ram(&self, address: u16) -> u8 {
+        if !self.is_ram_enabled {
+            return OPEN_BUS_VALUE;
+        }
+        self.ram[get_built_in_ram_address(address)] | BUILT_IN_RAM_UNUSED_BITS
+    }
+
+    fn update(&mut self, _duration: Duration) {
+        // Nothing to do.
+    }
+
+    fn save_battery(&self) -> Vec<u8> {
+        self.ram.clone()
+    }
+
+    fn load_battery(&mut self, data: &[u8]) {
+        for (cell, value) in self.ram.iter_mut().zip(data) {
+            *cell = value & !BUILT_IN_RAM_UNUSED_BITS;
+        }
+    }
+}
+
+/// The 512 half-bytes are echoed through the whole external RAM range.
+/// Information from: https://gbdev.io/pandocs/MBC2.html#a200bfff--15-echoes-of-a000a1ff
+fn get_built_in_ram_address(absolute_address: u16) -> usize {
+    ((absolute_address - EXT_RAM_START_ADDRESS) & BUILT_IN_RAM_ADDRESS_MASK) as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_controller(num_rom_banks: usize) -> MBC2BankController {
+        let mut rom = vec![0u8; num_rom_banks * ROM_BANK_SIZE];
+        for bank in 0..num_rom_banks {
+            rom[bank * ROM_BANK_SIZE] = bank as u8;
+        }
+        MBC2BankController::new(rom, num_rom_banks)
+    }
+
+    #[test]
+    fn address_bit_8_selects_rom_bank_register() {
+        let mut controller = create_controller(16);
+
+        controller.write_rom(0x2100, 0x05);
+        assert_eq!(controller.read_rom(0x4000), 5);
+
+        // Bit 8 cleared: RAM enable register, rom bank is unchanged.
+        controller.write_rom(0x2000, 0x03);
+        assert_eq!(controller.read_rom(0x4000), 5);
+    }
+
+    #[test]
+    fn rom_bank_zero_is_mapped_to_one_and_masked() {
+        let mut controller = create_controller(8);
+
+        controller.write_rom(0x0100, 0x00);
+        assert_eq!(controller.read_rom(0x4000), 1);
+
+        controller.write_rom(0x0100, 0xFA);
+        assert_eq!(controller.read_rom(0x4000), 2);
+    }
+
+    #[test]
+    fn ram_stores_half_bytes_and_echoes() {
+        let mut controller = create_controller(2);
+        controller.write_ext_ram(0xA000, 0x12);
+        assert_eq!(controller.read_ext_ram(0xA000), OPEN_BUS_VALUE);
+
+        controller.write_rom(0x0000, 0x0A);
+        controller.write_ext_ram(0xA001, 0x12);
+
+        assert_eq!(controller.read_ext_ram(0xA001), 0xF2);
+        assert_eq!(controller.read_ext_ram(0xA201), 0xF2);
+        assert_eq!(controller.read_ext_ram(0xBE01), 0xF2);
+
+        controller.write_rom(0x0000, 0x00);
+        assert_eq!(controller.read_ext_ram(0xA001), OPEN_BUS_VALUE);
+    }
+
+    #[test]
+    fn battery_save_round_trip() {
+        let mut controller = create_controller(2);
+        controller.write_rom(0x0000, 0x0A);
+        controller.write_ext_ram(0xA1FF, 0x0C);
+
+        let mut loaded = create_controller(2);
+        loaded.load_battery(&controller.save_battery());
+        loaded.write_rom(0x0000, 0x0A);
+
+        assert_eq!(loaded.read_ext_ram(0xA1FF), 0xFC);
+    }
+}
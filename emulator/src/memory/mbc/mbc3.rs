@@ -1,18 +1,18 @@
-use crate::memory::mbc::common::{get_rom_ram_banks, RomRamBanks};
+use crate::memory::mbc::common::{get_rom_ram_banks, load_ram, RomRamBanks};
 use crate::memory::mbc::interface::{
     MemoryBankController, EXT_RAM_START_ADDRESS, RAM_BANK_SIZE, ROM_BANK_0_END_ADDRESS,
     ROM_BANK_1_N_END_ADDRESS, ROM_BANK_1_N_START_ADDRESS, ROM_BANK_SIZE,
 };
+use crate::memory::OPEN_BUS_VALUE;
 use macros::BitAccessor;
 use std::cmp::max;
-use std::fs::File;
-use std::io::BufReader;
-use std::time::Duration;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 pub struct MBC3BankController {
     rom: Vec<u8>,
     ram: Vec<u8>,
     rtc: RealTimeCounter,
+    has_rtc: bool,
 
     rom_index: u8,
     max_rom_index: u8,
@@ -22,17 +22,20 @@ pub struct MBC3BankController {
 }
 
 impl MBC3BankController {
+    /// Creates the controller, `rtc_mode` is None for cartridges without timer.
     pub fn create(
-        rom_reader: &mut BufReader<File>,
+        rom_data: &[u8],
         num_rom_banks: usize,
         num_ram_banks: usize,
+        rtc_mode: Option<RtcMode>,
     ) -> Result<Box<dyn MemoryBankController>, String> {
-        let RomRamBanks { rom, ram } = get_rom_ram_banks(rom_reader, num_rom_banks, num_ram_banks)?;
+        let RomRamBanks { rom, ram } = get_rom_ram_banks(rom_data, num_rom_banks, num_ram_banks);
 
         Ok(Box::new(Self {
             rom,
             ram,
-            rtc: RealTimeCounter::new(),
+            rtc: RealTimeCounter::new(rtc_mode.unwrap_or_default()),
+            has_rtc: rtc_mode.is_some(),
             rom_index: 0,
             max_rom_index: num_rom_banks.saturating_sub(1) as u8,
             ram_rtc_index: 0,
@@ -55,12 +58,9 @@ impl MemoryBankController for MBC3BankController {
                 }
             }
             // https://gbdev.io/pandocs/MBC3.html#2000-3fff---rom-bank-number-write-only
+            // The num_rom_banks is always a power of 2, the upper bits of the index are ignored.
             0x2000..=0x3FFF => {
-                if value <= self.max_rom_index {
-                    self.rom_index = value;
-                } else {
-                    panic!("Unhandled rom index {}", value);
-                }
+                self.rom_index = value & self.max_rom_index;
             }
             // https://gbdev.io/pandocs/MBC3.html#4000-5fff---ram-bank-number---or---rtc-register-select-write-only
             0x4000..=0x5FFF => {
@@ -94,13 +94,15 @@ impl MemoryBankController for MBC3BankController {
             return;
         }
         match self.ram_rtc_index {
+            0..=3 if self.ram.is_empty() => {}
             0..=3 if self.ram_rtc_index <= self.max_ram_index => {
-                self.ram[get_ext_ram_relative_address(address, self.max_ram_index)] = value
+                self.ram[get_ext_ram_relative_address(address, self.ram_rtc_index)] = value
             }
             8..=0xC => {
                 self.rtc.write(self.ram_rtc_index, value);
             }
-            _ => panic!("Trying to read invalid address {} is invalid ", address),
+            // Nothing is mapped for the other indexes.
+            _ => {}
         }
     }
 
@@ -108,26 +110,55 @@ impl MemoryBankController for MBC3BankController {
     /// Doc: https://gbdev.io/pandocs/MBC3.html#a000-bfff---rtc-register-08-0c-readwrite
     fn read_ext_ram(&self, address: u16) -> u8 {
         if !self.ram_rtc_enabled {
-            return 0;
+            return OPEN_BUS_VALUE;
         }
         match self.ram_rtc_index {
+            0..=3 if self.ram.is_empty() => OPEN_BUS_VALUE,
             0..=3 if self.ram_rtc_index <= self.max_ram_index => {
-                self.ram[get_ext_ram_relative_address(address, self.max_ram_index)]
+                self.ram[get_ext_ram_relative_address(address, self.ram_rtc_index)]
             }
+            0..=3 => OPEN_BUS_VALUE,
             8..=0xC => self.rtc.read(self.ram_rtc_index),
-            _ => panic!("Trying to read invalid address {} is invalid ", address),
+            // Nothing is mapped for the other indexes.
+            _ => OPEN_BUS_VALUE,
         }
     }
 
     fn update(&mut self, duration: Duration) {
         self.rtc.update(duration);
     }
+
+    /// The RTC registers are stored after the RAM using the format shared by BGB and VBA-M.
+    fn save_battery(&self) -> Vec<u8> {
+        let mut data = self.ram.clone();
+        if self.has_rtc {
+            data.extend(self.rtc.save(SystemTime::now()));
+        }
+        data
+    }
+
+    fn load_battery(&mut self, data: &[u8]) {
+        load_ram(&mut self.ram, data);
+        if self.has_rtc && data.len() > self.ram.len() {
+            self.rtc.load(&data[self.ram.len()..], SystemTime::now());
+        }
+    }
 }
 
 fn get_ext_ram_relative_address(absolute_address: u16, ram_index: u8) -> usize {
     (absolute_address - EXT_RAM_START_ADDRESS) as usize + (ram_index as usize * RAM_BANK_SIZE)
 }
 
+/// Selects how the real time clock behaves between emulator sessions.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum RtcMode {
+    /// The clock only advances with the emulated time, it is stopped while the emulator is closed.
+    Emulated,
+    /// The clock catches up with the host time elapsed since the save file was written.
+    #[default]
+    WallClock,
+}
+
 #[derive(BitAccessor, Debug, Copy, Clone, Default)]
 struct DayHighFlag {
     #[bit_offset_size(day_carry, 7, 1)]
@@ -135,6 +166,14 @@ struct DayHighFlag {
     #[bit_offset_size(day_high, 0, 1)]
     pub value: u8,
 }
+
+// https://gbdev.io/pandocs/MBC3.html#the-clock-counter-registers
+const SECONDS_MASK: u8 = 0b11_1111;
+const MINUTES_MASK: u8 = 0b11_1111;
+const HOURS_MASK: u8 = 0b1_1111;
+const DAY_HIGH_MASK: u8 = 0b1100_0001;
+const DAY_COUNTER_SIZE: u64 = 0x200;
+
 #[derive(Debug, Clone, Default)]
 struct RealTimeCounterRegister {
     microseconds: u64,
@@ -148,20 +187,56 @@ struct RealTimeCounterRegister {
 impl RealTimeCounterRegister {
     pub fn add_time(&mut self, duration: Duration) {
         // https://gbdev.io/pandocs/MBC3.html#the-clock-counter-registers
+        if self.day_high_flags.read_halt() == 1 {
+            return;
+        }
         let microseconds = duration.as_micros() + self.microseconds as u128;
         self.microseconds = (microseconds % 1_000_000) as u64;
         let seconds = (microseconds / 1_000_000) as u64 + self.seconds as u64;
         self.seconds = (seconds % 60) as u8;
         let minutes = (seconds / 60) + self.minutes as u64;
         self.minutes = (minutes % 60) as u8;
-        let hours = (seconds / 60) + self.hours as u64;
+        let hours = (minutes / 60) + self.hours as u64;
         self.hours = (hours % 24) as u8;
-        let days_low = (hours / 24) + self.day_low as u64;
-        self.day_low = (days_low % (u8::MAX as u64)) as u8;
-        let days_high = (days_low / (u8::MAX as u64)) + self.day_high_flags.value as u64;
-        self.day_high_flags.write_day_high((days_high & 0b1) as u8);
-        let flag = (self.day_high_flags.read_day_carry() > 0) || days_high > 1;
-        self.day_high_flags.write_day_carry(flag as u8);
+        let days = (hours / 24) + self.get_days();
+        self.set_days(days % DAY_COUNTER_SIZE);
+        // The carry stays set until the game clears it.
+        if days >= DAY_COUNTER_SIZE {
+            self.day_high_flags.write_day_carry(1);
+        }
+    }
+
+    /// The day counter is 9 bits wide: bit 0 of the day high register is its most significant bit.
+    fn get_days(&self) -> u64 {
+        self.day_low as u64 + ((self.day_high_flags.read_day_high() as u64) << 8)
+    }
+
+    fn set_days(&mut self, days: u64) {
+        self.day_low = (days & 0xFF) as u8;
+        self.day_high_flags.write_day_high(((days >> 8) & 0b1) as u8);
+    }
+
+    fn to_save(&self) -> [u32; 5] {
+        [
+            self.seconds as u32,
+            self.minutes as u32,
+            self.hours as u32,
+            self.day_low as u32,
+            self.day_high_flags.value as u32,
+        ]
+    }
+
+    fn from_save(values: &[u32]) -> Self {
+        Self {
+            microseconds: 0,
+            seconds: values[0] as u8 & SECONDS_MASK,
+            minutes: values[1] as u8 & MINUTES_MASK,
+            hours: values[2] as u8 & HOURS_MASK,
+            day_low: values[3] as u8,
+            day_high_flags: DayHighFlag {
+                value: values[4] as u8 & DAY_HIGH_MASK,
+            },
+        }
     }
 }
 
@@ -172,41 +247,41 @@ const RTC_HOURS_SELECT_VALUE: u8 = 0x0A;
 const RTC_DAY_LOW_SELECT_VALUE: u8 = 0x0B;
 const RTC_DAY_HIGH_SELECT_VALUE: u8 = 0x0C;
 
+/// Save format used by BGB and VBA-M: 5 registers, 5 latched registers (4 bytes each, little endian)
+/// followed by the unix timestamp of the save (8 bytes, some emulators only write 4 bytes).
+const RTC_SAVE_REGISTERS_SIZE: usize = 5 * 4 * 2;
+const RTC_SAVE_SIZE: usize = RTC_SAVE_REGISTERS_SIZE + 8;
+const RTC_SAVE_SIZE_32_BITS_TIMESTAMP: usize = RTC_SAVE_REGISTERS_SIZE + 4;
+
 struct RealTimeCounter {
     register: RealTimeCounterRegister,
     latched: RealTimeCounterRegister,
     latch_control: u8,
-    is_latched: bool,
+    mode: RtcMode,
 }
 
 impl RealTimeCounter {
-    pub fn new() -> Self {
+    pub fn new(mode: RtcMode) -> Self {
         Self {
             register: RealTimeCounterRegister::default(),
             latched: RealTimeCounterRegister::default(),
             // Set value different from 0 0r 1 to ensure the latch is not enabled by simply writing a 1.
             // https://gbdev.io/pandocs/MBC3.html#6000-7fff---latch-clock-data-write-only
             latch_control: 2,
-            is_latched: false,
+            mode,
         }
     }
 
+    /// Writing 0 then 1 copies the counter in the latched registers, which are the ones being read.
     pub fn latch(&mut self, value: u8) {
         if self.latch_control == 0 && value == 1 {
-            self.is_latched = !self.is_latched;
-            if self.is_latched {
-                self.latched = self.register.clone();
-            }
+            self.latched = self.register.clone();
         }
         self.latch_control = value;
     }
 
     pub fn read(&self, selected_value: u8) -> u8 {
-        let register = if self.is_latched {
-            &self.latched
-        } else {
-            &self.register
-        };
+        let register = &self.latched;
 
         match selected_value {
             RTC_SECONDS_SELECT_VALUE => register.seconds,
@@ -214,38 +289,264 @@ impl RealTimeCounter {
             RTC_HOURS_SELECT_VALUE => register.hours,
             RTC_DAY_LOW_SELECT_VALUE => register.day_low,
             RTC_DAY_HIGH_SELECT_VALUE => register.day_high_flags.value,
-            _ => 0,
+            _ => OPEN_BUS_VALUE,
         }
     }
 
+    /// Writes go to the counter and are directly visible in the latched registers.
     pub fn write(&mut self, selected_value: u8, value: u8) {
-        let register = if self.is_latched {
-            &mut self.latched
+        for register in [&mut self.register, &mut self.latched] {
+            match selected_value {
+                RTC_SECONDS_SELECT_VALUE => {
+                    register.seconds = value & SECONDS_MASK;
+                    // Writing the seconds resets the sub-second counter.
+                    register.microseconds = 0;
+                }
+                RTC_MINUTES_SELECT_VALUE => {
+                    register.minutes = value & MINUTES_MASK;
+                }
+                RTC_HOURS_SELECT_VALUE => {
+                    register.hours = value & HOURS_MASK;
+                }
+                RTC_DAY_LOW_SELECT_VALUE => {
+                    register.day_low = value;
+                }
+                RTC_DAY_HIGH_SELECT_VALUE => {
+                    register.day_high_flags.value = value & DAY_HIGH_MASK;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    pub fn update(&mut self, duration: Duration) {
+        self.register.add_time(duration);
+    }
+
+    pub fn save(&self, now: SystemTime) -> Vec<u8> {
+        let timestamp = now
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or(0);
+        let mut data = Vec::with_capacity(RTC_SAVE_SIZE);
+        for value in self.register.to_save().iter().chain(&self.latched.to_save()) {
+            data.extend(value.to_le_bytes());
+        }
+        data.extend(timestamp.to_le_bytes());
+        data
+    }
+
+    pub fn load(&mut self, data: &[u8], now: SystemTime) {
+        if data.len() < RTC_SAVE_SIZE_32_BITS_TIMESTAMP {
+            return;
+        }
+        let values: Vec<u32> = data[..RTC_SAVE_REGISTERS_SIZE]
+            .chunks_exact(4)
+            .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+            .collect();
+        self.register = RealTimeCounterRegister::from_save(&values[..5]);
+        self.latched = RealTimeCounterRegister::from_save(&values[5..]);
+
+        let timestamp_bytes = &data[RTC_SAVE_REGISTERS_SIZE..];
+        let timestamp = if timestamp_bytes.len() >= 8 {
+            u64::from_le_bytes(timestamp_bytes[..8].try_into().unwrap())
         } else {
-            &mut self.register
+            u32::from_le_bytes(timestamp_bytes[..4].try_into().unwrap()) as u64
         };
 
-        match selected_value {
-            RTC_SECONDS_SELECT_VALUE => {
-                register.seconds = value;
-            }
-            RTC_MINUTES_SELECT_VALUE => {
-                register.minutes = value;
-            }
-            RTC_HOURS_SELECT_VALUE => {
-                register.hours = value;
-            }
-            RTC_DAY_LOW_SELECT_VALUE => {
-                register.day_low = value;
+        if self.mode == RtcMode::WallClock {
+            let saved_time = UNIX_EPOCH + Duration::from_secs(timestamp);
+            if let Ok(elapsed) = now.duration_since(saved_time) {
+                self.register.add_time(elapsed);
             }
-            RTC_DAY_HIGH_SELECT_VALUE => {
-                register.day_high_flags.value = value;
-            }
-            _ => {}
         }
     }
+}
 
-    pub fn update(&mut self, duration: Duration) {
-        self.register.add_time(duration);
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set_time(rtc: &mut RealTimeCounter, days: u16, hours: u8, minutes: u8, seconds: u8) {
+        rtc.write(RTC_SECONDS_SELECT_VALUE, seconds);
+        rtc.write(RTC_MINUTES_SELECT_VALUE, minutes);
+        rtc.write(RTC_HOURS_SELECT_VALUE, hours);
+        rtc.write(RTC_DAY_LOW_SELECT_VALUE, (days & 0xFF) as u8);
+        rtc.write(RTC_DAY_HIGH_SELECT_VALUE, (days >> 8) as u8);
+    }
+
+    fn latch(rtc: &mut RealTimeCounter) {
+        rtc.latch(0);
+        rtc.latch(1);
+    }
+
+    #[test]
+    fn seconds_roll_over_into_minutes() {
+        let mut rtc = RealTimeCounter::new(RtcMode::Emulated);
+        set_time(&mut rtc, 0, 0, 0, 59);
+
+        rtc.update(Duration::from_secs(1));
+        latch(&mut rtc);
+
+        assert_eq!(rtc.read(RTC_SECONDS_SELECT_VALUE), 0);
+        assert_eq!(rtc.read(RTC_MINUTES_SELECT_VALUE), 1);
+        assert_eq!(rtc.read(RTC_HOURS_SELECT_VALUE), 0);
+    }
+
+    #[test]
+    fn minutes_roll_over_into_hours() {
+        let mut rtc = RealTimeCounter::new(RtcMode::Emulated);
+        set_time(&mut rtc, 0, 0, 59, 59);
+
+        rtc.update(Duration::from_secs(1));
+        latch(&mut rtc);
+
+        assert_eq!(rtc.read(RTC_SECONDS_SELECT_VALUE), 0);
+        assert_eq!(rtc.read(RTC_MINUTES_SELECT_VALUE), 0);
+        assert_eq!(rtc.read(RTC_HOURS_SELECT_VALUE), 1);
+        assert_eq!(rtc.read(RTC_DAY_LOW_SELECT_VALUE), 0);
+    }
+
+    #[test]
+    fn hours_roll_over_into_days() {
+        let mut rtc = RealTimeCounter::new(RtcMode::Emulated);
+        set_time(&mut rtc, 0, 23, 59, 59);
+
+        rtc.update(Duration::from_secs(1));
+        latch(&mut rtc);
+
+        assert_eq!(rtc.read(RTC_HOURS_SELECT_VALUE), 0);
+        assert_eq!(rtc.read(RTC_DAY_LOW_SELECT_VALUE), 1);
+        assert_eq!(rtc.read(RTC_DAY_HIGH_SELECT_VALUE), 0);
+    }
+
+    #[test]
+    fn day_low_rolls_over_into_day_high() {
+        let mut rtc = RealTimeCounter::new(RtcMode::Emulated);
+        set_time(&mut rtc, 255, 23, 59, 59);
+
+        rtc.update(Duration::from_secs(1));
+        latch(&mut rtc);
+
+        assert_eq!(rtc.read(RTC_DAY_LOW_SELECT_VALUE), 0);
+        assert_eq!(rtc.read(RTC_DAY_HIGH_SELECT_VALUE), 0b1);
+    }
+
+    #[test]
+    fn day_counter_overflow_sets_carry() {
+        let mut rtc = RealTimeCounter::new(RtcMode::Emulated);
+        set_time(&mut rtc, 511, 23, 59, 59);
+
+        rtc.update(Duration::from_secs(1));
+        latch(&mut rtc);
+
+        assert_eq!(rtc.read(RTC_DAY_LOW_SELECT_VALUE), 0);
+        assert_eq!(rtc.read(RTC_DAY_HIGH_SELECT_VALUE), 0b1000_0000);
+
+        // The carry is sticky.
+        rtc.update(Duration::from_secs(24 * 60 * 60));
+        latch(&mut rtc);
+        assert_eq!(rtc.read(RTC_DAY_LOW_SELECT_VALUE), 1);
+        assert_eq!(rtc.read(RTC_DAY_HIGH_SELECT_VALUE), 0b1000_0000);
+    }
+
+    #[test]
+    fn halt_stops_the_clock() {
+        let mut rtc = RealTimeCounter::new(RtcMode::Emulated);
+        rtc.write(RTC_DAY_HIGH_SELECT_VALUE, 0b0100_0000);
+
+        rtc.update(Duration::from_secs(10));
+        latch(&mut rtc);
+        assert_eq!(rtc.read(RTC_SECONDS_SELECT_VALUE), 0);
+
+        rtc.write(RTC_DAY_HIGH_SELECT_VALUE, 0);
+        rtc.update(Duration::from_secs(10));
+        latch(&mut rtc);
+        assert_eq!(rtc.read(RTC_SECONDS_SELECT_VALUE), 10);
+    }
+
+    #[test]
+    fn latched_registers_do_not_change_until_next_latch() {
+        let mut rtc = RealTimeCounter::new(RtcMode::Emulated);
+        latch(&mut rtc);
+
+        rtc.update(Duration::from_secs(5));
+        assert_eq!(rtc.read(RTC_SECONDS_SELECT_VALUE), 0);
+
+        rtc.latch(1);
+        assert_eq!(rtc.read(RTC_SECONDS_SELECT_VALUE), 0);
+
+        latch(&mut rtc);
+        assert_eq!(rtc.read(RTC_SECONDS_SELECT_VALUE), 5);
+    }
+
+    #[test]
+    fn wall_clock_mode_catches_up_on_load() {
+        let now = SystemTime::now();
+        let mut rtc = RealTimeCounter::new(RtcMode::WallClock);
+        set_time(&mut rtc, 3, 10, 0, 0);
+        let data = rtc.save(now - Duration::from_secs(2 * 60 * 60 + 30));
+
+        let mut loaded = RealTimeCounter::new(RtcMode::WallClock);
+        loaded.load(&data, now);
+        latch(&mut loaded);
+
+        assert_eq!(loaded.read(RTC_SECONDS_SELECT_VALUE), 30);
+        assert_eq!(loaded.read(RTC_HOURS_SELECT_VALUE), 12);
+        assert_eq!(loaded.read(RTC_DAY_LOW_SELECT_VALUE), 3);
+    }
+
+    #[test]
+    fn emulated_mode_does_not_catch_up_on_load() {
+        let now = SystemTime::now();
+        let mut rtc = RealTimeCounter::new(RtcMode::Emulated);
+        set_time(&mut rtc, 3, 10, 0, 0);
+        let data = rtc.save(now - Duration::from_secs(2 * 60 * 60));
+
+        let mut loaded = RealTimeCounter::new(RtcMode::Emulated);
+        loaded.load(&data, now);
+        latch(&mut loaded);
+
+        assert_eq!(loaded.read(RTC_HOURS_SELECT_VALUE), 10);
+        assert_eq!(data.len(), RTC_SAVE_SIZE);
+    }
+
+    #[test]
+    fn halted_clock_does_not_catch_up_on_load() {
+        let now = SystemTime::now();
+        let mut rtc = RealTimeCounter::new(RtcMode::WallClock);
+        rtc.write(RTC_DAY_HIGH_SELECT_VALUE, 0b0100_0000);
+        let data = rtc.save(now - Duration::from_secs(60));
+
+        let mut loaded = RealTimeCounter::new(RtcMode::WallClock);
+        loaded.load(&data, now);
+        latch(&mut loaded);
+
+        assert_eq!(loaded.read(RTC_MINUTES_SELECT_VALUE), 0);
+        assert_eq!(loaded.read(RTC_DAY_HIGH_SELECT_VALUE), 0b0100_0000);
+    }
+
+    #[test]
+    fn out_of_range_bank_and_select_values_do_not_crash() {
+        let mut controller = MBC3BankController {
+            rom: (0..4 * ROM_BANK_SIZE).map(|i| (i / ROM_BANK_SIZE) as u8).collect(),
+            ram: vec![0; RAM_BANK_SIZE],
+            rtc: RealTimeCounter::new(RtcMode::Emulated),
+            has_rtc: false,
+            rom_index: 0,
+            max_rom_index: 3,
+            ram_rtc_enabled: false,
+            ram_rtc_index: 0,
+            max_ram_index: 0,
+        };
+
+        // Only the bits needed for the 4 banks are used.
+        controller.write_rom(0x2000, 0x7E);
+        assert_eq!(controller.read_rom(0x4000), 2);
+
+        controller.write_rom(0x0000, 0x0A);
+        controller.write_rom(0x4000, 0x05);
+        controller.write_ext_ram(0xA000, 0x42);
+        assert_eq!(controller.read_ext_ram(0xA000), OPEN_BUS_VALUE);
     }
 }
@@ -1,41 +1,77 @@
-use crate::memory::mbc::common::{get_rom_ram_banks, RomRamBanks};
+use crate::memory::mbc::common::{get_rom_ram_banks, load_ram, RomRamBanks};
 use crate::memory::mbc::interface::{MemoryBankController, EXT_RAM_START_ADDRESS, RAM_BANK_SIZE};
-use std::fs::File;
-use std::io::BufReader;
+use crate::memory::OPEN_BUS_VALUE;
 use std::ops::Shl;
 use std::time::Duration;
 
+/// Information from: https://gbdev.io/pandocs/MBC5.html#4000-5fff---ram-bank-number-write-only
+const RAM_BANK_NUMBER_MASK: u8 = 0x0F;
+/// On rumble cartridges, the bit 3 of the ram bank number controls the motor.
+/// Information from: https://gbdev.io/pandocs/MBC5.html#rumble
+const RUMBLE_MOTOR_BIT: u8 = 0b1000;
+
 pub struct MBC5BankController {
     rom: Vec<u8>,
     ram: Vec<u8>,
 
+    mask_rom_bank_number: usize,
     rom_bank_number: u8,
     rom_upper_bits: u8,
 
     ram_bank_number: u8,
     is_ram_enabled: bool,
+
+    has_rumble: bool,
+    is_rumble_on: bool,
+    rumble_event: Option<bool>,
 }
 
 impl MBC5BankController {
     pub fn create(
-        rom_reader: &mut BufReader<File>,
+        rom_data: &[u8],
         num_rom_banks: usize,
         num_ram_banks: usize,
+        has_rumble: bool,
     ) -> Result<Box<dyn MemoryBankController>, String> {
-        let RomRamBanks { rom, ram } = get_rom_ram_banks(rom_reader, num_rom_banks, num_ram_banks)?;
+        let RomRamBanks { rom, ram } = get_rom_ram_banks(rom_data, num_rom_banks, num_ram_banks);
+
+        Ok(Box::new(Self::new(rom, num_rom_banks, ram, has_rumble)))
+    }
 
-        Ok(Box::new(Self {
+    fn new(rom: Vec<u8>, num_rom_banks: usize, ram: Vec<u8>, has_rumble: bool) -> Self {
+        Self {
             rom,
             ram,
+            // The num_rom_banks is always a power of 2.
+            // This make the number of rom banks - 1, the mask for the rom bank number.
+            mask_rom_bank_number: num_rom_banks.saturating_sub(1),
             rom_bank_number: 1,
             rom_upper_bits: 0,
             ram_bank_number: 0,
             is_ram_enabled: false,
-        }))
+            has_rumble,
+            is_rumble_on: false,
+            rumble_event: None,
+        }
     }
 
     fn get_ext_ram_relative_address(&self, address: u16) -> usize {
-        (address - EXT_RAM_START_ADDRESS) as usize + (self.ram_bank_number as usize * RAM_BANK_SIZE)
+        let relative_address = (address - EXT_RAM_START_ADDRESS) as usize
+            + (self.ram_bank_number as usize * RAM_BANK_SIZE);
+        relative_address % self.ram.len()
+    }
+
+    fn write_ram_bank_number(&mut self, value: u8) {
+        if !self.has_rumble {
+            self.ram_bank_number = value & RAM_BANK_NUMBER_MASK;
+            return;
+        }
+        self.ram_bank_number = value & RAM_BANK_NUMBER_MASK & !RUMBLE_MOTOR_BIT;
+        let is_rumble_on = value & RUMBLE_MOTOR_BIT != 0;
+        if is_rumble_on != self.is_rumble_on {
+            self.is_rumble_on = is_rumble_on;
+            self.rumble_event = Some(is_rumble_on);
+        }
     }
 }
 
@@ -54,9 +90,9 @@ impl MemoryBankController for MBC5BankController {
             0x3000..=0x3FFF => {
                 self.rom_upper_bits = value & 0b1;
             }
-            // https://gbdev.io/pandocs/MBC1.html#40005fff--ram-bank-number--or--upper-bits-of-rom-bank-number-write-only
+            // https://gbdev.io/pandocs/MBC5.html#4000-5fff---ram-bank-number-write-only
             0x4000..=0x5FFF => {
-                self.ram_bank_number = value;
+                self.write_ram_bank_number(value);
             }
             0x6000..=0x7FFF => { /* Nothing to do */ }
             _ => panic!("Invalid memory address 0x{:X}", address),
@@ -71,8 +107,10 @@ impl MemoryBankController for MBC5BankController {
             }
             0x4000..=0x7FFF => {
                 // https://gbdev.io/pandocs/MBC1.html#40007fff
-                let rom_bank_number: usize =
-                    self.rom_bank_number as usize + (self.rom_upper_bits as usize).shl(8);
+                // The upper bits of the bank number are ignored on smaller roms.
+                let rom_bank_number: usize = (self.rom_bank_number as usize
+                    + (self.rom_upper_bits as usize).shl(8))
+                    & self.mask_rom_bank_number;
                 let address = (address as usize & 0x3FFF) + rom_bank_number.shl(14);
                 self.rom[address]
             }
@@ -91,8 +129,8 @@ impl MemoryBankController for MBC5BankController {
     }
 
     fn read_ext_ram(&self, address: u16) -> u8 {
-        if !self.is_ram_enabled {
-            return 0xFF;
+        if !self.is_ram_enabled || self.ram.is_empty() {
+            return OPEN_BUS_VALUE;
         }
         self.ram[self.get_ext_ram_relative_address(address)]
     }
@@ -100,4 +138,68 @@ impl MemoryBankController for MBC5BankController {
     fn update(&mut self, _duration: Duration) {
         // Nothing to do.
     }
+
+    fn save_battery(&self) -> Vec<u8> {
+        self.ram.clone()
+    }
+
+    fn load_battery(&mut self, data: &[u8]) {
+        load_ram(&mut self.ram, data);
+    }
+
+    fn take_rumble_event(&mut self) -> Option<bool> {
+        self.rumble_event.take()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::mbc::interface::ROM_BANK_SIZE;
+
+    #[test]
+    fn rumble_bit_is_not_a_ram_bank_bit() {
+        let mut controller = MBC5BankController::new(vec![], 0, vec![0; 4 * RAM_BANK_SIZE], true);
+        controller.write_rom(0x0000, 0x0A);
+        controller.write_rom(0x4000, 0x01);
+        controller.write_ext_ram(0xA000, 0x42);
+
+        controller.write_rom(0x4000, 0x09);
+        assert_eq!(controller.read_ext_ram(0xA000), 0x42);
+    }
+
+    #[test]
+    fn rumble_events_are_only_sent_on_change() {
+        let mut controller = MBC5BankController::new(vec![], 0, vec![], true);
+        assert_eq!(controller.take_rumble_event(), None);
+
+        controller.write_rom(0x4000, 0x08);
+        controller.write_rom(0x4000, 0x0A);
+        assert_eq!(controller.take_rumble_event(), Some(true));
+        assert_eq!(controller.take_rumble_event(), None);
+
+        controller.write_rom(0x4000, 0x00);
+        assert_eq!(controller.take_rumble_event(), Some(false));
+    }
+
+    #[test]
+    fn no_rumble_events_without_motor() {
+        let mut controller = MBC5BankController::new(vec![], 0, vec![0; 16 * RAM_BANK_SIZE], false);
+        controller.write_rom(0x4000, 0x08);
+
+        assert_eq!(controller.take_rumble_event(), None);
+        assert_eq!(controller.ram_bank_number, 0x08);
+    }
+
+    #[test]
+    fn rom_bank_number_is_masked_by_the_rom_size() {
+        let num_rom_banks = 32;
+        let mut rom = vec![0u8; num_rom_banks * ROM_BANK_SIZE];
+        rom[31 * ROM_BANK_SIZE] = 0x42;
+        let mut controller = MBC5BankController::new(rom, num_rom_banks, vec![], false);
+
+        controller.write_rom(0x2000, 0xFF);
+        controller.write_rom(0x3000, 0x01);
+        assert_eq!(controller.read_rom(0x4000), 0x42);
+    }
 }
@@ -0,0 +1,433 @@
+use crate::joypad::Tilt;
+use crate::memory::mbc::common::{get_rom_ram_banks, RomRamBanks};
+use crate::memory::mbc::interface::{MemoryBankController, ROM_BANK_SIZE};
+use crate::memory::OPEN_BUS_VALUE;
+use std::time::Duration;
+
+/// Information from: https://gbdev.io/pandocs/MBC7.html#0000-1fff--ram-enable-1-write-only
+const RAM_ENABLE_1_VALUE: u8 = 0x0A;
+/// Information from: https://gbdev.io/pandocs/MBC7.html#4000-5fff--ram-enable-2-write-only
+const RAM_ENABLE_2_VALUE: u8 = 0x40;
+/// Only A000-AFFF is mapped to the registers, B000-BFFF always reads 0xFF.
+const REGISTERS_END_ADDRESS: u16 = 0xAFFF;
+
+/// Information from: https://gbdev.io/pandocs/MBC7.html#ax0x-ax1x--accelerometer-latch-write-only
+const ERASE_LATCH_VALUE: u8 = 0x55;
+const LATCH_VALUE: u8 = 0xAA;
+const ERASED_ACCELEROMETER_VALUE: u16 = 0x8000;
+/// Information from: https://gbdev.io/pandocs/MBC7.html#ax2x-ax3x--accelerometer-x-read-only
+const ACCELEROMETER_CENTER_VALUE: f32 = 0x81D0 as f32;
+const ACCELEROMETER_GRAVITY_VALUE: f32 = 0x70 as f32;
+
+/// Information from: https://gbdev.io/pandocs/MBC7.html#ax8x--eeprom-read-write
+const EEPROM_CHIP_SELECT_BIT: u8 = 0b1000_0000;
+const EEPROM_CLOCK_BIT: u8 = 0b0100_0000;
+const EEPROM_DATA_IN_BIT: u8 = 0b0000_0010;
+const EEPROM_DATA_OUT_BIT: u8 = 0b0000_0001;
+/// The 93LC56 is organized as 128 words of 16 bits.
+const EEPROM_NUM_WORDS: usize = 128;
+const EEPROM_WORD_BITS: u8 = 16;
+/// Commands are a start bit, a 2 bits opcode and a 8 bits address.
+const EEPROM_COMMAND_BITS: u8 = 11;
+
+pub struct MBC7BankController {
+    rom: Vec<u8>,
+
+    mask_rom_bank_number: usize,
+    rom_bank_number: usize,
+    is_ram_enabled_1: bool,
+    is_ram_enabled_2: bool,
+
+    tilt: Tilt,
+    is_latch_erased: bool,
+    latched_x: u16,
+    latched_y: u16,
+
+    eeprom: Eeprom,
+}
+
+impl MBC7BankController {
+    pub fn create(
+        rom_data: &[u8],
+        num_rom_banks: usize,
+    ) -> Result<Box<dyn MemoryBankController>, String> {
+        // The cartridge header declares no RAM, the save data is stored in the EEPROM.
+        let RomRamBanks { rom, .. } = get_rom_ram_banks(rom_data, num_rom_banks, 0);
+
+        Ok(Box::new(Self::new(rom, num_rom_banks)))
+    }
+
+    fn new(rom: Vec<u8>, num_rom_banks: usize) -> Self {
+        Self {
+            rom,
+            // The num_rom_banks is always a power of 2.
+            // This make the number of rom banks - 1, the mask for the rom bank number.
+            mask_rom_bank_number: num_rom_banks.saturating_sub(1),
+            rom_bank_number: 1,
+            is_ram_enabled_1: false,
+            is_ram_enabled_2: false,
+            tilt: Tilt::default(),
+            is_latch_erased: false,
+            latched_x: ERASED_ACCELEROMETER_VALUE,
+            latched_y: ERASED_ACCELEROMETER_VALUE,
+            eeprom: Eeprom::new(),
+        }
+    }
+
+    fn is_ram_enabled(&self) -> bool {
+        self.is_ram_enabled_1 && self.is_ram_enabled_2
+    }
+
+    fn write_register(&mut self, register: u16, value: u8) {
+        match register {
+            // https://gbdev.io/pandocs/MBC7.html#ax0x-ax1x--accelerometer-latch-write-only
+            0x0 if value == ERASE_LATCH_VALUE => {
+                self.is_latch_erased = true;
+                self.latched_x = ERASED_ACCELEROMETER_VALUE;
+                self.latched_y = ERASED_ACCELEROMETER_VALUE;
+            }
+            0x1 if value == LATCH_VALUE && self.is_latch_erased => {
+                self.is_latch_erased = false;
+                self.latched_x = get_accelerometer_value(self.tilt.x);
+                self.latched_y = get_accelerometer_value(self.tilt.y);
+            }
+            // https://gbdev.io/pandocs/MBC7.html#ax8x--eeprom-read-write
+            0x8 => self.eeprom.write(value),
+            _ => { /* Nothing to do */ }
+        }
+    }
+
+    fn read_register(&self, register: u16) -> u8 {
+        match register {
+            // https://gbdev.io/pandocs/MBC7.html#ax2x-ax3x--accelerometer-x-read-only
+            0x2 => self.latched_x as u8,
+            0x3 => (self.latched_x >> 8) as u8,
+            // https://gbdev.io/pandocs/MBC7.html#ax4x-ax5x--accelerometer-y-read-only
+            0x4 => self.latched_y as u8,
+            0x5 => (self.latched_y >> 8) as u8,
+            // https://gbdev.io/pandocs/MBC7.html#ax6x-ax7x--unknown
+            0x6 => 0x00,
+            0x8 => self.eeprom.read(),
+            _ => OPEN_BUS_VALUE,
+        }
+    }
+}
+
+/// Converts a tilt, in fractions of the gravity, to the value returned by the accelerometer.
+fn get_accelerometer_value(tilt: f32) -> u16 {
+    (ACCELEROMETER_CENTER_VALUE + tilt.clamp(-1.0, 1.0) * ACCELEROMETER_GRAVITY_VALUE) as u16
+}
+
+impl MemoryBankController for MBC7BankController {
+    fn write_rom(&mut self, address: u16, value: u8) {
+        match address {
+            // https://gbdev.io/pandocs/MBC7.html#0000-1fff--ram-enable-1-write-only
+            0x0000..=0x1FFF => {
+                self.is_ram_enabled_1 = value == RAM_ENABLE_1_VALUE;
+            }
+            // https://gbdev.io/pandocs/MBC7.html#2000-3fff--rom-bank-number-write-only
+            0x2000..=0x3FFF => {
+                self.rom_bank_number = value as usize & self.mask_rom_bank_number;
+            }
+            // https://gbdev.io/pandocs/MBC7.html#4000-5fff--ram-enable-2-write-only
+            0x4000..=0x5FFF => {
+                self.is_ram_enabled_2 = value == RAM_ENABLE_2_VALUE;
+            }
+            _ => { /* Nothing to do */ }
+        }
+    }
+
+    fn read_rom(&self, address: u16) -> u8 {
+        match address {
+            0x0000..=0x3FFF => self.rom[address as usize],
+            0x4000..=0x7FFF => {
+                let bank_address = self.rom_bank_number * ROM_BANK_SIZE;
+                self.rom[bank_address + (address as usize & 0x3FFF)]
+            }
+            _ => OPEN_BUS_VALUE,
+        }
+    }
+
+    fn write_ext_ram(&mut self, address: u16, value: u8) {
+        if !self.is_ram_enabled() || address > REGISTERS_END_ADDRESS {
+            return;
+        }
+        self.write_register((address >> 4) & 0xF, value);
+    }
+
+    fn read_ext_ram(&self, address: u16) -> u8 {
+        if !self.is_ram_enabled() || address > REGISTERS_END_ADDRESS {
+            return OPEN_BUS_VALUE;
+        }
+        self.read_register((address >> 4) & 0xF)
+    }
+
+    fn update(&mut self, _duration: Duration) {
+        // Nothing to do.
+    }
+
+    fn save_battery(&self) -> Vec<u8> {
+        self.eeprom.save()
+    }
+
+    fn load_battery(&mut self, data: &[u8]) {
+        self.eeprom.load(data);
+    }
+
+    fn set_tilt(&mut self, tilt: Tilt) {
+        self.tilt = tilt;
+    }
+}
+
+/// 93LC56 serial EEPROM, accessed bit by bit through the Ax8x register.
+///
+/// Information from: https://gbdev.io/pandocs/MBC7.html#eeprom
+struct Eeprom {
+    words: [u16; EEPROM_NUM_WORDS],
+    is_write_enabled: bool,
+
+    chip_select: bool,
+    clock: bool,
+    data_in: bool,
+    data_out: bool,
+
+    command: u32,
+    command_bits: u8,
+    output: u16,
+    output_bits: u8,
+}
+
+impl Eeprom {
+    fn new() -> Self {
+        Self {
+            words: [0xFFFF; EEPROM_NUM_WORDS],
+            is_write_enabled: false,
+            chip_select: false,
+            clock: false,
+            data_in: false,
+            data_out: true,
+            command: 0,
+            command_bits: 0,
+            output: 0,
+            output_bits: 0,
+        }
+    }
+
+    fn read(&self) -> u8 {
+        let mut value = 0;
+        if self.chip_select {
+            value |= EEPROM_CHIP_SELECT_BIT;
+        }
+        if self.clock {
+            value |= EEPROM_CLOCK_BIT;
+        }
+        if self.data_in {
+            value |= EEPROM_DATA_IN_BIT;
+        }
+        if self.data_out {
+            value |= EEPROM_DATA_OUT_BIT;
+        }
+        value
+    }
+
+    fn write(&mut self, value: u8) {
+        let chip_select = value & EEPROM_CHIP_SELECT_BIT != 0;
+        let clock = value & EEPROM_CLOCK_BIT != 0;
+        self.data_in = value & EEPROM_DATA_IN_BIT != 0;
+
+        if !chip_select {
+            // Deselecting the chip aborts the current command.
+            self.reset_command();
+            self.output_bits = 0;
+        } else if clock && !self.clock {
+            self.clock_rising_edge();
+        }
+        self.chip_select = chip_select;
+        self.clock = clock;
+    }
+
+    /// Data is shifted in and out, most significant bit first, on the rising edge of the clock.
+    fn clock_rising_edge(&mut self) {
+        if self.output_bits > 0 {
+            self.output_bits -= 1;
+            self.data_out = (self.output >> self.output_bits) & 1 != 0;
+            return;
+        }
+        // Leading zeros before the start bit are ignored.
+        if self.command_bits == 0 && !self.data_in {
+            return;
+        }
+        self.command = (self.command << 1) | self.data_in as u32;
+        self.command_bits += 1;
+        self.execute_command();
+    }
+
+    fn execute_command(&mut self) {
+        if self.command_bits < EEPROM_COMMAND_BITS {
+            return;
+        }
+        // The data of the write commands is shifted in after the command.
+        let header = self.command >> (self.command_bits - EEPROM_COMMAND_BITS);
+        let opcode = (header >> 8) & 0b11;
+        let address = (header & 0xFF) as usize % EEPROM_NUM_WORDS;
+        let data = self.command as u16;
+        match (opcode, (header >> 6) & 0b11) {
+            // READ: a dummy 0 bit is output before the 16 bits of data.
+            (0b10, _) => {
+                self.data_out = false;
+                self.output = self.words[address];
+                self.output_bits = EEPROM_WORD_BITS;
+                self.reset_command();
+            }
+            // EWEN / EWDS: enable or disable the erase and write commands.
+            (0b00, 0b11) => self.complete_command(|eeprom| eeprom.is_write_enabled = true),
+            (0b00, 0b00) => self.complete_command(|eeprom| eeprom.is_write_enabled = false),
+            // ERASE / ERAL: set the words to 0xFFFF.
+            (0b11, _) => self.complete_write(|words| words[address] = 0xFFFF),
+            (0b00, 0b10) => self.complete_write(|words| words.fill(0xFFFF)),
+            // WRITE / WRAL: wait for the 16 bits of data.
+            (0b01, _) | (0b00, 0b01)
+                if self.command_bits < EEPROM_COMMAND_BITS + EEPROM_WORD_BITS => {}
+            (0b01, _) => self.complete_write(|words| words[address] = data),
+            (0b00, 0b01) => self.complete_write(|words| words.fill(data)),
+            _ => self.reset_command(),
+        }
+    }
+
+    fn complete_write(&mut self, write: impl FnOnce(&mut [u16; EEPROM_NUM_WORDS])) {
+        if self.is_write_enabled {
+            write(&mut self.words);
+        }
+        self.complete_command(|_| {});
+    }
+
+    /// The chip reports that it is ready once the command is completed.
+    fn complete_command(&mut self, command: impl FnOnce(&mut Self)) {
+        command(self);
+        self.data_out = true;
+        self.reset_command();
+    }
+
+    fn reset_command(&mut self) {
+        self.command = 0;
+        self.command_bits = 0;
+    }
+
+    fn save(&self) -> Vec<u8> {
+        self.words
+            .iter()
+            .flat_map(|word| word.to_le_bytes())
+            .collect()
+    }
+
+    fn load(&mut self, data: &[u8]) {
+        for (word, bytes) in self.words.iter_mut().zip(data.chunks_exact(2)) {
+            *word = u16::from_le_bytes([bytes[0], bytes[1]]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_controller() -> MBC7BankController {
+        let mut controller = MBC7BankController::new(vec![0; 4 * ROM_BANK_SIZE], 4);
+        controller.write_rom(0x0000, RAM_ENABLE_1_VALUE);
+        controller.write_rom(0x4000, RAM_ENABLE_2_VALUE);
+        controller
+    }
+
+    fn send_bits(controller: &mut MBC7BankController, value: u32, nb_bits: u8) {
+        for bit in (0..nb_bits).rev() {
+            let data_in = if (value >> bit) & 1 != 0 {
+                EEPROM_DATA_IN_BIT
+            } else {
+                0
+            };
+            controller.write_ext_ram(0xA080, EEPROM_CHIP_SELECT_BIT | data_in);
+            controller.write_ext_ram(0xA080, EEPROM_CHIP_SELECT_BIT | EEPROM_CLOCK_BIT | data_in);
+        }
+    }
+
+    fn end_command(controller: &mut MBC7BankController) {
+        controller.write_ext_ram(0xA080, 0x00);
+    }
+
+    fn read_word(controller: &mut MBC7BankController, address: u32) -> u16 {
+        send_bits(controller, 0b110 << 8 | address, EEPROM_COMMAND_BITS);
+        assert_eq!(controller.read_ext_ram(0xA080) & EEPROM_DATA_OUT_BIT, 0);
+        let mut word = 0;
+        for _ in 0..EEPROM_WORD_BITS {
+            send_bits(controller, 0, 1);
+            word = (word << 1) | (controller.read_ext_ram(0xA080) & EEPROM_DATA_OUT_BIT) as u16;
+        }
+        end_command(controller);
+        word
+    }
+
+    #[test]
+    fn registers_need_both_ram_enables() {
+        let mut controller = MBC7BankController::new(vec![0; 4 * ROM_BANK_SIZE], 4);
+        controller.write_rom(0x0000, RAM_ENABLE_1_VALUE);
+        assert_eq!(controller.read_ext_ram(0xA060), OPEN_BUS_VALUE);
+
+        controller.write_rom(0x4000, RAM_ENABLE_2_VALUE);
+        assert_eq!(controller.read_ext_ram(0xA060), 0x00);
+        assert_eq!(controller.read_ext_ram(0xB060), OPEN_BUS_VALUE);
+    }
+
+    #[test]
+    fn accelerometer_is_latched_after_erase() {
+        let mut controller = create_controller();
+        controller.set_tilt(Tilt { x: 1.0, y: -0.5 });
+
+        // Latching without erasing first is ignored.
+        controller.write_ext_ram(0xA010, LATCH_VALUE);
+        assert_eq!(controller.read_ext_ram(0xA030), 0x80);
+
+        controller.write_ext_ram(0xA000, ERASE_LATCH_VALUE);
+        controller.write_ext_ram(0xA010, LATCH_VALUE);
+        assert_eq!(controller.read_ext_ram(0xA020), 0x40);
+        assert_eq!(controller.read_ext_ram(0xA030), 0x82);
+        assert_eq!(controller.read_ext_ram(0xA040), 0x98);
+        assert_eq!(controller.read_ext_ram(0xA050), 0x81);
+
+        controller.set_tilt(Tilt::default());
+        assert_eq!(controller.read_ext_ram(0xA020), 0x40);
+    }
+
+    #[test]
+    fn eeprom_is_write_protected_until_enabled() {
+        let mut controller = create_controller();
+        send_bits(&mut controller, 0b101 << 8 | 0x03, EEPROM_COMMAND_BITS);
+        send_bits(&mut controller, 0x1234, EEPROM_WORD_BITS);
+        end_command(&mut controller);
+        assert_eq!(read_word(&mut controller, 0x03), 0xFFFF);
+
+        send_bits(&mut controller, 0b100_1100_0000, EEPROM_COMMAND_BITS);
+        end_command(&mut controller);
+        send_bits(&mut controller, 0b101 << 8 | 0x03, EEPROM_COMMAND_BITS);
+        send_bits(&mut controller, 0x1234, EEPROM_WORD_BITS);
+        assert_eq!(controller.read_ext_ram(0xA080) & EEPROM_DATA_OUT_BIT, 1);
+        end_command(&mut controller);
+        assert_eq!(read_word(&mut controller, 0x03), 0x1234);
+    }
+
+    #[test]
+    fn eeprom_erase_and_battery_save() {
+        let mut controller = create_controller();
+        send_bits(&mut controller, 0b100_1100_0000, EEPROM_COMMAND_BITS);
+        end_command(&mut controller);
+        send_bits(&mut controller, 0b100_0100_0000, EEPROM_COMMAND_BITS);
+        send_bits(&mut controller, 0xBEEF, EEPROM_WORD_BITS);
+        end_command(&mut controller);
+        send_bits(&mut controller, 0b111 << 8 | 0x7F, EEPROM_COMMAND_BITS);
+        end_command(&mut controller);
+
+        let mut loaded = create_controller();
+        loaded.load_battery(&controller.save_battery());
+        assert_eq!(read_word(&mut loaded, 0x00), 0xBEEF);
+        assert_eq!(read_word(&mut loaded, 0x7F), 0xFFFF);
+    }
+}
@@ -0,0 +1,269 @@
+//! Information from: https://gbdev.io/pandocs/MMM01.html
+//!
+//! The MMM01 starts unmapped: the menu stored in the last 32 KiB of the ROM is visible at
+//! 0000-7FFF, and all the registers can be written. Once the menu sets the lock bit, the outer
+//! bank bits and the masks are frozen, and the selected game sees a MBC1-like controller limited
+//! to its own part of the ROM and RAM.
+
+use crate::memory::mbc::common::{get_rom_ram_banks, load_ram, RomRamBanks};
+use crate::memory::mbc::interface::{
+    MemoryBankController, EXT_RAM_START_ADDRESS, RAM_BANK_SIZE, ROM_BANK_SIZE,
+};
+use crate::memory::OPEN_BUS_VALUE;
+use std::time::Duration;
+
+/// 0000-1FFF: RAM enable, RAM bank mask and lock bit.
+const RAM_ENABLE_MASK: u8 = 0x0F;
+const RAM_ENABLE_VALUE: u8 = 0x0A;
+const LOCK_BIT: u8 = 0b0100_0000;
+/// 2000-3FFF: ROM bank number, the 2 upper bits can only be written before the lock.
+const ROM_BANK_LOW_MASK: u8 = 0b1_1111;
+const ROM_BANK_MID_MASK: u8 = 0b11;
+/// 4000-5FFF: RAM bank number, the upper bits select the game ROM and RAM before the lock.
+const RAM_BANK_LOW_MASK: u8 = 0b11;
+const MBC1_MODE_WRITE_DISABLE_BIT: u8 = 0b0100_0000;
+/// 6000-7FFF: MBC1 banking mode, the ROM bank mask can only be written before the lock.
+const MBC1_MODE_BIT: u8 = 0b1;
+
+pub struct MMM01BankController {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+
+    num_rom_banks: usize,
+    is_locked: bool,
+    is_ram_enabled: bool,
+
+    rom_bank_low: u8,
+    rom_bank_mid: u8,
+    rom_bank_high: u8,
+    /// Bits 1-4 of the rom bank low number frozen after the lock.
+    rom_bank_mask: u8,
+
+    ram_bank_low: u8,
+    ram_bank_high: u8,
+    /// Bits of the ram bank low number frozen after the lock.
+    ram_bank_mask: u8,
+
+    is_mbc1_mode_enabled: bool,
+    is_mbc1_mode_write_disabled: bool,
+}
+
+impl MMM01BankController {
+    pub fn create(
+        rom_data: &[u8],
+        num_rom_banks: usize,
+        num_ram_banks: usize,
+    ) -> Result<Box<dyn MemoryBankController>, String> {
+        let RomRamBanks { rom, ram } = get_rom_ram_banks(rom_data, num_rom_banks, num_ram_banks);
+
+        Ok(Box::new(Self::new(rom, ram, num_rom_banks)))
+    }
+
+    fn new(rom: Vec<u8>, ram: Vec<u8>, num_rom_banks: usize) -> Self {
+        Self {
+            rom,
+            ram,
+            num_rom_banks,
+            is_locked: false,
+            is_ram_enabled: false,
+            rom_bank_low: 0,
+            rom_bank_mid: 0,
+            rom_bank_high: 0,
+            rom_bank_mask: 0,
+            ram_bank_low: 0,
+            ram_bank_high: 0,
+            ram_bank_mask: 0,
+            is_mbc1_mode_enabled: false,
+            is_mbc1_mode_write_disabled: false,
+        }
+    }
+
+    /// Bits of the rom bank number selecting the game, fixed by the menu.
+    fn get_game_rom_bank_bits(&self) -> usize {
+        ((self.rom_bank_high as usize) << 7) | ((self.rom_bank_mid as usize) << 5)
+    }
+
+    fn get_rom_bank_0_number(&self) -> usize {
+        if !self.is_locked {
+            // The menu is stored in the last 32 KiB of the ROM.
+            return self.num_rom_banks.saturating_sub(2);
+        }
+        let mut rom_bank_number = self.get_game_rom_bank_bits();
+        if self.is_mbc1_mode_enabled {
+            rom_bank_number |= (self.ram_bank_low as usize) << 5;
+        }
+        rom_bank_number % self.num_rom_banks
+    }
+
+    fn get_rom_bank_n_number(&self) -> usize {
+        if !self.is_locked {
+            return self.num_rom_banks.saturating_sub(1);
+        }
+        // Like on the MBC1, the 0 -> 1 translation is done on the 5 bits of the register.
+        let rom_bank_low = match self.rom_bank_low {
+            0 => 1,
+            value => value as usize,
+        };
+        (self.get_game_rom_bank_bits() | rom_bank_low) % self.num_rom_banks
+    }
+
+    fn get_ext_ram_relative_address(&self, address: u16) -> usize {
+        let ram_bank_number = ((self.ram_bank_high as usize) << 2) | self.ram_bank_low as usize;
+        let relative_address =
+            (address - EXT_RAM_START_ADDRESS) as usize + ram_bank_number * RAM_BANK_SIZE;
+        relative_address % self.ram.len()
+    }
+
+    /// Only the bits not frozen by the mask can be written once the mapping is locked.
+    fn write_masked(current: u8, value: u8, frozen_bits: u8, is_locked: bool) -> u8 {
+        if is_locked {
+            (current & frozen_bits) | (value & !frozen_bits)
+        } else {
+            value
+        }
+    }
+}
+
+impl MemoryBankController for MMM01BankController {
+    fn write_rom(&mut self, address: u16, value: u8) {
+        match address {
+            0x0000..=0x1FFF => {
+                self.is_ram_enabled = value & RAM_ENABLE_MASK == RAM_ENABLE_VALUE;
+                if !self.is_locked {
+                    self.ram_bank_mask = (value >> 4) & RAM_BANK_LOW_MASK;
+                    self.is_locked = value & LOCK_BIT != 0;
+                }
+            }
+            0x2000..=0x3FFF => {
+                self.rom_bank_low = Self::write_masked(
+                    self.rom_bank_low,
+                    value & ROM_BANK_LOW_MASK,
+                    self.rom_bank_mask << 1,
+                    self.is_locked,
+                );
+                if !self.is_locked {
+                    self.rom_bank_mid = (value >> 5) & ROM_BANK_MID_MASK;
+                }
+            }
+            0x4000..=0x5FFF => {
+                self.ram_bank_low = Self::write_masked(
+                    self.ram_bank_low,
+                    value & RAM_BANK_LOW_MASK,
+                    self.ram_bank_mask,
+                    self.is_locked,
+                );
+                if !self.is_locked {
+                    self.ram_bank_high = (value >> 2) & 0b11;
+                    self.rom_bank_high = (value >> 4) & 0b11;
+                    self.is_mbc1_mode_write_disabled = value & MBC1_MODE_WRITE_DISABLE_BIT != 0;
+                }
+            }
+            0x6000..=0x7FFF => {
+                if !self.is_mbc1_mode_write_disabled {
+                    self.is_mbc1_mode_enabled = value & MBC1_MODE_BIT != 0;
+                }
+                if !self.is_locked {
+                    self.rom_bank_mask = (value >> 2) & 0b1111;
+                }
+            }
+            _ => panic!("Invalid memory address 0x{:X}", address),
+        }
+    }
+
+    fn read_rom(&self, address: u16) -> u8 {
+        match address {
+            0x0000..=0x3FFF => {
+                self.rom[self.get_rom_bank_0_number() * ROM_BANK_SIZE + address as usize]
+            }
+            0x4000..=0x7FFF => {
+                let bank_address = self.get_rom_bank_n_number() * ROM_BANK_SIZE;
+                self.rom[bank_address + (address as usize & 0x3FFF)]
+            }
+            _ => OPEN_BUS_VALUE,
+        }
+    }
+
+    fn write_ext_ram(&mut self, address: u16, value: u8) {
+        if !self.is_ram_enabled || self.ram.is_empty() {
+            return;
+        }
+        let relative_address = self.get_ext_ram_relative_address(address);
+        self.ram[relative_address] = value;
+    }
+
+    fn read_ext_ram(&self, address: u16) -> u8 {
+        if !self.is_ram_enabled || self.ram.is_empty() {
+            return OPEN_BUS_VALUE;
+        }
+        self.ram[self.get_ext_ram_relative_address(address)]
+    }
+
+    fn update(&mut self, _duration: Duration) {
+        // Nothing to do.
+    }
+
+    fn save_battery(&self) -> Vec<u8> {
+        self.ram.clone()
+    }
+
+    fn load_battery(&mut self, data: &[u8]) {
+        load_ram(&mut self.ram, data);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_controller(num_rom_banks: usize) -> MMM01BankController {
+        let mut rom = vec![0u8; num_rom_banks * ROM_BANK_SIZE];
+        for bank in 0..num_rom_banks {
+            rom[bank * ROM_BANK_SIZE] = bank as u8;
+        }
+        MMM01BankController::new(rom, vec![0; 4 * RAM_BANK_SIZE], num_rom_banks)
+    }
+
+    #[test]
+    fn boots_from_the_last_banks() {
+        let mut controller = create_controller(64);
+        assert_eq!(controller.read_rom(0x0000), 62);
+        assert_eq!(controller.read_rom(0x4000), 63);
+
+        // The mapping registers do not change the menu banks until locked.
+        controller.write_rom(0x2000, 0x21);
+        assert_eq!(controller.read_rom(0x4000), 63);
+    }
+
+    #[test]
+    fn lock_maps_the_selected_game() {
+        let mut controller = create_controller(64);
+        // Game starting at bank 0x20, using 8 banks: bits 3-4 of the rom bank are frozen.
+        controller.write_rom(0x2000, 0b010_0000);
+        controller.write_rom(0x6000, 0b11_0000);
+        controller.write_rom(0x0000, LOCK_BIT);
+
+        assert_eq!(controller.read_rom(0x0000), 0x20);
+        assert_eq!(controller.read_rom(0x4000), 0x21);
+
+        controller.write_rom(0x2000, 0x1F);
+        assert_eq!(controller.read_rom(0x4000), 0x27);
+
+        // Writes to the outer bits are ignored once locked.
+        controller.write_rom(0x2000, 0x60);
+        controller.write_rom(0x6000, 0x00);
+        assert_eq!(controller.read_rom(0x0000), 0x20);
+        assert_eq!(controller.read_rom(0x4000), 0x21);
+    }
+
+    #[test]
+    fn ram_bank_mask_freezes_game_ram_bank() {
+        let mut controller = create_controller(8);
+        controller.write_rom(0x4000, 0b10);
+        controller.write_rom(0x0000, LOCK_BIT | 0b10_0000 | RAM_ENABLE_VALUE);
+        controller.write_ext_ram(0xA000, 0x42);
+
+        controller.write_rom(0x4000, 0b00);
+        assert_eq!(controller.read_ext_ram(0xA000), 0x42);
+        assert_eq!(controller.ram_bank_low, 0b10);
+    }
+}
@@ -1,6 +1,12 @@
 mod common;
+pub(crate) mod huc1;
+pub(crate) mod huc3;
 pub(crate) mod interface;
 pub(crate) mod mbc1;
+pub(crate) mod mbc2;
 pub(crate) mod mbc3;
 pub(crate) mod mbc5;
+pub(crate) mod mbc7;
+pub(crate) mod mmm01;
 pub(crate) mod no_controller;
+pub(crate) mod pocket_camera;
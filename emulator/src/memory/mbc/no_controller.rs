@@ -1,7 +1,6 @@
-use crate::memory::mbc::common::{get_rom_ram_banks, RomRamBanks};
+use crate::memory::mbc::common::{get_rom_ram_banks, load_ram, RomRamBanks};
 use crate::memory::mbc::interface::{MemoryBankController, EXT_RAM_START_ADDRESS};
-use std::fs::File;
-use std::io::BufReader;
+use crate::memory::OPEN_BUS_VALUE;
 use std::time::Duration;
 
 pub struct NoMemoryBankController {
@@ -11,10 +10,10 @@ pub struct NoMemoryBankController {
 
 impl NoMemoryBankController {
     pub fn create(
-        rom_reader: &mut BufReader<File>,
+        rom_data: &[u8],
         num_ram_banks: usize,
     ) -> Result<Box<dyn MemoryBankController>, String> {
-        let RomRamBanks { rom, ram } = get_rom_ram_banks(rom_reader, 2, num_ram_banks)?;
+        let RomRamBanks { rom, ram } = get_rom_ram_banks(rom_data, 2, num_ram_banks);
 
         Ok(Box::new(Self { rom, ram }))
     }
@@ -30,14 +29,28 @@ impl MemoryBankController for NoMemoryBankController {
     }
 
     fn write_ext_ram(&mut self, address: u16, value: u8) {
-        self.ram[(address - EXT_RAM_START_ADDRESS) as usize] = value;
+        let relative_address = (address - EXT_RAM_START_ADDRESS) as usize;
+        if let Some(cell) = self.ram.get_mut(relative_address) {
+            *cell = value;
+        }
     }
 
     fn read_ext_ram(&self, address: u16) -> u8 {
-        self.ram[(address - EXT_RAM_START_ADDRESS) as usize]
+        self.ram
+            .get((address - EXT_RAM_START_ADDRESS) as usize)
+            .copied()
+            .unwrap_or(OPEN_BUS_VALUE)
     }
 
     fn update(&mut self, _duration: Duration) {
         // Nothing to do.
     }
+
+    fn save_battery(&self) -> Vec<u8> {
+        self.ram.clone()
+    }
+
+    fn load_battery(&mut self, data: &[u8]) {
+        load_ram(&mut self.ram, data);
+    }
 }
@@ -0,0 +1,406 @@
+//! Information from: https://gbdev.io/pandocs/Gameboy_Camera.html
+//!
+//! The Pocket Camera maps 16 RAM banks and, when the RAM bank bit 4 is set, the registers of the
+//! M64282FP sensor at A000-A07F. A capture converts the image of the source into 2bpp tiles
+//! written at the start of the RAM bank 0, after the exposure, edge enhancement and dithering
+//! configured in the registers.
+
+use crate::camera::{CameraImageSource, SENSOR_HEIGHT, SENSOR_WIDTH};
+use crate::memory::mbc::common::{get_rom_ram_banks, load_ram, RomRamBanks};
+use crate::memory::mbc::interface::{
+    MemoryBankController, EXT_RAM_START_ADDRESS, RAM_BANK_SIZE, ROM_BANK_SIZE,
+};
+use crate::memory::OPEN_BUS_VALUE;
+use crate::timer::convert_cycles_to_duration;
+use std::time::Duration;
+
+/// The camera always has 128 KiB of RAM.
+const NUM_RAM_BANKS: usize = 16;
+const RAM_ENABLE_MASK: u8 = 0x0F;
+const RAM_ENABLE_VALUE: u8 = 0x0A;
+const ROM_BANK_NUMBER_MASK: u8 = 0b11_1111;
+const RAM_BANK_NUMBER_MASK: u8 = 0b1111;
+/// Information from: https://gbdev.io/pandocs/Gameboy_Camera.html#4000-5fff--ram-bank-number--camera-registers-select-write-only
+const REGISTERS_SELECT_BIT: u8 = 0b1_0000;
+const REGISTERS_ADDRESS_MASK: u16 = 0x7F;
+const NUM_REGISTERS: usize = 0x36;
+
+/// Information from: https://gbdev.io/pandocs/Gameboy_Camera.html#camera-registers
+const REGISTER_CONTROL: usize = 0x00;
+const REGISTER_GAIN_EDGE: usize = 0x01;
+const REGISTER_EXPOSURE_HIGH: usize = 0x02;
+const REGISTER_EXPOSURE_LOW: usize = 0x03;
+const REGISTER_EDGE_RATIO_INVERT: usize = 0x04;
+const REGISTER_DITHER_MATRIX: usize = 0x06;
+/// Only the capture bit and the 2 filter bits of A000 can be read back.
+const CONTROL_READABLE_MASK: u8 = 0b111;
+const CONTROL_CAPTURE_BIT: u8 = 0b1;
+const GAIN_MASK: u8 = 0b1_1111;
+const EDGE_ENHANCEMENT_MASK: u8 = 0b1110_0000;
+const N_BIT: u8 = 0b1000_0000;
+const INVERT_BIT: u8 = 0b1000;
+const EDGE_RATIO_SHIFT: u8 = 4;
+const EDGE_RATIO_MASK: u8 = 0b111;
+const EDGE_RATIOS: [f32; 8] = [0.5, 0.75, 1.0, 1.25, 2.0, 3.0, 4.0, 5.0];
+/// The exposure time is in steps of 16 µs, the source image is seen as is for this exposure.
+const REFERENCE_EXPOSURE: f32 = 0x1000 as f32;
+/// Each pixel of the 4x4 matrix has 3 thresholds, one per darker color.
+const DITHER_MATRIX_SIZE: usize = 4;
+const DITHER_NUM_THRESHOLDS: usize = 3;
+
+/// Information from: https://gbdev.io/pandocs/Gameboy_Camera.html#sensor-image-capture
+const CAPTURE_BASE_M_CYCLES: u64 = 32446;
+const CAPTURE_NO_N_M_CYCLES: u64 = 512;
+const CAPTURE_EXPOSURE_M_CYCLES: u64 = 16;
+const CYCLES_PER_M_CYCLE: u64 = 4;
+/// The captured image is written as 16x14 tiles at A100-AEFF of the RAM bank 0.
+const IMAGE_ADDRESS: usize = 0x100;
+const TILE_SIZE: usize = 8;
+const TILE_NUM_BYTES: usize = 16;
+const NUM_TILES_PER_ROW: usize = SENSOR_WIDTH / TILE_SIZE;
+
+pub struct PocketCameraBankController {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+
+    mask_rom_bank_number: usize,
+    rom_bank_number: u8,
+    ram_bank_number: u8,
+    is_ram_enabled: bool,
+    are_registers_selected: bool,
+
+    registers: [u8; NUM_REGISTERS],
+    capture_time_left: Option<Duration>,
+    source: Box<dyn CameraImageSource>,
+}
+
+impl PocketCameraBankController {
+    pub fn create(
+        rom_data: &[u8],
+        num_rom_banks: usize,
+        source: Box<dyn CameraImageSource>,
+    ) -> Result<Box<dyn MemoryBankController>, String> {
+        let RomRamBanks { rom, ram } = get_rom_ram_banks(rom_data, num_rom_banks, NUM_RAM_BANKS);
+
+        Ok(Box::new(Self::new(rom, ram, num_rom_banks, source)))
+    }
+
+    fn new(
+        rom: Vec<u8>,
+        ram: Vec<u8>,
+        num_rom_banks: usize,
+        source: Box<dyn CameraImageSource>,
+    ) -> Self {
+        Self {
+            rom,
+            ram,
+            // The num_rom_banks is always a power of 2.
+            // This make the number of rom banks - 1, the mask for the rom bank number.
+            mask_rom_bank_number: num_rom_banks.saturating_sub(1),
+            rom_bank_number: 1,
+            ram_bank_number: 0,
+            is_ram_enabled: false,
+            are_registers_selected: false,
+            registers: [0; NUM_REGISTERS],
+            capture_time_left: None,
+            source,
+        }
+    }
+
+    fn get_ext_ram_relative_address(&self, address: u16) -> usize {
+        let relative_address = (address - EXT_RAM_START_ADDRESS) as usize
+            + (self.ram_bank_number as usize * RAM_BANK_SIZE);
+        relative_address % self.ram.len()
+    }
+
+    fn write_register(&mut self, address: u16, value: u8) {
+        let index = (address & REGISTERS_ADDRESS_MASK) as usize;
+        if index >= NUM_REGISTERS {
+            return;
+        }
+        if index == REGISTER_CONTROL {
+            if value & CONTROL_CAPTURE_BIT != 0 && self.capture_time_left.is_none() {
+                self.capture_time_left = Some(self.get_capture_duration());
+            }
+            // Clearing the capture bit does not stop a running capture.
+            let capture_bit = if self.capture_time_left.is_some() {
+                CONTROL_CAPTURE_BIT
+            } else {
+                0
+            };
+            self.registers[index] = (value & !CONTROL_CAPTURE_BIT) | capture_bit;
+            return;
+        }
+        self.registers[index] = value;
+    }
+
+    fn get_capture_duration(&self) -> Duration {
+        let exposure = u16::from_be_bytes([
+            self.registers[REGISTER_EXPOSURE_HIGH],
+            self.registers[REGISTER_EXPOSURE_LOW],
+        ]) as u64;
+        let mut m_cycles = CAPTURE_BASE_M_CYCLES + CAPTURE_EXPOSURE_M_CYCLES * exposure;
+        if self.registers[REGISTER_GAIN_EDGE] & N_BIT == 0 {
+            m_cycles += CAPTURE_NO_N_M_CYCLES;
+        }
+        convert_cycles_to_duration(m_cycles * CYCLES_PER_M_CYCLE)
+    }
+
+    fn finish_capture(&mut self) {
+        self.capture_time_left = None;
+        self.registers[REGISTER_CONTROL] &= !CONTROL_CAPTURE_BIT;
+
+        let colors = process_image(&self.registers, &self.source.capture());
+        let image = &mut self.ram[IMAGE_ADDRESS..IMAGE_ADDRESS + colors.len() / 4];
+        image.fill(0);
+        for (index, color) in colors.into_iter().enumerate() {
+            let (x, y) = (index % SENSOR_WIDTH, index / SENSOR_WIDTH);
+            let tile = (y / TILE_SIZE) * NUM_TILES_PER_ROW + x / TILE_SIZE;
+            let offset = tile * TILE_NUM_BYTES + (y % TILE_SIZE) * 2;
+            let bit = 7 - (x % TILE_SIZE);
+            image[offset] |= (color & 0b01) << bit;
+            image[offset + 1] |= ((color & 0b10) >> 1) << bit;
+        }
+    }
+}
+
+/// Converts the brightness seen by the sensor into Game Boy colors, 0 being white and 3 black.
+fn process_image(registers: &[u8; NUM_REGISTERS], pixels: &[u8]) -> Vec<u8> {
+    // Simplified model of the sensor: the output voltage is linear with the gain and exposure.
+    let gain = 1.0 + (registers[REGISTER_GAIN_EDGE] & GAIN_MASK) as f32 / 8.0;
+    let exposure = u16::from_be_bytes([
+        registers[REGISTER_EXPOSURE_HIGH],
+        registers[REGISTER_EXPOSURE_LOW],
+    ]) as f32
+        / REFERENCE_EXPOSURE;
+    let get_pixel = |x: isize, y: isize| {
+        let x = x.clamp(0, SENSOR_WIDTH as isize - 1) as usize;
+        let y = y.clamp(0, SENSOR_HEIGHT as isize - 1) as usize;
+        pixels.get(y * SENSOR_WIDTH + x).copied().unwrap_or(0) as f32 * gain * exposure
+    };
+
+    let is_edge_enhanced =
+        registers[REGISTER_GAIN_EDGE] & EDGE_ENHANCEMENT_MASK == EDGE_ENHANCEMENT_MASK;
+    let edge_ratio = EDGE_RATIOS
+        [((registers[REGISTER_EDGE_RATIO_INVERT] >> EDGE_RATIO_SHIFT) & EDGE_RATIO_MASK) as usize];
+    let is_inverted = registers[REGISTER_EDGE_RATIO_INVERT] & INVERT_BIT != 0;
+
+    let mut colors = Vec::with_capacity(SENSOR_WIDTH * SENSOR_HEIGHT);
+    for y in 0..SENSOR_HEIGHT as isize {
+        for x in 0..SENSOR_WIDTH as isize {
+            let mut value = get_pixel(x, y);
+            if is_edge_enhanced {
+                let neighbors = get_pixel(x - 1, y)
+                    + get_pixel(x + 1, y)
+                    + get_pixel(x, y - 1)
+                    + get_pixel(x, y + 1);
+                value += edge_ratio * (4.0 * value - neighbors);
+            }
+            let mut value = value.clamp(0.0, 255.0) as u8;
+            if is_inverted {
+                value = 255 - value;
+            }
+
+            let matrix_index = (x as usize % DITHER_MATRIX_SIZE)
+                + (y as usize % DITHER_MATRIX_SIZE) * DITHER_MATRIX_SIZE;
+            let start = REGISTER_DITHER_MATRIX + matrix_index * DITHER_NUM_THRESHOLDS;
+            let thresholds = &registers[start..start + DITHER_NUM_THRESHOLDS];
+            let darkness = thresholds
+                .iter()
+                .filter(|threshold| value < **threshold)
+                .count() as u8;
+            colors.push(darkness);
+        }
+    }
+    colors
+}
+
+impl MemoryBankController for PocketCameraBankController {
+    fn write_rom(&mut self, address: u16, value: u8) {
+        match address {
+            0x0000..=0x1FFF => {
+                self.is_ram_enabled = value & RAM_ENABLE_MASK == RAM_ENABLE_VALUE;
+            }
+            // ROM bank number, bank 0 is not translated to 1.
+            0x2000..=0x3FFF => {
+                self.rom_bank_number = value & ROM_BANK_NUMBER_MASK;
+            }
+            0x4000..=0x5FFF => {
+                self.are_registers_selected = value & REGISTERS_SELECT_BIT != 0;
+                self.ram_bank_number = value & RAM_BANK_NUMBER_MASK;
+            }
+            _ => { /* Nothing to do */ }
+        }
+    }
+
+    fn read_rom(&self, address: u16) -> u8 {
+        match address {
+            0x0000..=0x3FFF => self.rom[address as usize],
+            0x4000..=0x7FFF => {
+                let rom_bank_number = self.rom_bank_number as usize & self.mask_rom_bank_number;
+                self.rom[rom_bank_number * ROM_BANK_SIZE + (address as usize & 0x3FFF)]
+            }
+            _ => OPEN_BUS_VALUE,
+        }
+    }
+
+    fn write_ext_ram(&mut self, address: u16, value: u8) {
+        if self.are_registers_selected {
+            self.write_register(address, value);
+            return;
+        }
+        if !self.is_ram_enabled {
+            return;
+        }
+        let relative_address = self.get_ext_ram_relative_address(address);
+        self.ram[relative_address] = value;
+    }
+
+    fn read_ext_ram(&self, address: u16) -> u8 {
+        if self.are_registers_selected {
+            // Only A000 can be read, the other registers are write only.
+            return match address & REGISTERS_ADDRESS_MASK {
+                0 => self.registers[REGISTER_CONTROL] & CONTROL_READABLE_MASK,
+                _ => 0x00,
+            };
+        }
+        if self.capture_time_left.is_some() {
+            // The RAM is used by the capture.
+            return 0x00;
+        }
+        self.ram[self.get_ext_ram_relative_address(address)]
+    }
+
+    fn update(&mut self, duration: Duration) {
+        if let Some(time_left) = self.capture_time_left {
+            match time_left.checked_sub(duration) {
+                Some(time_left) if !time_left.is_zero() => self.capture_time_left = Some(time_left),
+                _ => self.finish_capture(),
+            }
+        }
+    }
+
+    fn save_battery(&self) -> Vec<u8> {
+        self.ram.clone()
+    }
+
+    fn load_battery(&mut self, data: &[u8]) {
+        load_ram(&mut self.ram, data);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct UniformSource(u8);
+
+    impl CameraImageSource for UniformSource {
+        fn capture(&mut self) -> Vec<u8> {
+            vec![self.0; SENSOR_WIDTH * SENSOR_HEIGHT]
+        }
+    }
+
+    fn create_controller(brightness: u8) -> PocketCameraBankController {
+        PocketCameraBankController::new(
+            vec![0; 2 * ROM_BANK_SIZE],
+            vec![0; NUM_RAM_BANKS * RAM_BANK_SIZE],
+            2,
+            Box::new(UniformSource(brightness)),
+        )
+    }
+
+    /// Sets the exposure to the reference and the same thresholds on the whole matrix.
+    fn configure(controller: &mut PocketCameraBankController, thresholds: [u8; 3]) {
+        controller.write_rom(0x4000, REGISTERS_SELECT_BIT);
+        controller.write_ext_ram(0xA001, N_BIT);
+        controller.write_ext_ram(0xA002, 0x10);
+        controller.write_ext_ram(0xA003, 0x00);
+        for index in 0..DITHER_MATRIX_SIZE * DITHER_MATRIX_SIZE {
+            for (offset, threshold) in thresholds.iter().enumerate() {
+                let address = 0xA006 + (index * DITHER_NUM_THRESHOLDS + offset) as u16;
+                controller.write_ext_ram(address, *threshold);
+            }
+        }
+    }
+
+    #[test]
+    fn registers_replace_ram_when_selected() {
+        let mut controller = create_controller(0);
+        controller.write_rom(0x0000, RAM_ENABLE_VALUE);
+        controller.write_rom(0x4000, 0x0F);
+        controller.write_ext_ram(0xA000, 0x42);
+        assert_eq!(controller.read_ext_ram(0xA000), 0x42);
+
+        controller.write_rom(0x4000, REGISTERS_SELECT_BIT);
+        controller.write_ext_ram(0xA000, 0x06);
+        controller.write_ext_ram(0xA001, 0xFF);
+        assert_eq!(controller.read_ext_ram(0xA000), 0x06);
+        assert_eq!(controller.read_ext_ram(0xA001), 0x00);
+        // The registers are mirrored every 0x80 bytes.
+        assert_eq!(controller.read_ext_ram(0xA080), 0x06);
+
+        controller.write_rom(0x4000, 0x0F);
+        assert_eq!(controller.read_ext_ram(0xA000), 0x42);
+    }
+
+    #[test]
+    fn capture_is_busy_then_writes_the_image() {
+        let mut controller = create_controller(0x80);
+        configure(&mut controller, [0x40, 0x90, 0xC0]);
+        controller.write_ext_ram(0xA000, CONTROL_CAPTURE_BIT);
+        assert_eq!(controller.read_ext_ram(0xA000), CONTROL_CAPTURE_BIT);
+
+        let duration = controller.get_capture_duration();
+        controller.update(duration / 2);
+        assert_eq!(controller.read_ext_ram(0xA000), CONTROL_CAPTURE_BIT);
+        controller.update(duration / 2);
+        assert_eq!(controller.read_ext_ram(0xA000), 0);
+
+        // 0x80 is below 2 of the 3 thresholds: color 2 sets only the high bit plane.
+        controller.write_rom(0x4000, 0x00);
+        assert_eq!(controller.read_ext_ram(0xA100), 0x00);
+        assert_eq!(controller.read_ext_ram(0xA101), 0xFF);
+        assert_eq!(controller.read_ext_ram(0xAEFF), 0xFF);
+    }
+
+    #[test]
+    fn capture_duration_depends_on_exposure_and_n_bit() {
+        let mut controller = create_controller(0);
+        controller.write_rom(0x4000, REGISTERS_SELECT_BIT);
+        controller.write_ext_ram(0xA003, 0x01);
+        assert_eq!(
+            controller.get_capture_duration(),
+            convert_cycles_to_duration((32446 + 512 + 16) * 4)
+        );
+
+        controller.write_ext_ram(0xA001, N_BIT);
+        assert_eq!(
+            controller.get_capture_duration(),
+            convert_cycles_to_duration((32446 + 16) * 4)
+        );
+    }
+
+    #[test]
+    fn invert_and_edge_enhancement_change_the_colors() {
+        let mut registers = [0; NUM_REGISTERS];
+        registers[REGISTER_EXPOSURE_HIGH] = 0x10;
+        registers[REGISTER_DITHER_MATRIX..].fill(0x80);
+        let mut pixels = vec![0x40; SENSOR_WIDTH * SENSOR_HEIGHT];
+        pixels[SENSOR_WIDTH + 1] = 0x70;
+
+        let colors = process_image(&registers, &pixels);
+        assert_eq!(colors[SENSOR_WIDTH + 1], 3);
+
+        registers[REGISTER_EDGE_RATIO_INVERT] = INVERT_BIT;
+        let colors = process_image(&registers, &pixels);
+        assert_eq!(colors[SENSOR_WIDTH + 1], 0);
+
+        // With a ratio of 0.5, the pixel brighter than its neighbors is enhanced above 0x80.
+        registers[REGISTER_EDGE_RATIO_INVERT] = 0;
+        registers[REGISTER_GAIN_EDGE] = EDGE_ENHANCEMENT_MASK;
+        let colors = process_image(&registers, &pixels);
+        assert_eq!(colors[SENSOR_WIDTH + 1], 0);
+        assert_eq!(colors[0], 3);
+    }
+}
@@ -5,6 +5,13 @@ pub(crate) mod init;
 pub(crate) mod mbc;
 pub(crate) mod ram;
 pub(crate) mod registers;
+pub(crate) mod unusable;
+
+/// Value read when nothing drives the data bus (e.g. disabled or missing cartridge RAM).
+///
+/// The pull-up resistors of the data bus make these reads return 0xFF.
+/// Information from: https://gbdev.io/pandocs/MBC1.html#a000bfff--ram-bank-0003-if-any
+pub const OPEN_BUS_VALUE: u8 = 0xFF;
 
 pub trait Memory {
     fn read(&self, address: u16) -> u8;
@@ -28,6 +28,8 @@ pub struct Registers {
 
     pub halted: bool,
     pub stopped: bool,
+    /// Set by the illegal opcodes, the CPU stops until the console is reset.
+    pub locked_up: bool,
     pub ime_flag: bool, // Interrupt Master Enable Flag
 }
 
@@ -46,6 +48,7 @@ impl Registers {
             pc: 0,
             halted: false,
             stopped: false,
+            locked_up: false,
             ime_flag: false,
         }
     }
@@ -0,0 +1,128 @@
+//! Information from: https://gbdev.io/pandocs/Memory_Map.html#fea0-feff-range
+//!
+//! The behavior of this range is not documented by Nintendo and depends on the hardware revision:
+//! * DMG, MGB, SGB, SGB2: reads return 0x00 (0xFF when OAM is blocked by the PPU or by an OAM
+//!   DMA transfer), writes are ignored.
+//! * CGB revisions 0 to D: the range is backed by a small RAM with revision specific mirroring.
+//! * CGB revision E and AGB: reads return the high nibble of the lower address byte twice.
+//!
+//! The CGB 0-D mirroring follows the SameBoy implementation: https://github.com/LIJI32/SameBoy
+
+use crate::memory::gbmemory::{NOT_USABLE_END_ADDRESS, NOT_USABLE_START_ADDRESS};
+use crate::model::HardwareModel;
+
+const NOT_USABLE_SIZE: usize = (NOT_USABLE_END_ADDRESS - NOT_USABLE_START_ADDRESS) as usize + 1;
+const OAM_BLOCKED_VALUE: u8 = 0xFF;
+const CGB_D_MIRROR_START_ADDRESS: u16 = 0xFEC0;
+
+pub struct UnusableMemory {
+    model: HardwareModel,
+    ram: Vec<u8>,
+}
+
+impl UnusableMemory {
+    pub fn new(model: HardwareModel) -> Self {
+        Self {
+            model,
+            ram: vec![0u8; NOT_USABLE_SIZE],
+        }
+    }
+
+    pub fn read(&self, address: u16, is_oam_blocked: bool) -> u8 {
+        if is_oam_blocked {
+            return OAM_BLOCKED_VALUE;
+        }
+        match self.model {
+            HardwareModel::Dmg | HardwareModel::Mgb | HardwareModel::Sgb | HardwareModel::Sgb2 => 0,
+            HardwareModel::CgbE | HardwareModel::Agb => {
+                let low = address & 0xF0;
+                (low | (low >> 4)) as u8
+            }
+            _ => self.ram[self.get_ram_index(address)],
+        }
+    }
+
+    pub fn write(&mut self, address: u16, value: u8, is_oam_blocked: bool) {
+        if is_oam_blocked || !self.has_ram() {
+            return;
+        }
+        let index = self.get_ram_index(address);
+        self.ram[index] = value;
+    }
+
+    fn has_ram(&self) -> bool {
+        matches!(
+            self.model,
+            HardwareModel::Cgb0
+                | HardwareModel::CgbA
+                | HardwareModel::CgbB
+                | HardwareModel::CgbC
+                | HardwareModel::CgbD
+        )
+    }
+
+    fn get_ram_index(&self, address: u16) -> usize {
+        let address = match self.model {
+            HardwareModel::CgbD if address >= CGB_D_MIRROR_START_ADDRESS => address | 0xF0,
+            HardwareModel::CgbD => address,
+            _ => address & !0x18,
+        };
+        (address - NOT_USABLE_START_ADDRESS) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dmg_returns_zero_or_ff_when_oam_is_blocked() {
+        let mut memory = UnusableMemory::new(HardwareModel::Dmg);
+        memory.write(0xFEA0, 0x12, false);
+
+        assert_eq!(memory.read(0xFEA0, false), 0);
+        assert_eq!(memory.read(0xFEFF, false), 0);
+        assert_eq!(memory.read(0xFEA0, true), 0xFF);
+    }
+
+    #[test]
+    fn cgb_e_returns_address_pattern() {
+        let memory = UnusableMemory::new(HardwareModel::CgbE);
+
+        assert_eq!(memory.read(0xFEA0, false), 0xAA);
+        assert_eq!(memory.read(0xFEB7, false), 0xBB);
+        assert_eq!(memory.read(0xFEFF, false), 0xFF);
+        assert_eq!(memory.read(0xFEC3, true), 0xFF);
+    }
+
+    #[test]
+    fn cgb_c_ram_is_mirrored() {
+        let mut memory = UnusableMemory::new(HardwareModel::CgbC);
+        memory.write(0xFEA1, 0x42, false);
+
+        assert_eq!(memory.read(0xFEA1, false), 0x42);
+        assert_eq!(memory.read(0xFEA9, false), 0x42);
+        assert_eq!(memory.read(0xFEB9, false), 0x42);
+        assert_eq!(memory.read(0xFEC1, false), 0);
+    }
+
+    #[test]
+    fn cgb_d_upper_range_is_mirrored() {
+        let mut memory = UnusableMemory::new(HardwareModel::CgbD);
+        memory.write(0xFEC5, 0x42, false);
+        memory.write(0xFEA5, 0x24, false);
+
+        assert_eq!(memory.read(0xFED5, false), 0x42);
+        assert_eq!(memory.read(0xFEF5, false), 0x42);
+        assert_eq!(memory.read(0xFEA5, false), 0x24);
+        assert_eq!(memory.read(0xFEB5, false), 0);
+    }
+
+    #[test]
+    fn writes_are_ignored_when_oam_is_blocked() {
+        let mut memory = UnusableMemory::new(HardwareModel::Cgb0);
+        memory.write(0xFEA0, 0x42, true);
+
+        assert_eq!(memory.read(0xFEA0, false), 0);
+    }
+}
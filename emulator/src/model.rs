@@ -0,0 +1,40 @@
+use crate::cartridge::CGBFlag;
+
+/// Hardware revision being emulated.
+///
+/// Most of the emulation only depends on whether the CGB mode is used or not, but some
+/// undocumented behaviors change from one revision to the other.
+/// Information from: https://gbdev.io/pandocs/Specifications.html
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum HardwareModel {
+    Dmg,
+    Mgb,
+    Sgb,
+    Sgb2,
+    Cgb0,
+    CgbA,
+    CgbB,
+    CgbC,
+    CgbD,
+    CgbE,
+    Agb,
+}
+
+impl HardwareModel {
+    /// Returns the model used when none is explicitly requested for a cartridge.
+    pub fn default_for(cgb_flag: CGBFlag) -> Self {
+        if cgb_flag.use_cgb() {
+            Self::CgbE
+        } else {
+            Self::Dmg
+        }
+    }
+
+    pub fn is_cgb(&self) -> bool {
+        !matches!(self, Self::Dmg | Self::Mgb | Self::Sgb | Self::Sgb2)
+    }
+
+    pub fn is_sgb(&self) -> bool {
+        matches!(self, Self::Sgb | Self::Sgb2)
+    }
+}
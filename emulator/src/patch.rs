@@ -0,0 +1,412 @@
+//! Soft patching of the roms with IPS, BPS and UPS files.
+
+use std::path::{Path, PathBuf};
+
+/// Information from: https://zerosoft.zophar.net/ips.php
+const IPS_MAGIC: &[u8] = b"PATCH";
+const IPS_END_MARKER: &[u8] = b"EOF";
+/// Information from: https://www.romhacking.net/documents/746/
+const BPS_MAGIC: &[u8] = b"BPS1";
+/// Information from: https://www.romhacking.net/documents/392/
+const UPS_MAGIC: &[u8] = b"UPS1";
+/// BPS and UPS files end with the CRC32 of the source, the target and the patch.
+const CHECKSUMS_SIZE: usize = 12;
+/// The largest rom size a cartridge header can declare, 8 MiB.
+/// Information from: https://gbdev.io/pandocs/The_Cartridge_Header.html#0148--rom-size
+const MAX_TARGET_SIZE: usize = 8 * 1024 * 1024;
+
+/// Extensions of the patch files looked for next to the rom, in order of priority.
+pub const PATCH_EXTENSIONS: [&str; 3] = ["bps", "ups", "ips"];
+
+/// Returns the patch file named like the rom, if one exists.
+pub fn find_patch(rom_path: &Path) -> Option<PathBuf> {
+    PATCH_EXTENSIONS
+        .iter()
+        .map(|extension| rom_path.with_extension(extension))
+        .find(|path| path.is_file())
+}
+
+/// Applies the patch to the rom, the format is detected from the patch header.
+pub fn apply_patch(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, String> {
+    if patch.starts_with(IPS_MAGIC) {
+        apply_ips(rom, patch)
+    } else if patch.starts_with(BPS_MAGIC) {
+        apply_bps(rom, patch)
+    } else if patch.starts_with(UPS_MAGIC) {
+        apply_ups(rom, patch)
+    } else {
+        Err("Unknown patch format".to_string())
+    }
+}
+
+struct PatchReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> PatchReader<'a> {
+    fn new(data: &'a [u8], position: usize) -> Self {
+        Self { data, position }
+    }
+
+    fn read_bytes(&mut self, size: usize) -> Result<&'a [u8], String> {
+        let bytes = self
+            .position
+            .checked_add(size)
+            .and_then(|end| self.data.get(self.position..end))
+            .ok_or("The patch is truncated")?;
+        self.position += size;
+        Ok(bytes)
+    }
+
+    fn read_u8(&mut self) -> Result<u8, String> {
+        Ok(self.read_bytes(1)?[0])
+    }
+
+    fn read_big_endian(&mut self, size: usize) -> Result<usize, String> {
+        Ok(self
+            .read_bytes(size)?
+            .iter()
+            .fold(0, |value, byte| (value << 8) | *byte as usize))
+    }
+
+    /// Variable length number shared by BPS and UPS.
+    fn read_number(&mut self) -> Result<usize, String> {
+        let mut value = 0usize;
+        let mut shift = 1usize;
+        loop {
+            let byte = self.read_u8()?;
+            value = ((byte & 0x7F) as usize)
+                .checked_mul(shift)
+                .and_then(|digit| value.checked_add(digit))
+                .ok_or("Invalid number in the patch")?;
+            if byte & 0x80 != 0 {
+                return Ok(value);
+            }
+            shift = shift
+                .checked_mul(1 << 7)
+                .ok_or("Invalid number in the patch")?;
+            value = value
+                .checked_add(shift)
+                .ok_or("Invalid number in the patch")?;
+        }
+    }
+
+    /// Reads the size of the patched rom, larger sizes are refused before allocating the rom.
+    fn read_target_size(&mut self) -> Result<usize, String> {
+        let size = self.read_number()?;
+        if size > MAX_TARGET_SIZE {
+            return Err(format!(
+                "The patched rom would have {} bytes, more than the largest rom",
+                size
+            ));
+        }
+        Ok(size)
+    }
+}
+
+fn apply_ips(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, String> {
+    let mut target = rom.to_vec();
+    let mut reader = PatchReader::new(patch, IPS_MAGIC.len());
+    loop {
+        if reader.data[reader.position..].starts_with(IPS_END_MARKER) {
+            reader.position += IPS_END_MARKER.len();
+            break;
+        }
+        let offset = reader.read_big_endian(3)?;
+        let size = reader.read_big_endian(2)?;
+        // A size of 0 is a run of the same byte.
+        let (size, data) = if size == 0 {
+            let size = reader.read_big_endian(2)?;
+            (size, vec![reader.read_u8()?; size])
+        } else {
+            (size, reader.read_bytes(size)?.to_vec())
+        };
+        if target.len() < offset + size {
+            target.resize(offset + size, 0);
+        }
+        target[offset..offset + size].copy_from_slice(&data);
+    }
+    // Extension used by some patches to truncate the rom.
+    if let Ok(size) = reader.read_big_endian(3) {
+        target.truncate(size);
+    }
+    Ok(target)
+}
+
+fn apply_ups(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, String> {
+    let checksums = get_checksums(patch)?;
+    let mut reader = PatchReader::new(&patch[..patch.len() - CHECKSUMS_SIZE], UPS_MAGIC.len());
+    let source_size = reader.read_number()?;
+    let target_size = reader.read_target_size()?;
+    checksums.check_source(rom, source_size)?;
+
+    let mut target = rom.to_vec();
+    target.resize(target_size, 0);
+    let mut position = 0usize;
+    while reader.position < reader.data.len() {
+        position = position
+            .checked_add(reader.read_number()?)
+            .filter(|position| *position <= target_size)
+            .ok_or("Invalid offset in the patch")?;
+        loop {
+            let byte = reader.read_u8()?;
+            position += 1;
+            if byte == 0 {
+                break;
+            }
+            if let Some(value) = target.get_mut(position - 1) {
+                *value ^= byte;
+            }
+        }
+    }
+    checksums.check_target(&target)?;
+    Ok(target)
+}
+
+fn apply_bps(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, String> {
+    let checksums = get_checksums(patch)?;
+    let mut reader = PatchReader::new(&patch[..patch.len() - CHECKSUMS_SIZE], BPS_MAGIC.len());
+    let source_size = reader.read_number()?;
+    let target_size = reader.read_target_size()?;
+    let metadata_size = reader.read_number()?;
+    reader.read_bytes(metadata_size)?;
+    checksums.check_source(rom, source_size)?;
+
+    let mut target = Vec::with_capacity(target_size);
+    let mut source_offset = 0usize;
+    let mut target_offset = 0usize;
+    while reader.position < reader.data.len() {
+        let action = reader.read_number()?;
+        let length = (action >> 2) + 1;
+        if length > target_size - target.len() {
+            return Err("The patch writes after the end of the patched rom".to_string());
+        }
+        match action & 0b11 {
+            // Source read
+            0 => {
+                let start = target.len();
+                target.extend_from_slice(get_range(rom, start, length)?);
+            }
+            // Target read
+            1 => target.extend_from_slice(reader.read_bytes(length)?),
+            // Source copy
+            2 => {
+                source_offset = apply_relative_offset(source_offset, reader.read_number()?)?;
+                target.extend_from_slice(get_range(rom, source_offset, length)?);
+                source_offset += length;
+            }
+            // Target copy, the copied bytes can overlap the written ones.
+            _ => {
+                target_offset = apply_relative_offset(target_offset, reader.read_number()?)?;
+                for _ in 0..length {
+                    let value = *target.get(target_offset).ok_or("Invalid target copy")?;
+                    target.push(value);
+                    target_offset += 1;
+                }
+            }
+        }
+    }
+    if target.len() != target_size {
+        return Err(format!(
+            "The patched rom has {} bytes instead of {}",
+            target.len(),
+            target_size
+        ));
+    }
+    checksums.check_target(&target)?;
+    Ok(target)
+}
+
+fn get_range(data: &[u8], start: usize, length: usize) -> Result<&[u8], String> {
+    start
+        .checked_add(length)
+        .and_then(|end| data.get(start..end))
+        .ok_or_else(|| "The patch reads outside of the source rom".to_string())
+}
+
+/// The lowest bit is the sign of the offset.
+fn apply_relative_offset(offset: usize, value: usize) -> Result<usize, String> {
+    let delta = value >> 1;
+    let result = if value & 1 == 0 {
+        offset.checked_add(delta)
+    } else {
+        offset.checked_sub(delta)
+    };
+    result.ok_or_else(|| "Invalid relative offset in the patch".to_string())
+}
+
+struct Checksums {
+    source: u32,
+    target: u32,
+}
+
+impl Checksums {
+    fn check_source(&self, rom: &[u8], source_size: usize) -> Result<(), String> {
+        if rom.len() != source_size || crc32fast::hash(rom) != self.source {
+            return Err("The patch was not made for this rom".to_string());
+        }
+        Ok(())
+    }
+
+    fn check_target(&self, target: &[u8]) -> Result<(), String> {
+        if crc32fast::hash(target) != self.target {
+            return Err("The checksum of the patched rom is invalid".to_string());
+        }
+        Ok(())
+    }
+}
+
+fn get_checksums(patch: &[u8]) -> Result<Checksums, String> {
+    let start = patch
+        .len()
+        .checked_sub(CHECKSUMS_SIZE)
+        .ok_or("The patch is truncated")?;
+    let read = |index: usize| {
+        let offset = start + index * 4;
+        u32::from_le_bytes(patch[offset..offset + 4].try_into().unwrap())
+    };
+    if crc32fast::hash(&patch[..patch.len() - 4]) != read(2) {
+        return Err("The checksum of the patch is invalid".to_string());
+    }
+    Ok(Checksums {
+        source: read(0),
+        target: read(1),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn push_number(data: &mut Vec<u8>, mut value: usize) {
+        loop {
+            let byte = (value & 0x7F) as u8;
+            value >>= 7;
+            if value == 0 {
+                data.push(0x80 | byte);
+                return;
+            }
+            data.push(byte);
+            value -= 1;
+        }
+    }
+
+    fn push_checksums(data: &mut Vec<u8>, source: &[u8], target: &[u8]) {
+        data.extend(crc32fast::hash(source).to_le_bytes());
+        data.extend(crc32fast::hash(target).to_le_bytes());
+        data.extend(crc32fast::hash(data).to_le_bytes());
+    }
+
+    #[test]
+    fn ips_records_and_runs_are_applied() {
+        let mut patch = IPS_MAGIC.to_vec();
+        patch.extend([0, 0, 1, 0, 2, 0xAA, 0xBB]);
+        patch.extend([0, 0, 6, 0, 0, 0, 3, 0xCC]);
+        patch.extend(IPS_END_MARKER);
+
+        let target = apply_patch(&[0; 4], &patch).unwrap();
+        assert_eq!(target, vec![0, 0xAA, 0xBB, 0, 0, 0, 0xCC, 0xCC, 0xCC]);
+    }
+
+    #[test]
+    fn ups_hunks_are_applied() {
+        let source = b"ABCD";
+        let target = b"ABXDE";
+        let mut patch = UPS_MAGIC.to_vec();
+        push_number(&mut patch, source.len());
+        push_number(&mut patch, target.len());
+        push_number(&mut patch, 2);
+        patch.extend([b'C' ^ b'X', 0]);
+        push_number(&mut patch, 0);
+        patch.extend([b'E', 0]);
+        push_checksums(&mut patch, source, target);
+
+        assert_eq!(apply_patch(source, &patch).unwrap(), target.to_vec());
+        assert!(apply_patch(b"ABCE", &patch).is_err());
+    }
+
+    #[test]
+    fn bps_actions_are_applied() {
+        let source = b"ABCD";
+        let target = b"ABXDEE";
+        let mut patch = BPS_MAGIC.to_vec();
+        push_number(&mut patch, source.len());
+        push_number(&mut patch, target.len());
+        push_number(&mut patch, 0);
+        // Source copy of "AB" from offset 0.
+        push_number(&mut patch, (1 << 2) | 2);
+        push_number(&mut patch, 0);
+        // Target read of "X".
+        push_number(&mut patch, 1);
+        patch.push(b'X');
+        // Source read of "D".
+        push_number(&mut patch, 0);
+        // Target read of "E", then target copy of the "E" at offset 4.
+        push_number(&mut patch, 1);
+        patch.push(b'E');
+        push_number(&mut patch, 3);
+        push_number(&mut patch, 4 << 1);
+        push_checksums(&mut patch, source, target);
+
+        assert_eq!(apply_patch(source, &patch).unwrap(), target.to_vec());
+
+        let last = patch.len() - 1;
+        patch[last] ^= 0xFF;
+        assert!(apply_patch(source, &patch).is_err());
+    }
+    #[test]
+    fn truncated_patches_are_rejected() {
+        let source = b"ABCD";
+        let mut patch = UPS_MAGIC.to_vec();
+        push_number(&mut patch, source.len());
+        // The size of the patched rom is missing its last byte.
+        patch.push(0x00);
+        push_checksums(&mut patch, source, source);
+
+        assert_eq!(
+            apply_patch(source, &patch),
+            Err("The patch is truncated".to_string())
+        );
+        assert!(apply_patch(source, BPS_MAGIC).is_err());
+    }
+
+    #[test]
+    fn overflowing_numbers_are_rejected() {
+        let source = b"ABCD";
+        let mut patch = BPS_MAGIC.to_vec();
+        patch.extend([0x7F; 10]);
+        patch.push(0xFF);
+        push_checksums(&mut patch, source, source);
+
+        assert_eq!(
+            apply_patch(source, &patch),
+            Err("Invalid number in the patch".to_string())
+        );
+    }
+
+    #[test]
+    fn oversized_patched_roms_are_rejected() {
+        let source = b"ABCD";
+        let mut patch = UPS_MAGIC.to_vec();
+        push_number(&mut patch, source.len());
+        push_number(&mut patch, usize::MAX >> 1);
+        push_checksums(&mut patch, source, source);
+        assert!(apply_patch(source, &patch).is_err());
+
+        // A target copy cannot grow the rom past its declared size.
+        let mut patch = BPS_MAGIC.to_vec();
+        push_number(&mut patch, source.len());
+        push_number(&mut patch, 2);
+        push_number(&mut patch, 0);
+        push_number(&mut patch, 1);
+        patch.push(b'E');
+        push_number(&mut patch, ((usize::MAX >> 3) << 2) | 3);
+        push_number(&mut patch, 0);
+        push_checksums(&mut patch, source, source);
+        assert_eq!(
+            apply_patch(source, &patch),
+            Err("The patch writes after the end of the patched rom".to_string())
+        );
+    }
+}
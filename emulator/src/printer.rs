@@ -0,0 +1,409 @@
+//! Game Boy Printer, plugged on the link cable.
+//!
+//! Information from: https://gbdev.io/pandocs/Gameboy_Printer.html
+
+use crate::serial::SerialDevice;
+use log::{error, info};
+use std::fs;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+const MAGIC_1: u8 = 0x88;
+const MAGIC_2: u8 = 0x33;
+/// Sent by the printer on the first byte following the checksum.
+const DEVICE_ID: u8 = 0x81;
+
+const COMMAND_INIT: u8 = 0x01;
+const COMMAND_PRINT: u8 = 0x02;
+const COMMAND_DATA: u8 = 0x04;
+const COMMAND_STATUS: u8 = 0x0F;
+const PRINT_ARGUMENTS_SIZE: usize = 4;
+
+const STATUS_CHECKSUM_ERROR: u8 = 1 << 0;
+const STATUS_PRINTING: u8 = 1 << 1;
+const STATUS_IMAGE_DATA_FULL: u8 = 1 << 2;
+const STATUS_UNPROCESSED_DATA: u8 = 1 << 3;
+const STATUS_PACKET_ERROR: u8 = 1 << 4;
+/// Number of status requests answered as printing after a print command.
+const PRINTING_STATUS_COUNT: u8 = 4;
+
+/// The printer buffer holds 9 data packets of 2 rows of 20 tiles.
+const TILES_PER_ROW: usize = 20;
+const TILE_SIZE: usize = 16;
+const TILE_ROW_SIZE: usize = TILES_PER_ROW * TILE_SIZE;
+const BUFFER_SIZE: usize = 9 * 2 * TILE_ROW_SIZE;
+pub const PAPER_WIDTH: usize = TILES_PER_ROW * 8;
+/// Each margin unit feeds the height of a tile row.
+const MARGIN_UNIT_HEIGHT: usize = 8;
+/// Gray levels of the 4 shades, from white to black.
+const SHADES: [u8; 4] = [0xFF, 0xAA, 0x55, 0x00];
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum PacketState {
+    Magic1,
+    Magic2,
+    Command,
+    Compression,
+    LengthLow,
+    LengthHigh,
+    Data,
+    ChecksumLow,
+    ChecksumHigh,
+    DeviceId,
+    Status,
+}
+
+#[derive(Default)]
+struct Packet {
+    command: u8,
+    is_compressed: bool,
+    length: usize,
+    data: Vec<u8>,
+    checksum: u16,
+    computed_checksum: u16,
+}
+
+impl Packet {
+    fn add_to_checksum(&mut self, value: u8) {
+        self.computed_checksum = self.computed_checksum.wrapping_add(value as u16);
+    }
+}
+
+/// Receives the packets sent by the game and saves the printed paper strips as PNG images.
+/// A strip ends when a print command feeds paper after the image.
+pub struct GameBoyPrinter {
+    output_directory: PathBuf,
+    state: PacketState,
+    packet: Packet,
+    status: u8,
+    printing_status_count: u8,
+    /// Tile data received since the last print.
+    buffer: Vec<u8>,
+    /// Grayscale pixels of the paper strip, PAPER_WIDTH pixels per line.
+    paper: Vec<u8>,
+    saved_files: Vec<PathBuf>,
+}
+
+impl GameBoyPrinter {
+    /// Creates a printer saving its paper strips in the directory.
+    pub fn new(output_directory: &Path) -> Self {
+        Self {
+            output_directory: output_directory.to_path_buf(),
+            state: PacketState::Magic1,
+            packet: Packet::default(),
+            status: 0,
+            printing_status_count: 0,
+            buffer: Vec::new(),
+            paper: Vec::new(),
+            saved_files: Vec::new(),
+        }
+    }
+
+    /// Returns the PNG files written by the printer.
+    pub fn get_saved_files(&self) -> &[PathBuf] {
+        &self.saved_files
+    }
+
+    fn handle_packet(&mut self) {
+        let packet = std::mem::take(&mut self.packet);
+        if packet.checksum != packet.computed_checksum {
+            self.status |= STATUS_CHECKSUM_ERROR;
+            return;
+        }
+        self.status &= !(STATUS_CHECKSUM_ERROR | STATUS_PACKET_ERROR);
+        match packet.command {
+            COMMAND_INIT => {
+                self.buffer.clear();
+                self.status = 0;
+                self.printing_status_count = 0;
+            }
+            COMMAND_DATA => {
+                let data = if packet.is_compressed {
+                    decompress(&packet.data)
+                } else {
+                    packet.data
+                };
+                let size = data.len().min(BUFFER_SIZE - self.buffer.len());
+                self.buffer.extend_from_slice(&data[..size]);
+                // An empty data packet ends the transfer of the image.
+                if !data.is_empty() {
+                    self.status |= STATUS_UNPROCESSED_DATA;
+                }
+                if self.buffer.len() == BUFFER_SIZE {
+                    self.status |= STATUS_IMAGE_DATA_FULL;
+                }
+            }
+            COMMAND_PRINT if packet.data.len() == PRINT_ARGUMENTS_SIZE => {
+                self.print(packet.data[0], packet.data[1], packet.data[2]);
+                self.status &= !(STATUS_UNPROCESSED_DATA | STATUS_IMAGE_DATA_FULL);
+                self.status |= STATUS_PRINTING;
+                self.printing_status_count = PRINTING_STATUS_COUNT;
+            }
+            COMMAND_STATUS => {
+                if self.printing_status_count > 0 {
+                    self.printing_status_count -= 1;
+                    if self.printing_status_count == 0 {
+                        self.status &= !STATUS_PRINTING;
+                    }
+                }
+            }
+            _ => self.status |= STATUS_PACKET_ERROR,
+        }
+    }
+
+    /// The margins byte holds the lines fed before the image in its high nibble and after the
+    /// image in its low nibble. No image is printed with 0 sheets, only the paper is fed.
+    fn print(&mut self, sheets: u8, margins: u8, palette: u8) {
+        self.feed_paper((margins >> 4) as usize);
+        if sheets > 0 {
+            let image = render_tiles(&self.buffer, palette);
+            self.paper.extend_from_slice(&image);
+        }
+        self.buffer.clear();
+        let margin_after = (margins & 0x0F) as usize;
+        self.feed_paper(margin_after);
+        if margin_after > 0 {
+            self.save_paper();
+        }
+    }
+
+    fn feed_paper(&mut self, nb_units: usize) {
+        let size = nb_units * MARGIN_UNIT_HEIGHT * PAPER_WIDTH;
+        self.paper.resize(self.paper.len() + size, SHADES[0]);
+    }
+
+    /// Saves the paper strip in the next free print_NNN.png file of the output directory.
+    fn save_paper(&mut self) {
+        if self.paper.is_empty() {
+            return;
+        }
+        let paper = std::mem::take(&mut self.paper);
+        let path = (self.saved_files.len() + 1..)
+            .map(|index| {
+                self.output_directory
+                    .join(format!("print_{:03}.png", index))
+            })
+            .find(|path| !path.exists())
+            .unwrap();
+        match save_png(&path, &paper) {
+            Ok(()) => {
+                info!("Printed {:?}", path);
+                self.saved_files.push(path);
+            }
+            Err(e) => error!("Could not save the print {:?}: {}", path, e),
+        }
+    }
+}
+
+impl Drop for GameBoyPrinter {
+    /// Saves the paper strip that was not fed out of the printer.
+    fn drop(&mut self) {
+        self.save_paper();
+    }
+}
+
+impl SerialDevice for GameBoyPrinter {
+    fn exchange(&mut self, value: u8) -> u8 {
+        let mut response = 0x00;
+        self.state = match self.state {
+            PacketState::Magic1 if value == MAGIC_1 => PacketState::Magic2,
+            PacketState::Magic1 => PacketState::Magic1,
+            PacketState::Magic2 if value == MAGIC_2 => {
+                self.packet = Packet::default();
+                PacketState::Command
+            }
+            PacketState::Magic2 => PacketState::Magic1,
+            PacketState::Command => {
+                self.packet.command = value;
+                self.packet.add_to_checksum(value);
+                PacketState::Compression
+            }
+            PacketState::Compression => {
+                self.packet.is_compressed = value & 1 != 0;
+                self.packet.add_to_checksum(value);
+                PacketState::LengthLow
+            }
+            PacketState::LengthLow => {
+                self.packet.length = value as usize;
+                self.packet.add_to_checksum(value);
+                PacketState::LengthHigh
+            }
+            PacketState::LengthHigh => {
+                self.packet.length |= (value as usize) << 8;
+                self.packet.add_to_checksum(value);
+                if self.packet.length == 0 {
+                    PacketState::ChecksumLow
+                } else {
+                    PacketState::Data
+                }
+            }
+            PacketState::Data => {
+                self.packet.data.push(value);
+                self.packet.add_to_checksum(value);
+                if self.packet.data.len() == self.packet.length {
+                    PacketState::ChecksumLow
+                } else {
+                    PacketState::Data
+                }
+            }
+            PacketState::ChecksumLow => {
+                self.packet.checksum = value as u16;
+                PacketState::ChecksumHigh
+            }
+            PacketState::ChecksumHigh => {
+                self.packet.checksum |= (value as u16) << 8;
+                PacketState::DeviceId
+            }
+            PacketState::DeviceId => {
+                response = DEVICE_ID;
+                self.handle_packet();
+                PacketState::Status
+            }
+            PacketState::Status => {
+                response = self.status;
+                PacketState::Magic1
+            }
+        };
+        response
+    }
+}
+
+/// A control byte with bit 7 set repeats the next byte (control & 0x7F) + 2 times, otherwise the
+/// next control + 1 bytes are copied.
+fn decompress(data: &[u8]) -> Vec<u8> {
+    let mut result = Vec::new();
+    let mut index = 0;
+    while index < data.len() {
+        let control = data[index] as usize;
+        index += 1;
+        if control & 0x80 != 0 {
+            if let Some(value) = data.get(index) {
+                result.resize(result.len() + (control & 0x7F) + 2, *value);
+            }
+            index += 1;
+        } else {
+            let end = (index + control + 1).min(data.len());
+            result.extend_from_slice(&data[index..end]);
+            index = end;
+        }
+    }
+    result
+}
+
+/// Converts the rows of 2bpp tiles to grayscale pixels, the palette maps the color indexes to the
+/// shades like the BGP register.
+fn render_tiles(tiles: &[u8], palette: u8) -> Vec<u8> {
+    let nb_rows = tiles.len() / TILE_ROW_SIZE;
+    let mut pixels = Vec::with_capacity(nb_rows * 8 * PAPER_WIDTH);
+    for row in 0..nb_rows {
+        for line in 0..8 {
+            for tile in 0..TILES_PER_ROW {
+                let offset = row * TILE_ROW_SIZE + tile * TILE_SIZE + line * 2;
+                let (low, high) = (tiles[offset], tiles[offset + 1]);
+                for bit in (0..8).rev() {
+                    let index = (((high >> bit) & 1) << 1) | ((low >> bit) & 1);
+                    let shade = (palette >> (index * 2)) & 0b11;
+                    pixels.push(SHADES[shade as usize]);
+                }
+            }
+        }
+    }
+    pixels
+}
+
+fn save_png(path: &Path, pixels: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(directory) = path.parent() {
+        fs::create_dir_all(directory)?;
+    }
+    let height = pixels.len() / PAPER_WIDTH;
+    let mut encoder = png::Encoder::new(
+        BufWriter::new(File::create(path)?),
+        PAPER_WIDTH as u32,
+        height as u32,
+    );
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.write_header()?.write_image_data(pixels)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::BufReader;
+
+    /// Sends the packet and returns the device id and the status answered by the printer.
+    fn send_packet(
+        printer: &mut GameBoyPrinter,
+        command: u8,
+        compression: u8,
+        data: &[u8],
+    ) -> (u8, u8) {
+        let mut bytes = vec![
+            command,
+            compression,
+            data.len() as u8,
+            (data.len() >> 8) as u8,
+        ];
+        bytes.extend_from_slice(data);
+        let checksum = bytes
+            .iter()
+            .fold(0u16, |sum, value| sum.wrapping_add(*value as u16));
+        bytes.extend_from_slice(&checksum.to_le_bytes());
+        for value in [MAGIC_1, MAGIC_2].iter().chain(bytes.iter()) {
+            assert_eq!(printer.exchange(*value), 0);
+        }
+        (printer.exchange(0), printer.exchange(0))
+    }
+
+    #[test]
+    fn compressed_data_is_printed_to_png() {
+        let directory = std::env::temp_dir().join(format!("printer_test_{}", std::process::id()));
+        let mut printer = GameBoyPrinter::new(&directory);
+        assert_eq!(
+            send_packet(&mut printer, COMMAND_INIT, 0, &[]),
+            (DEVICE_ID, 0)
+        );
+
+        // A row of tiles with the color index 3 in runs of 129, 129 and 62 bytes.
+        let mut data = vec![0xFF, 0xFF, 0xFF, 0xFF, 0x80 | 60, 0xFF];
+        // A row of tiles with the color index 1 in copies of 128, 128 and 64 bytes.
+        for size in [128, 128, 64] {
+            data.push(size as u8 - 1);
+            data.extend([0xFF, 0x00].repeat(size / 2));
+        }
+        let (_, status) = send_packet(&mut printer, COMMAND_DATA, 1, &data);
+        assert_eq!(status, STATUS_UNPROCESSED_DATA);
+        assert_eq!(printer.buffer.len(), 2 * TILE_ROW_SIZE);
+
+        // One sheet, no margin before and one after, BGP like palette.
+        let (_, status) = send_packet(&mut printer, COMMAND_PRINT, 0, &[1, 0x01, 0xE4, 0x40]);
+        assert_eq!(status, STATUS_PRINTING);
+        for _ in 0..PRINTING_STATUS_COUNT {
+            send_packet(&mut printer, COMMAND_STATUS, 0, &[]);
+        }
+        assert_eq!(send_packet(&mut printer, COMMAND_STATUS, 0, &[]).1, 0);
+
+        let path = &printer.get_saved_files()[0];
+        let decoder = png::Decoder::new(BufReader::new(File::open(path).unwrap()));
+        let mut reader = decoder.read_info().unwrap();
+        let mut pixels = vec![0; reader.output_buffer_size().unwrap()];
+        let info = reader.next_frame(&mut pixels).unwrap();
+        assert_eq!((info.width, info.height), (160, 24));
+        assert_eq!(pixels[0], SHADES[3]);
+        assert_eq!(pixels[8 * PAPER_WIDTH], SHADES[1]);
+        assert_eq!(pixels[23 * PAPER_WIDTH], SHADES[0]);
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn invalid_checksum_is_reported() {
+        let mut printer = GameBoyPrinter::new(Path::new("."));
+        for value in [MAGIC_1, MAGIC_2, COMMAND_INIT, 0, 0, 0, 0xFF, 0xFF] {
+            assert_eq!(printer.exchange(value), 0);
+        }
+        assert_eq!(printer.exchange(0), DEVICE_ID);
+        assert_eq!(printer.exchange(0), STATUS_CHECKSUM_ERROR);
+    }
+}
@@ -1,27 +1,114 @@
+//! Serial port, connected to the link cable.
+//!
+//! Information from: https://gbdev.io/pandocs/Serial_Data_Transfer_(Link_Cable).html
+
+use crate::interrupts::Interrupt;
 use macros::BitAccessor;
+use std::io::Write;
+use std::sync::{Arc, Mutex};
 
 pub const SERIAL_TRANSFER_START_ADDRESS: u16 = 0xFF01;
 pub const SERIAL_TRANSFER_DATA_ADDRESS: u16 = 0xFF01;
 pub const SERIAL_TRANSFER_CONTROL_ADDRESS: u16 = 0xFF02;
 pub const SERIAL_TRANSFER_END_ADDRESS: u16 = 0xFF02;
 
-
 const CGB_CONTROL_UNUSED_BITS: u8 = 0b0111_1100;
 const DMG_CONTROL_UNUSED_BITS: u8 = 0b0111_1110;
+const TRANSFER_ENABLE_BIT: u8 = 0b1000_0000;
+/// The internal clock runs at 8192 Hz, or 262144 Hz in the CGB fast mode.
+const NORMAL_CYCLES_PER_BIT: u64 = 512;
+const FAST_CYCLES_PER_BIT: u64 = 16;
+const BITS_PER_TRANSFER: u8 = 8;
+
+/// Device plugged on the other end of the link cable.
+pub trait SerialDevice: Send {
+    /// Called when the console starts a transfer with its internal clock.
+    /// Receives the byte sent by the console and returns the byte it receives.
+    fn exchange(&mut self, value: u8) -> u8;
+
+    /// Called while the console waits for a transfer clocked by the device, with the byte that
+    /// the console will send. Returns the byte received by the console once the device clocks the
+    /// transfer.
+    fn poll_external(&mut self, _value: u8) -> Option<u8> {
+        None
+    }
+
+    /// Called when the console stops waiting for a transfer clocked by the device, because the
+    /// transfer was aborted or the console switched to its internal clock.
+    fn cancel_external(&mut self) {}
+}
+
+/// No cable is plugged: the input line stays high so 0xFF is received, and there is no external
+/// clock.
+#[derive(Default)]
+pub struct NoSerialDevice {}
+
+impl SerialDevice for NoSerialDevice {
+    fn exchange(&mut self, _value: u8) -> u8 {
+        0xFF
+    }
+}
+
+/// Collects the bytes sent by the console, the test roms report their results this way.
+/// The clones share the same buffer, so a clone can read what the plugged device captured.
+#[derive(Clone, Default)]
+pub struct SerialCapture {
+    output: Arc<Mutex<Vec<u8>>>,
+    print_to_stdout: bool,
+}
+
+impl SerialCapture {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Also prints the captured bytes to stdout as they are received.
+    pub fn with_stdout(mut self) -> Self {
+        self.print_to_stdout = true;
+        self
+    }
+
+    pub fn get_bytes(&self) -> Vec<u8> {
+        self.output.lock().unwrap().clone()
+    }
+
+    /// Returns the captured bytes as text, invalid UTF-8 sequences are replaced.
+    pub fn get_text(&self) -> String {
+        String::from_utf8_lossy(&self.output.lock().unwrap()).into_owned()
+    }
+}
+
+impl SerialDevice for SerialCapture {
+    fn exchange(&mut self, value: u8) -> u8 {
+        self.output.lock().unwrap().push(value);
+        if self.print_to_stdout {
+            let mut stdout = std::io::stdout();
+            let _ = stdout.write_all(&[value]).and_then(|_| stdout.flush());
+        }
+        0xFF
+    }
+}
 
-#[derive(BitAccessor, Debug)]
+#[derive(BitAccessor)]
 pub struct SerialTransfer {
     // https://gbdev.io/pandocs/Serial_Data_Transfer_(Link_Cable).html#ff01--sb-serial-transfer-data
     data: u8,
 
     // https://gbdev.io/pandocs/Serial_Data_Transfer_(Link_Cable).html#ff02--sc-serial-transfer-control
-    #[bit_offset_size(transfer_enable, 1, 1)]
+    #[bit_offset_size(transfer_enable, 7, 1)]
     #[bit_offset_size(control_unused, 2, 5)]
     #[bit_offset_size(clock_speed, 1, 1)]
     #[bit_offset_size(clock_select, 0, 1)]
     control: u8,
 
     is_cgb: bool,
+    device: Box<dyn SerialDevice>,
+    /// Byte received from the device, shifted in bit by bit.
+    incoming: u8,
+    remaining_bits: u8,
+    cycles: u64,
+    /// The console waits for the device to clock a transfer.
+    is_waiting_external: bool,
 }
 
 impl SerialTransfer {
@@ -30,9 +117,26 @@ impl SerialTransfer {
             data: 0,
             control: 0,
             is_cgb,
+            device: Box::new(NoSerialDevice::default()),
+            incoming: 0,
+            remaining_bits: 0,
+            cycles: 0,
+            is_waiting_external: false,
         }
     }
 
+    /// Replaces the device plugged on the link cable.
+    pub fn set_device(&mut self, device: Box<dyn SerialDevice>) {
+        self.device = device;
+        self.is_waiting_external = false;
+    }
+
+    /// Unplugs the device from the link cable and returns it.
+    pub fn take_device(&mut self) -> Box<dyn SerialDevice> {
+        self.is_waiting_external = false;
+        std::mem::replace(&mut self.device, Box::new(NoSerialDevice::default()))
+    }
+
     pub fn read(&self, address: u16) -> u8 {
         match address {
             SERIAL_TRANSFER_DATA_ADDRESS => self.data,
@@ -51,9 +155,183 @@ impl SerialTransfer {
     pub fn write(&mut self, address: u16, value: u8) {
         match address {
             SERIAL_TRANSFER_DATA_ADDRESS => self.data = value,
-            SERIAL_TRANSFER_CONTROL_ADDRESS => self.control = value,
+            SERIAL_TRANSFER_CONTROL_ADDRESS => {
+                self.control = value;
+                // Clearing the transfer enable bit aborts the current transfer.
+                if self.read_transfer_enable() == 0 {
+                    self.remaining_bits = 0;
+                }
+            }
 
             _ => panic!("This function should never be called with address outside range [0xFF01, 0xFF02], called with {}", address),
         }
     }
+
+    /// Shifts the bits of the current transfer, returns the serial interrupt when it completes.
+    pub fn update(&mut self, nb_cycles: u64) -> Option<Interrupt> {
+        if self.remaining_bits == 0 && !self.start_transfer() {
+            return None;
+        }
+
+        self.cycles += nb_cycles;
+        let cycles_per_bit = self.get_cycles_per_bit();
+        while self.remaining_bits > 0 && self.cycles >= cycles_per_bit {
+            self.cycles -= cycles_per_bit;
+            self.data = (self.data << 1) | (self.incoming >> 7);
+            self.incoming <<= 1;
+            self.remaining_bits -= 1;
+        }
+        if self.remaining_bits > 0 {
+            return None;
+        }
+        self.control &= !TRANSFER_ENABLE_BIT;
+        Some(Interrupt::Serial)
+    }
+
+    /// Exchanges the byte with the device if a transfer was requested, returns true on success.
+    fn start_transfer(&mut self) -> bool {
+        let is_external = self.read_transfer_enable() == 1 && !self.is_internal_clock();
+        if self.is_waiting_external && !is_external {
+            self.is_waiting_external = false;
+            self.device.cancel_external();
+        }
+        if self.read_transfer_enable() == 0 {
+            return false;
+        }
+        let incoming = if is_external {
+            let incoming = self.device.poll_external(self.data);
+            self.is_waiting_external = incoming.is_none();
+            match incoming {
+                Some(incoming) => incoming,
+                None => return false,
+            }
+        } else {
+            self.device.exchange(self.data)
+        };
+        self.incoming = incoming;
+        self.remaining_bits = BITS_PER_TRANSFER;
+        self.cycles = 0;
+        true
+    }
+
+    fn is_internal_clock(&self) -> bool {
+        self.read_clock_select() == 1
+    }
+
+    /// The external clock is assumed to run at the normal speed.
+    fn get_cycles_per_bit(&self) -> u64 {
+        if self.is_cgb && self.is_internal_clock() && self.read_clock_speed() == 1 {
+            FAST_CYCLES_PER_BIT
+        } else {
+            NORMAL_CYCLES_PER_BIT
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct EchoDevice {
+        external: Option<u8>,
+        nb_cancels: Arc<Mutex<u32>>,
+    }
+
+    impl EchoDevice {
+        fn new(external: Option<u8>) -> Self {
+            Self {
+                external,
+                nb_cancels: Default::default(),
+            }
+        }
+    }
+
+    impl SerialDevice for EchoDevice {
+        fn exchange(&mut self, value: u8) -> u8 {
+            !value
+        }
+
+        fn poll_external(&mut self, _value: u8) -> Option<u8> {
+            self.external.take()
+        }
+
+        fn cancel_external(&mut self) {
+            *self.nb_cancels.lock().unwrap() += 1;
+        }
+    }
+
+    #[test]
+    fn internal_clock_without_cable_receives_0xff() {
+        let mut serial = SerialTransfer::new(false);
+        serial.write(SERIAL_TRANSFER_DATA_ADDRESS, 0x42);
+        serial.write(SERIAL_TRANSFER_CONTROL_ADDRESS, 0x81);
+
+        assert_eq!(serial.update(4 * NORMAL_CYCLES_PER_BIT), None);
+        assert_eq!(serial.read(SERIAL_TRANSFER_DATA_ADDRESS), 0x2F);
+        assert_eq!(
+            serial.update(4 * NORMAL_CYCLES_PER_BIT),
+            Some(Interrupt::Serial)
+        );
+        assert_eq!(serial.read(SERIAL_TRANSFER_DATA_ADDRESS), 0xFF);
+        assert_eq!(serial.read(SERIAL_TRANSFER_CONTROL_ADDRESS), 0x7F);
+        assert_eq!(serial.update(8 * NORMAL_CYCLES_PER_BIT), None);
+    }
+
+    #[test]
+    fn capture_collects_sent_bytes() {
+        let capture = SerialCapture::new();
+        let mut serial = SerialTransfer::new(false);
+        serial.set_device(Box::new(capture.clone()));
+        for value in b"Passed" {
+            serial.write(SERIAL_TRANSFER_DATA_ADDRESS, *value);
+            serial.write(SERIAL_TRANSFER_CONTROL_ADDRESS, 0x81);
+            serial.update(8 * NORMAL_CYCLES_PER_BIT);
+        }
+        assert_eq!(capture.get_text(), "Passed");
+    }
+
+    #[test]
+    fn cgb_fast_clock_exchanges_with_device() {
+        let mut serial = SerialTransfer::new(true);
+        serial.set_device(Box::new(EchoDevice::new(None)));
+        serial.write(SERIAL_TRANSFER_DATA_ADDRESS, 0x0F);
+        serial.write(SERIAL_TRANSFER_CONTROL_ADDRESS, 0x83);
+
+        assert_eq!(
+            serial.update(8 * FAST_CYCLES_PER_BIT),
+            Some(Interrupt::Serial)
+        );
+        assert_eq!(serial.read(SERIAL_TRANSFER_DATA_ADDRESS), 0xF0);
+    }
+
+    #[test]
+    fn external_clock_waits_for_device() {
+        let mut serial = SerialTransfer::new(false);
+        serial.write(SERIAL_TRANSFER_DATA_ADDRESS, 0x12);
+        serial.write(SERIAL_TRANSFER_CONTROL_ADDRESS, 0x80);
+        assert_eq!(serial.update(16 * NORMAL_CYCLES_PER_BIT), None);
+
+        serial.set_device(Box::new(EchoDevice::new(Some(0x34))));
+        assert_eq!(
+            serial.update(8 * NORMAL_CYCLES_PER_BIT),
+            Some(Interrupt::Serial)
+        );
+        assert_eq!(serial.read(SERIAL_TRANSFER_DATA_ADDRESS), 0x34);
+    }
+
+    #[test]
+    fn aborting_external_transfer_cancels_the_wait() {
+        let device = EchoDevice::new(None);
+        let nb_cancels = device.nb_cancels.clone();
+        let mut serial = SerialTransfer::new(false);
+        serial.set_device(Box::new(device));
+        serial.write(SERIAL_TRANSFER_CONTROL_ADDRESS, 0x80);
+        serial.update(NORMAL_CYCLES_PER_BIT);
+        assert_eq!(*nb_cancels.lock().unwrap(), 0);
+
+        serial.write(SERIAL_TRANSFER_CONTROL_ADDRESS, 0x00);
+        serial.update(NORMAL_CYCLES_PER_BIT);
+        serial.update(NORMAL_CYCLES_PER_BIT);
+        assert_eq!(*nb_cancels.lock().unwrap(), 1);
+    }
 }
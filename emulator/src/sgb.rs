@@ -0,0 +1,700 @@
+//! Super Game Boy: command packets sent through the joypad register, palettes and borders.
+//!
+//! The game area is colorized with 4 palettes selected by an attribute map of 20x18 tiles, and is
+//! drawn in the middle of a 256x224 frame surrounded by the border.
+//! The default border of the SGB BIOS is not included: until the game sends its own border, the
+//! area around the game is filled with the color 0.
+//!
+//! Information from: https://gbdev.io/pandocs/SGB_Functions.html
+
+use crate::video::controller::VideoController;
+use crate::video::framebuffer::FrameBuffer;
+use crate::video::renderer::{Color, BLACK, DMG_COLORS, SCREEN_HEIGHT, SCREEN_WIDTH, WHITE};
+use crate::video::tile::{get_tile_address, get_vram_tile_offset_from_area};
+use log::debug;
+
+pub const SGB_SCREEN_WIDTH: usize = 256;
+pub const SGB_SCREEN_HEIGHT: usize = 224;
+/// Position of the game area in the frame.
+const GAME_X: usize = 48;
+const GAME_Y: usize = 40;
+const GAME_WIDTH: usize = SCREEN_WIDTH as usize;
+const GAME_HEIGHT: usize = SCREEN_HEIGHT as usize;
+
+/// Information from: https://gbdev.io/pandocs/SGB_Functions.html#command-packet-transfers
+const PACKET_SIZE: usize = 16;
+const PACKET_BITS: usize = PACKET_SIZE * 8;
+const P14_P15_MASK: u8 = 0b0011_0000;
+const RESET_PULSE: u8 = 0b0000_0000;
+const ZERO_PULSE: u8 = 0b0010_0000;
+const ONE_PULSE: u8 = 0b0001_0000;
+const P15_BIT: u8 = 0b0010_0000;
+const LINES_HIGH: u8 = 0b0011_0000;
+
+const PAL01: u8 = 0x00;
+const PAL23: u8 = 0x01;
+const PAL03: u8 = 0x02;
+const PAL12: u8 = 0x03;
+const ATTR_BLK: u8 = 0x04;
+const ATTR_LIN: u8 = 0x05;
+const ATTR_DIV: u8 = 0x06;
+const ATTR_CHR: u8 = 0x07;
+const PAL_SET: u8 = 0x0A;
+const PAL_TRN: u8 = 0x0B;
+const MLT_REQ: u8 = 0x11;
+const CHR_TRN: u8 = 0x13;
+const PCT_TRN: u8 = 0x14;
+const ATTR_TRN: u8 = 0x15;
+const ATTR_SET: u8 = 0x16;
+const MASK_EN: u8 = 0x17;
+
+/// The attribute map has a palette number for each tile of the game area.
+const ATTRIBUTE_MAP_WIDTH: usize = 20;
+const ATTRIBUTE_MAP_HEIGHT: usize = 18;
+const NB_PALETTES: usize = 4;
+const NB_SYSTEM_PALETTES: usize = 512;
+const NB_ATTRIBUTE_FILES: usize = 45;
+const ATTRIBUTE_FILE_SIZE: usize = ATTRIBUTE_MAP_WIDTH * ATTRIBUTE_MAP_HEIGHT / 4;
+
+/// The VRAM transfers copy the 4KB displayed by the first 256 tiles of the background.
+/// Information from: https://gbdev.io/pandocs/SGB_Functions.html#vram-transfers
+const TRANSFER_SIZE: usize = 0x1000;
+const TILE_SIZE: usize = 16;
+const NB_TRANSFER_TILES: usize = TRANSFER_SIZE / TILE_SIZE;
+const TILE_MAP_WIDTH: usize = 32;
+
+/// Information from: https://gbdev.io/pandocs/SGB_Command_Border.html
+const BORDER_TILE_SIZE: usize = 32;
+const NB_BORDER_TILES: usize = 256;
+const BORDER_MAP_WIDTH: usize = 32;
+const BORDER_MAP_SIZE: usize = 0x800;
+const NB_BORDER_PALETTES: usize = 4;
+const BORDER_PALETTE_SIZE: usize = 16;
+/// The border uses the palettes 4 to 7.
+const FIRST_BORDER_PALETTE: usize = 4;
+
+/// Information from: https://gbdev.io/pandocs/SGB_Command_Mask.html
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Mask {
+    None,
+    /// Keeps the last picture on screen.
+    Freeze,
+    Black,
+    Color0,
+}
+
+/// VRAM transfer requested by a command, done when the next frame is displayed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Transfer {
+    SystemPalettes,
+    /// Border tiles, the first 128 or the last 128 tiles.
+    BorderTiles(usize),
+    BorderMap,
+    AttributeFiles,
+}
+
+/// Decodes the packets sent with pulses on the P14 and P15 lines.
+///
+/// A packet starts with a reset pulse (both lines low), followed by 128 bits sent one at a time
+/// (P14 low for 0, P15 low for 1) with both lines high in between, and a 0 stop bit.
+struct PacketReader {
+    is_receiving: bool,
+    nb_bits: usize,
+    packet: [u8; PACKET_SIZE],
+    lines: u8,
+}
+
+impl PacketReader {
+    fn new() -> Self {
+        Self {
+            is_receiving: false,
+            nb_bits: 0,
+            packet: [0; PACKET_SIZE],
+            lines: LINES_HIGH,
+        }
+    }
+
+    /// Returns the packet when the stop bit is written.
+    fn write(&mut self, value: u8) -> Option<[u8; PACKET_SIZE]> {
+        let lines = value & P14_P15_MASK;
+        let previous_lines = std::mem::replace(&mut self.lines, lines);
+        match lines {
+            RESET_PULSE => {
+                self.is_receiving = true;
+                self.nb_bits = 0;
+                self.packet = [0; PACKET_SIZE];
+                None
+            }
+            LINES_HIGH => None,
+            _ if !self.is_receiving || previous_lines != LINES_HIGH => None,
+            _ if self.nb_bits == PACKET_BITS => {
+                self.is_receiving = false;
+                (lines == ZERO_PULSE).then_some(self.packet)
+            }
+            _ => {
+                if lines == ONE_PULSE {
+                    self.packet[self.nb_bits / 8] |= 1 << (self.nb_bits % 8);
+                }
+                self.nb_bits += 1;
+                None
+            }
+        }
+    }
+}
+
+pub struct SuperGameBoy {
+    reader: PacketReader,
+    /// Packets of the command being received, the first packet gives the number of packets.
+    command: Vec<u8>,
+    palettes: [[Color; 4]; NB_PALETTES],
+    system_palettes: Vec<[Color; 4]>,
+    attributes: [[u8; ATTRIBUTE_MAP_WIDTH]; ATTRIBUTE_MAP_HEIGHT],
+    attribute_files: Vec<[u8; ATTRIBUTE_FILE_SIZE]>,
+    mask: Mask,
+    pending_transfer: Option<Transfer>,
+    border_tiles: Vec<u8>,
+    border_map: Vec<u8>,
+    border_palettes: [[Color; BORDER_PALETTE_SIZE]; NB_BORDER_PALETTES],
+    /// Shade of each pixel of the game area.
+    game_screen: Vec<u8>,
+    frame: FrameBuffer,
+    nb_players: u8,
+    current_player: u8,
+    joypad_lines: u8,
+}
+
+impl SuperGameBoy {
+    pub fn new() -> Self {
+        Self {
+            reader: PacketReader::new(),
+            command: vec![],
+            palettes: [DMG_COLORS; NB_PALETTES],
+            system_palettes: vec![DMG_COLORS; NB_SYSTEM_PALETTES],
+            attributes: [[0; ATTRIBUTE_MAP_WIDTH]; ATTRIBUTE_MAP_HEIGHT],
+            attribute_files: vec![[0; ATTRIBUTE_FILE_SIZE]; NB_ATTRIBUTE_FILES],
+            mask: Mask::None,
+            pending_transfer: None,
+            border_tiles: vec![0; NB_BORDER_TILES * BORDER_TILE_SIZE],
+            border_map: vec![0; BORDER_MAP_SIZE],
+            border_palettes: [[WHITE; BORDER_PALETTE_SIZE]; NB_BORDER_PALETTES],
+            game_screen: vec![0; GAME_WIDTH * GAME_HEIGHT],
+            frame: FrameBuffer::new(SGB_SCREEN_WIDTH, SGB_SCREEN_HEIGHT),
+            nb_players: 1,
+            current_player: 0,
+            joypad_lines: LINES_HIGH,
+        }
+    }
+
+    /// Receives the value written in the joypad register.
+    pub fn write_joypad(&mut self, value: u8) {
+        // Information from: https://gbdev.io/pandocs/SGB_Command_Multiplayer.html
+        // The next joypad is selected when P15 goes back high.
+        let lines = value & P14_P15_MASK;
+        if self.joypad_lines & P15_BIT == 0 && lines & P15_BIT != 0 {
+            self.current_player = (self.current_player + 1) % self.nb_players;
+        }
+        self.joypad_lines = lines;
+
+        if let Some(packet) = self.reader.write(value) {
+            self.receive_packet(packet);
+        }
+    }
+
+    /// Returns the value read in the joypad register, given the value of the first joypad.
+    ///
+    /// With several joypads, the id of the current joypad is read when both lines are high, and
+    /// the other joypads have no button pressed.
+    pub fn read_joypad(&self, value: u8) -> u8 {
+        if self.nb_players > 1 && value & P14_P15_MASK == LINES_HIGH {
+            (value & 0xF0) | (0x0F - self.current_player)
+        } else if self.current_player > 0 {
+            value | 0x0F
+        } else {
+            value
+        }
+    }
+
+    /// Called once per frame with the frame of the renderer: does the VRAM transfer requested by
+    /// the last command and draws the 256x224 frame.
+    pub fn update_frame(&mut self, video: &VideoController, game_frame: &FrameBuffer) {
+        if let Some(transfer) = self.pending_transfer.take() {
+            let data = read_transfer_data(video);
+            self.transfer(transfer, &data);
+        }
+        if self.mask != Mask::Freeze {
+            self.game_screen.copy_from_slice(game_frame.get_indices());
+        }
+        self.render();
+    }
+
+    /// Returns the last frame drawn: the border and the colorized game area.
+    pub fn get_frame(&self) -> &FrameBuffer {
+        &self.frame
+    }
+
+    /// Draws each pixel of the 256x224 frame with the index of its color in the palette used.
+    fn render(&mut self) {
+        for y in 0..SGB_SCREEN_HEIGHT {
+            for x in 0..SGB_SCREEN_WIDTH {
+                let is_game_area = (GAME_X..GAME_X + GAME_WIDTH).contains(&x)
+                    && (GAME_Y..GAME_Y + GAME_HEIGHT).contains(&y);
+                let (index, color) = if is_game_area {
+                    self.get_game_color(x - GAME_X, y - GAME_Y)
+                } else {
+                    self.get_border_color(x, y)
+                };
+                self.frame.set_pixel(x, y, index, &color);
+            }
+        }
+    }
+
+    /// The masked game area uses the index 0.
+    fn get_game_color(&self, x: usize, y: usize) -> (u8, Color) {
+        match self.mask {
+            Mask::Black => (0, BLACK),
+            Mask::Color0 => (0, self.palettes[0][0]),
+            Mask::None | Mask::Freeze => {
+                let palette = self.attributes[y / 8][x / 8] as usize;
+                let shade = self.game_screen[y * GAME_WIDTH + x];
+                (shade, self.palettes[palette][shade as usize])
+            }
+        }
+    }
+
+    fn get_border_color(&self, x: usize, y: usize) -> (u8, Color) {
+        let entry_address = ((y / 8) * BORDER_MAP_WIDTH + x / 8) * 2;
+        let entry = u16::from_le_bytes([
+            self.border_map[entry_address],
+            self.border_map[entry_address + 1],
+        ]);
+        let tile = (entry & 0xFF) as usize;
+        let palette = ((entry >> 10) & 0b111) as usize;
+        let tile_x = if entry & 0x4000 != 0 {
+            7 - x % 8
+        } else {
+            x % 8
+        };
+        let tile_y = if entry & 0x8000 != 0 {
+            7 - y % 8
+        } else {
+            y % 8
+        };
+
+        // The tiles use the 4 bits per pixel SNES format: planes 0 and 1, then planes 2 and 3.
+        let tile_data = &self.border_tiles[tile * BORDER_TILE_SIZE..(tile + 1) * BORDER_TILE_SIZE];
+        let index = [0, 1, 16, 17]
+            .iter()
+            .enumerate()
+            .fold(0, |index, (plane, offset)| {
+                let bit = (tile_data[offset + tile_y * 2] >> (7 - tile_x)) & 1;
+                index | (bit << plane)
+            });
+        if index == 0 {
+            (0, self.palettes[0][0])
+        } else {
+            let palette = palette.saturating_sub(FIRST_BORDER_PALETTE) % NB_BORDER_PALETTES;
+            (index, self.border_palettes[palette][index as usize])
+        }
+    }
+
+    fn receive_packet(&mut self, packet: [u8; PACKET_SIZE]) {
+        self.command.extend_from_slice(&packet);
+        let nb_packets = (self.command[0] & 0b111).max(1) as usize;
+        if self.command.len() >= nb_packets * PACKET_SIZE {
+            let command = std::mem::take(&mut self.command);
+            self.handle_command(&command);
+        }
+    }
+
+    fn handle_command(&mut self, data: &[u8]) {
+        let command = data[0] >> 3;
+        debug!("SGB command 0x{:02X}", command);
+        match command {
+            PAL01 => self.set_palettes(data, 0, 1),
+            PAL23 => self.set_palettes(data, 2, 3),
+            PAL03 => self.set_palettes(data, 0, 3),
+            PAL12 => self.set_palettes(data, 1, 2),
+            ATTR_BLK => self.set_attribute_blocks(data),
+            ATTR_LIN => self.set_attribute_lines(data),
+            ATTR_DIV => self.set_attribute_division(data),
+            ATTR_CHR => self.set_attribute_characters(data),
+            PAL_SET => self.set_system_palettes(data),
+            PAL_TRN => self.pending_transfer = Some(Transfer::SystemPalettes),
+            MLT_REQ => {
+                self.nb_players = match data[1] & 0b11 {
+                    1 => 2,
+                    3 => 4,
+                    _ => 1,
+                };
+                self.current_player = 0;
+            }
+            CHR_TRN => {
+                self.pending_transfer = Some(Transfer::BorderTiles((data[1] & 1) as usize * 128))
+            }
+            PCT_TRN => self.pending_transfer = Some(Transfer::BorderMap),
+            ATTR_TRN => self.pending_transfer = Some(Transfer::AttributeFiles),
+            ATTR_SET => self.set_attribute_file(data[1]),
+            MASK_EN => {
+                self.mask = match data[1] & 0b11 {
+                    1 => Mask::Freeze,
+                    2 => Mask::Black,
+                    3 => Mask::Color0,
+                    _ => Mask::None,
+                }
+            }
+            _ => debug!("Unsupported SGB command 0x{:02X}", command),
+        }
+    }
+
+    /// Information from: https://gbdev.io/pandocs/SGB_Command_Palettes.html#sgb-command-00--pal01
+    fn set_palettes(&mut self, data: &[u8], first: usize, second: usize) {
+        // The color 0 is shared by all the palettes.
+        let color_0 = get_sgb_color(data[1], data[2]);
+        for palette in self.palettes.iter_mut() {
+            palette[0] = color_0;
+        }
+        for (palette, offset) in [(first, 3), (second, 9)] {
+            for color in 1..4 {
+                let address = offset + (color - 1) * 2;
+                self.palettes[palette][color] = get_sgb_color(data[address], data[address + 1]);
+            }
+        }
+    }
+
+    /// Information from: https://gbdev.io/pandocs/SGB_Command_Attribute.html#sgb-command-04--attr_blk
+    fn set_attribute_blocks(&mut self, data: &[u8]) {
+        let nb_blocks = data[1] as usize;
+        for block in data[2..].chunks_exact(6).take(nb_blocks) {
+            let control = block[0];
+            let inside = block[1] & 0b11;
+            let surrounding = (block[1] >> 2) & 0b11;
+            let outside = (block[1] >> 4) & 0b11;
+            let (x1, y1, x2, y2) = (
+                block[2] as usize,
+                block[3] as usize,
+                block[4] as usize,
+                block[5] as usize,
+            );
+            let change_inside = control & 0b001 != 0;
+            let change_outside = control & 0b100 != 0;
+            // When only the inside or the outside is changed, the surrounding line changes too.
+            let surrounding = match control & 0b111 {
+                0b001 => Some(inside),
+                0b100 => Some(outside),
+                control if control & 0b010 != 0 => Some(surrounding),
+                _ => None,
+            };
+            self.update_attributes(|x, y| {
+                let is_in_block = (x1..=x2).contains(&x) && (y1..=y2).contains(&y);
+                let is_inside = x1 < x && x < x2 && y1 < y && y < y2;
+                if is_inside {
+                    change_inside.then_some(inside)
+                } else if is_in_block {
+                    surrounding
+                } else {
+                    change_outside.then_some(outside)
+                }
+            });
+        }
+    }
+
+    /// Information from: https://gbdev.io/pandocs/SGB_Command_Attribute.html#sgb-command-05--attr_lin
+    fn set_attribute_lines(&mut self, data: &[u8]) {
+        let nb_lines = data[1] as usize;
+        for line in data[2..].iter().take(nb_lines) {
+            let number = (line & 0b1_1111) as usize;
+            let palette = (line >> 5) & 0b11;
+            let is_horizontal = line & 0x80 != 0;
+            self.update_attributes(|x, y| {
+                let is_on_line = if is_horizontal {
+                    y == number
+                } else {
+                    x == number
+                };
+                is_on_line.then_some(palette)
+            });
+        }
+    }
+
+    /// Information from: https://gbdev.io/pandocs/SGB_Command_Attribute.html#sgb-command-06--attr_div
+    fn set_attribute_division(&mut self, data: &[u8]) {
+        let after = data[1] & 0b11;
+        let before = (data[1] >> 2) & 0b11;
+        let on_line = (data[1] >> 4) & 0b11;
+        let is_horizontal = data[1] & 0x40 != 0;
+        let line = data[2] as usize;
+        self.update_attributes(|x, y| {
+            let position = if is_horizontal { y } else { x };
+            Some(match position.cmp(&line) {
+                std::cmp::Ordering::Less => before,
+                std::cmp::Ordering::Equal => on_line,
+                std::cmp::Ordering::Greater => after,
+            })
+        });
+    }
+
+    /// Information from: https://gbdev.io/pandocs/SGB_Command_Attribute.html#sgb-command-07--attr_chr
+    fn set_attribute_characters(&mut self, data: &[u8]) {
+        let (mut x, mut y) = (data[1] as usize, data[2] as usize);
+        let nb_tiles = u16::from_le_bytes([data[3], data[4]]) as usize;
+        let is_vertical = data[5] & 1 != 0;
+        let palettes = data[6..]
+            .iter()
+            .flat_map(|value| [6, 4, 2, 0].map(|shift| (value >> shift) & 0b11));
+        for palette in palettes.take(nb_tiles) {
+            if x >= ATTRIBUTE_MAP_WIDTH || y >= ATTRIBUTE_MAP_HEIGHT {
+                break;
+            }
+            self.attributes[y][x] = palette;
+            if is_vertical {
+                y += 1;
+                if y == ATTRIBUTE_MAP_HEIGHT {
+                    y = 0;
+                    x += 1;
+                }
+            } else {
+                x += 1;
+                if x == ATTRIBUTE_MAP_WIDTH {
+                    x = 0;
+                    y += 1;
+                }
+            }
+        }
+    }
+
+    /// Information from: https://gbdev.io/pandocs/SGB_Command_Palettes.html#sgb-command-0a--pal_set
+    fn set_system_palettes(&mut self, data: &[u8]) {
+        for palette in 0..NB_PALETTES {
+            let number = u16::from_le_bytes([data[1 + palette * 2], data[2 + palette * 2]]);
+            self.palettes[palette] = self.system_palettes[number as usize % NB_SYSTEM_PALETTES];
+        }
+        if data[9] & 0x80 != 0 {
+            self.set_attribute_file(data[9]);
+        }
+    }
+
+    /// Information from: https://gbdev.io/pandocs/SGB_Command_Attribute.html#sgb-command-16--attr_set
+    fn set_attribute_file(&mut self, value: u8) {
+        let file = self.attribute_files[(value & 0b11_1111) as usize % NB_ATTRIBUTE_FILES];
+        for (index, palette) in file
+            .iter()
+            .flat_map(|value| [6, 4, 2, 0].map(|shift| (value >> shift) & 0b11))
+            .enumerate()
+        {
+            self.attributes[index / ATTRIBUTE_MAP_WIDTH][index % ATTRIBUTE_MAP_WIDTH] = palette;
+        }
+        if value & 0x40 != 0 {
+            self.mask = Mask::None;
+        }
+    }
+
+    /// Sets the palette of the tiles for which the function returns one.
+    fn update_attributes<F>(&mut self, get_palette: F)
+    where
+        F: Fn(usize, usize) -> Option<u8>,
+    {
+        for (y, line) in self.attributes.iter_mut().enumerate() {
+            for (x, attribute) in line.iter_mut().enumerate() {
+                if let Some(palette) = get_palette(x, y) {
+                    *attribute = palette;
+                }
+            }
+        }
+    }
+
+    fn transfer(&mut self, transfer: Transfer, data: &[u8]) {
+        match transfer {
+            Transfer::SystemPalettes => {
+                for (palette, colors) in self.system_palettes.iter_mut().zip(data.chunks_exact(8)) {
+                    for (color, bytes) in palette.iter_mut().zip(colors.chunks_exact(2)) {
+                        *color = get_sgb_color(bytes[0], bytes[1]);
+                    }
+                }
+            }
+            Transfer::BorderTiles(first_tile) => {
+                let start = first_tile * BORDER_TILE_SIZE;
+                self.border_tiles[start..start + TRANSFER_SIZE].copy_from_slice(data);
+            }
+            Transfer::BorderMap => {
+                self.border_map.copy_from_slice(&data[..BORDER_MAP_SIZE]);
+                let palettes = &data[BORDER_MAP_SIZE..];
+                for (palette, colors) in self
+                    .border_palettes
+                    .iter_mut()
+                    .zip(palettes.chunks_exact(BORDER_PALETTE_SIZE * 2))
+                {
+                    for (color, bytes) in palette.iter_mut().zip(colors.chunks_exact(2)) {
+                        *color = get_sgb_color(bytes[0], bytes[1]);
+                    }
+                }
+            }
+            Transfer::AttributeFiles => {
+                for (file, bytes) in self
+                    .attribute_files
+                    .iter_mut()
+                    .zip(data.chunks_exact(ATTRIBUTE_FILE_SIZE))
+                {
+                    file.copy_from_slice(bytes);
+                }
+            }
+        }
+    }
+}
+
+impl Default for SuperGameBoy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Reads the tiles displayed by the background, from left to right and top to bottom.
+fn read_transfer_data(video: &VideoController) -> Vec<u8> {
+    let control = video.get_control();
+    let tile_map_offset = get_vram_tile_offset_from_area(control.read_bg_tile_map_area());
+    let vram = video.get_vram();
+    (0..NB_TRANSFER_TILES)
+        .flat_map(|tile| {
+            let map_index =
+                (tile / ATTRIBUTE_MAP_WIDTH) * TILE_MAP_WIDTH + tile % ATTRIBUTE_MAP_WIDTH;
+            let tile_index = vram[tile_map_offset + map_index] as usize;
+            let address = get_tile_address(tile_index, control.read_bg_window_tile_data_area());
+            vram[address..address + TILE_SIZE].iter().copied()
+        })
+        .collect()
+}
+
+/// Converts a little endian RGB555 color.
+fn get_sgb_color(low: u8, high: u8) -> Color {
+    let value = u16::from_le_bytes([low, high]);
+    let convert = |component: u16| {
+        let component = (component & 0b1_1111) as u8;
+        (component << 3) | (component >> 2)
+    };
+    Color {
+        alpha: 255,
+        red: convert(value),
+        green: convert(value >> 5),
+        blue: convert(value >> 10),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: Color = Color {
+        alpha: 255,
+        red: 0xFF,
+        green: 0,
+        blue: 0,
+    };
+
+    /// Sends the packet with the same pulses as a game.
+    fn send_packet(sgb: &mut SuperGameBoy, packet: [u8; PACKET_SIZE]) {
+        sgb.write_joypad(RESET_PULSE);
+        sgb.write_joypad(LINES_HIGH);
+        let bits = (0..PACKET_BITS).map(|bit| (packet[bit / 8] >> (bit % 8)) & 1 != 0);
+        for bit in bits.chain([false]) {
+            sgb.write_joypad(if bit { ONE_PULSE } else { ZERO_PULSE });
+            sgb.write_joypad(LINES_HIGH);
+        }
+    }
+
+    fn create_packet(command: u8, data: &[u8]) -> [u8; PACKET_SIZE] {
+        let mut packet = [0; PACKET_SIZE];
+        packet[0] = (command << 3) | 1;
+        packet[1..1 + data.len()].copy_from_slice(data);
+        packet
+    }
+
+    fn update_frame(sgb: &mut SuperGameBoy, game_frame: &FrameBuffer) {
+        sgb.update_frame(&VideoController::new(), game_frame);
+    }
+
+    #[test]
+    fn palettes_and_attributes_colorize_the_game_area() {
+        let mut sgb = SuperGameBoy::new();
+        // Color 0 is white, color 3 of the palette 1 is red.
+        send_packet(
+            &mut sgb,
+            create_packet(
+                PAL01,
+                &[0xFF, 0x7F, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x1F, 0x00],
+            ),
+        );
+        // The right half of the game area uses the palette 1.
+        send_packet(&mut sgb, create_packet(ATTR_DIV, &[0b0000_0001, 10]));
+        let mut game_frame = FrameBuffer::new(GAME_WIDTH, GAME_HEIGHT);
+        for (x, y) in [(8, 8), (100, 8)] {
+            game_frame.set_pixel(x, y, 3, &BLACK);
+        }
+        update_frame(&mut sgb, &game_frame);
+
+        let get_pixel = |sgb: &SuperGameBoy, x: usize, y: usize| {
+            sgb.get_frame().get_color(x + GAME_X, y + GAME_Y)
+        };
+        assert_eq!(get_pixel(&sgb, 8, 8), BLACK);
+        assert_eq!(get_pixel(&sgb, 100, 8), RED);
+        assert_eq!(get_pixel(&sgb, 0, 0), WHITE);
+        assert_eq!(sgb.get_frame().get_index(100 + GAME_X, 8 + GAME_Y), 3);
+        assert_eq!(sgb.attributes[0][9], 0);
+        assert_eq!(sgb.attributes[0][10], 0);
+        assert_eq!(sgb.attributes[0][11], 1);
+
+        send_packet(&mut sgb, create_packet(MASK_EN, &[2]));
+        update_frame(&mut sgb, &game_frame);
+        assert_eq!(get_pixel(&sgb, 0, 0), BLACK);
+    }
+
+    #[test]
+    fn attribute_block_changes_inside_and_surrounding_tiles() {
+        let mut sgb = SuperGameBoy::new();
+        send_packet(
+            &mut sgb,
+            create_packet(ATTR_BLK, &[1, 0b001, 0b10_01_11, 2, 2, 4, 4]),
+        );
+
+        assert_eq!(sgb.attributes[3][3], 3);
+        assert_eq!(sgb.attributes[2][2], 3);
+        assert_eq!(sgb.attributes[4][3], 3);
+        assert_eq!(sgb.attributes[5][5], 0);
+    }
+
+    #[test]
+    fn border_is_drawn_around_the_game_area() {
+        let mut sgb = SuperGameBoy::new();
+        let mut tiles = vec![0; TRANSFER_SIZE];
+        // The tile 1 uses the color 1 for all its pixels.
+        tiles[BORDER_TILE_SIZE..BORDER_TILE_SIZE + 16]
+            .iter_mut()
+            .step_by(2)
+            .for_each(|value| *value = 0xFF);
+        sgb.transfer(Transfer::BorderTiles(0), &tiles);
+        let mut map = vec![0; TRANSFER_SIZE];
+        // The first tile of the map uses the tile 1 and the palette 4.
+        map[0..2].copy_from_slice(&(1u16 | (4 << 10)).to_le_bytes());
+        map[BORDER_MAP_SIZE + 2..BORDER_MAP_SIZE + 4].copy_from_slice(&[0x1F, 0x00]);
+        sgb.transfer(Transfer::BorderMap, &map);
+
+        update_frame(&mut sgb, &FrameBuffer::new(GAME_WIDTH, GAME_HEIGHT));
+        assert_eq!(sgb.get_frame().get_color(0, 0), RED);
+        assert_eq!(sgb.get_frame().get_index(0, 0), 1);
+        assert_eq!(sgb.get_frame().get_color(8, 0), WHITE);
+    }
+
+    #[test]
+    fn multiplayer_request_cycles_through_the_joypads() {
+        let mut sgb = SuperGameBoy::new();
+        assert_eq!(sgb.read_joypad(0xFF), 0xFF);
+        send_packet(&mut sgb, create_packet(MLT_REQ, &[1]));
+
+        assert_eq!(sgb.read_joypad(0xFF), 0xFF);
+        sgb.write_joypad(ONE_PULSE);
+        sgb.write_joypad(LINES_HIGH);
+        assert_eq!(sgb.read_joypad(0xFF), 0xFE);
+        assert_eq!(sgb.read_joypad(0xE0), 0xEF);
+        sgb.write_joypad(ONE_PULSE);
+        sgb.write_joypad(LINES_HIGH);
+        assert_eq!(sgb.read_joypad(0xFF), 0xFF);
+    }
+}
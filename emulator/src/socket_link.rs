@@ -0,0 +1,417 @@
+//! Link cable between two emulator processes, over a TCP connection.
+//!
+//! Each message is three bytes: the message kind, a value and a sequence number. The console
+//! waiting for an external clock announces the byte it will send with a ready message, and
+//! withdraws it with a not ready message when it stops waiting. The console clocking the transfer
+//! answers with a transfer message carrying its own byte and the sequence number of the ready
+//! message it used, so a transfer answering a withdrawn byte is dropped.
+//!
+//! To compensate the latency, the console clocking a transfer waits for a ready message during
+//! twice the round trip time of the connection, within bounds. The round trip time is measured
+//! with ping messages. If no byte is announced in time, the input line stays high like with an
+//! unplugged cable.
+
+use crate::serial::SerialDevice;
+use log::{error, info};
+use std::io;
+use std::io::{Read, Write};
+use std::net::{Shutdown, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const MESSAGE_READY: u8 = 1;
+const MESSAGE_TRANSFER: u8 = 2;
+const MESSAGE_NOT_READY: u8 = 3;
+const MESSAGE_PING: u8 = 4;
+const MESSAGE_PONG: u8 = 5;
+const MESSAGE_SIZE: usize = 3;
+/// Bounds of the wait for a ready message, the maximum is also used until the round trip time
+/// is measured.
+const MIN_READY_WAIT: Duration = Duration::from_millis(1);
+const MAX_READY_WAIT: Duration = Duration::from_millis(20);
+/// The round trip time is measured again after this interval.
+const PING_INTERVAL: Duration = Duration::from_secs(1);
+/// Interval between two checks for an incoming connection while listening.
+const ACCEPT_POLL_INTERVAL: Duration = Duration::from_millis(50);
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How to open the link cable.
+#[derive(Clone, Debug, PartialEq)]
+pub enum LinkMode {
+    /// Waits for the other emulator to connect on the address.
+    Listen(String),
+    /// Connects to the other emulator listening on the address.
+    Connect(String),
+}
+
+impl LinkMode {
+    /// Opens the connection, blocks until the other emulator is connected or `is_cancelled` is set.
+    pub fn open(&self, is_cancelled: &AtomicBool) -> io::Result<SocketLinkCable> {
+        match self {
+            Self::Listen(address) => SocketLinkCable::listen(address, is_cancelled),
+            Self::Connect(address) => SocketLinkCable::connect(address),
+        }
+    }
+}
+
+#[derive(Default)]
+struct RemoteState {
+    /// Byte announced by the other side with its sequence number, waiting for a transfer clocked
+    /// by this side.
+    ready: Option<(u8, u8)>,
+    /// Byte sent by the other side in a transfer that it clocked, with the sequence number of the
+    /// ready message it answers.
+    received: Option<(u8, u8)>,
+    ping_sent: Option<Instant>,
+    round_trip_time: Option<Duration>,
+    is_disconnected: bool,
+}
+
+/// State shared with the thread reading the messages.
+struct Shared {
+    remote: Mutex<RemoteState>,
+    /// Notified when a ready message is received or the connection is lost.
+    ready_changed: Condvar,
+    writer: Mutex<TcpStream>,
+}
+
+impl Shared {
+    fn send(&self, kind: u8, value: u8, sequence: u8) {
+        let result = self
+            .writer
+            .lock()
+            .unwrap()
+            .write_all(&[kind, value, sequence]);
+        if let Err(e) = result {
+            error!("Link cable disconnected: {}", e);
+            self.remote.lock().unwrap().is_disconnected = true;
+            self.ready_changed.notify_all();
+        }
+    }
+}
+
+/// Serial device sending the transfers to another emulator.
+pub struct SocketLinkCable {
+    shared: Arc<Shared>,
+    /// Last byte announced to the other side while waiting for its clock, with its sequence
+    /// number.
+    announced: Option<(u8, u8)>,
+    sequence: u8,
+}
+
+impl SocketLinkCable {
+    /// Waits for the other emulator to connect, returns an `Interrupted` error once `is_cancelled`
+    /// is set.
+    pub fn listen(address: &str, is_cancelled: &AtomicBool) -> io::Result<Self> {
+        let listener = TcpListener::bind(address)?;
+        listener.set_nonblocking(true)?;
+        info!("Waiting for the link cable on {}", listener.local_addr()?);
+        loop {
+            match listener.accept() {
+                Ok((stream, peer)) => {
+                    info!("Link cable connected to {}", peer);
+                    stream.set_nonblocking(false)?;
+                    return Self::from_stream(stream);
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    if is_cancelled.load(Ordering::Relaxed) {
+                        return Err(io::Error::new(
+                            io::ErrorKind::Interrupted,
+                            "Link cable cancelled",
+                        ));
+                    }
+                    thread::sleep(ACCEPT_POLL_INTERVAL);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Connects to the other emulator, gives up after `CONNECT_TIMEOUT` on unreachable hosts.
+    pub fn connect(address: &str) -> io::Result<Self> {
+        let mut last_error =
+            io::Error::new(io::ErrorKind::InvalidInput, "No address to connect to");
+        for socket_address in address.to_socket_addrs()? {
+            match TcpStream::connect_timeout(&socket_address, CONNECT_TIMEOUT) {
+                Ok(stream) => {
+                    info!("Link cable connected to {}", socket_address);
+                    return Self::from_stream(stream);
+                }
+                Err(e) => last_error = e,
+            }
+        }
+        Err(last_error)
+    }
+
+    /// Starts reading the messages of the other emulator from the connected stream.
+    pub fn from_stream(stream: TcpStream) -> io::Result<Self> {
+        stream.set_nodelay(true)?;
+        let reader = stream.try_clone()?;
+        let shared = Arc::new(Shared {
+            remote: Mutex::new(RemoteState::default()),
+            ready_changed: Condvar::new(),
+            writer: Mutex::new(stream),
+        });
+        let thread_shared = shared.clone();
+        thread::spawn(move || read_messages(reader, thread_shared));
+
+        let cable = Self {
+            shared,
+            announced: None,
+            sequence: 0,
+        };
+        cable.send_ping();
+        Ok(cable)
+    }
+
+    pub fn is_connected(&self) -> bool {
+        !self.shared.remote.lock().unwrap().is_disconnected
+    }
+
+    /// Returns the last measured round trip time of the connection.
+    pub fn get_round_trip_time(&self) -> Option<Duration> {
+        self.shared.remote.lock().unwrap().round_trip_time
+    }
+
+    fn send_ping(&self) {
+        self.shared.remote.lock().unwrap().ping_sent = Some(Instant::now());
+        self.shared.send(MESSAGE_PING, 0, 0);
+    }
+}
+
+impl Drop for SocketLinkCable {
+    /// Closes the connection, which also stops the thread reading the messages.
+    fn drop(&mut self) {
+        let _ = self.shared.writer.lock().unwrap().shutdown(Shutdown::Both);
+    }
+}
+
+impl SerialDevice for SocketLinkCable {
+    fn exchange(&mut self, value: u8) -> u8 {
+        let remote = self.shared.remote.lock().unwrap();
+        let should_ping = remote
+            .ping_sent
+            .is_some_and(|sent| sent.elapsed() >= PING_INTERVAL);
+        let wait = remote
+            .round_trip_time
+            .map_or(MAX_READY_WAIT, |round_trip_time| {
+                (round_trip_time * 2).clamp(MIN_READY_WAIT, MAX_READY_WAIT)
+            });
+        let (mut remote, _) = self
+            .shared
+            .ready_changed
+            .wait_timeout_while(remote, wait, |remote| {
+                remote.ready.is_none() && !remote.is_disconnected
+            })
+            .unwrap();
+        let ready = remote.ready.take();
+        drop(remote);
+        if should_ping {
+            self.send_ping();
+        }
+        match ready {
+            Some((incoming, sequence)) => {
+                self.shared.send(MESSAGE_TRANSFER, value, sequence);
+                incoming
+            }
+            // Nobody is ready on the other side, the input line stays high.
+            None => 0xFF,
+        }
+    }
+
+    fn poll_external(&mut self, value: u8) -> Option<u8> {
+        let received = self.shared.remote.lock().unwrap().received.take();
+        match (received, self.announced) {
+            (Some((incoming, sequence)), Some((_, announced))) if sequence == announced => {
+                self.announced = None;
+                return Some(incoming);
+            }
+            // The transfer answers a byte that was withdrawn.
+            (Some(_), _) => info!("Link cable dropped a late transfer"),
+            (None, _) => {}
+        }
+        if self.announced.map(|(announced, _)| announced) != Some(value) {
+            self.sequence = self.sequence.wrapping_add(1);
+            self.announced = Some((value, self.sequence));
+            self.shared.send(MESSAGE_READY, value, self.sequence);
+        }
+        None
+    }
+
+    fn cancel_external(&mut self) {
+        self.shared.remote.lock().unwrap().received = None;
+        if let Some((_, sequence)) = self.announced.take() {
+            self.shared.send(MESSAGE_NOT_READY, 0, sequence);
+        }
+    }
+}
+
+fn read_messages(mut stream: TcpStream, shared: Arc<Shared>) {
+    let mut message = [0u8; MESSAGE_SIZE];
+    while stream.read_exact(&mut message).is_ok() {
+        let [kind, value, sequence] = message;
+        let mut remote = shared.remote.lock().unwrap();
+        match kind {
+            MESSAGE_READY => {
+                remote.ready = Some((value, sequence));
+                shared.ready_changed.notify_all();
+            }
+            MESSAGE_NOT_READY => {
+                if remote.ready.is_some_and(|(_, ready)| ready == sequence) {
+                    remote.ready = None;
+                }
+            }
+            MESSAGE_TRANSFER => remote.received = Some((value, sequence)),
+            MESSAGE_PING => {
+                drop(remote);
+                shared.send(MESSAGE_PONG, 0, 0);
+            }
+            MESSAGE_PONG => {
+                remote.round_trip_time = remote.ping_sent.map(|sent| sent.elapsed());
+            }
+            kind => error!("Unknown link cable message {}", kind),
+        }
+    }
+    info!("Link cable disconnected");
+    shared.remote.lock().unwrap().is_disconnected = true;
+    shared.ready_changed.notify_all();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_stream_pair() -> (TcpStream, TcpStream) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let connection = thread::spawn(move || TcpStream::connect(address).unwrap());
+        let accepted = listener.accept().unwrap().0;
+        (accepted, connection.join().unwrap())
+    }
+
+    fn create_cables() -> (SocketLinkCable, SocketLinkCable) {
+        let (first, second) = create_stream_pair();
+        (
+            SocketLinkCable::from_stream(first).unwrap(),
+            SocketLinkCable::from_stream(second).unwrap(),
+        )
+    }
+
+    /// Forwards the bytes read from `input` to `output` after the delay.
+    fn forward_with_delay(mut input: TcpStream, mut output: TcpStream, delay: Duration) {
+        let (sender, receiver) = std::sync::mpsc::channel::<(Instant, u8)>();
+        thread::spawn(move || {
+            for (received, byte) in receiver {
+                thread::sleep((received + delay).saturating_duration_since(Instant::now()));
+                if output.write_all(&[byte]).is_err() {
+                    break;
+                }
+            }
+        });
+        thread::spawn(move || {
+            let mut byte = [0u8];
+            while input.read_exact(&mut byte).is_ok() {
+                if sender.send((Instant::now(), byte[0])).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+
+    /// Creates cables connected through a proxy delaying each direction.
+    fn create_delayed_cables(delay: Duration) -> (SocketLinkCable, SocketLinkCable) {
+        let (first, first_proxy) = create_stream_pair();
+        let (second, second_proxy) = create_stream_pair();
+        forward_with_delay(
+            first_proxy.try_clone().unwrap(),
+            second_proxy.try_clone().unwrap(),
+            delay,
+        );
+        forward_with_delay(second_proxy, first_proxy, delay);
+        (
+            SocketLinkCable::from_stream(first).unwrap(),
+            SocketLinkCable::from_stream(second).unwrap(),
+        )
+    }
+
+    /// Waits until the messages sent by the other side are read.
+    fn wait_for(cable: &SocketLinkCable, condition: impl Fn(&RemoteState) -> bool) {
+        for _ in 0..1000 {
+            if condition(&cable.shared.remote.lock().unwrap()) {
+                return;
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+        panic!("The link cable message was not received");
+    }
+
+    #[test]
+    fn cancelled_listen_returns() {
+        let is_cancelled = AtomicBool::new(true);
+        let error = SocketLinkCable::listen("127.0.0.1:0", &is_cancelled).err();
+        assert_eq!(error.map(|e| e.kind()), Some(io::ErrorKind::Interrupted));
+    }
+
+    #[test]
+    fn transfer_between_local_sockets() {
+        let (mut first, mut second) = create_cables();
+
+        // Nobody waits on the other side, the transfer gives up after the bounded wait.
+        assert_eq!(first.exchange(0x42), 0xFF);
+
+        assert_eq!(second.poll_external(0x24), None);
+        wait_for(&first, |remote| remote.ready.is_some());
+        assert_eq!(first.exchange(0x42), 0x24);
+        let received = (0..1000).find_map(|_| {
+            thread::sleep(Duration::from_millis(1));
+            second.poll_external(0x24)
+        });
+        assert_eq!(received, Some(0x42));
+
+        drop(second);
+        wait_for(&first, |remote| remote.is_disconnected);
+        assert_eq!(first.exchange(0x42), 0xFF);
+    }
+
+    #[test]
+    fn cancelled_wait_is_withdrawn() {
+        let (mut first, mut second) = create_cables();
+
+        assert_eq!(second.poll_external(0x24), None);
+        wait_for(&first, |remote| remote.ready.is_some());
+        second.cancel_external();
+        wait_for(&first, |remote| remote.ready.is_none());
+        assert_eq!(first.exchange(0x42), 0xFF);
+    }
+
+    #[test]
+    fn transfer_waits_for_the_latency() {
+        let delay = Duration::from_millis(4);
+        let (mut first, mut second) = create_delayed_cables(delay);
+        wait_for(&first, |remote| remote.round_trip_time.is_some());
+        assert!(first.get_round_trip_time().unwrap() >= delay * 2);
+
+        // The ready message is still in flight when the transfer starts.
+        assert_eq!(second.poll_external(0x24), None);
+        assert_eq!(first.exchange(0x42), 0x24);
+        wait_for(&second, |remote| remote.received.is_some());
+        assert_eq!(second.poll_external(0x24), Some(0x42));
+    }
+
+    #[test]
+    fn transfer_answering_a_withdrawn_byte_is_dropped() {
+        let (mut first, mut second) = create_delayed_cables(Duration::from_millis(50));
+
+        assert_eq!(second.poll_external(0x24), None);
+        wait_for(&first, |remote| remote.ready.is_some());
+        // The not ready message is still in flight when the transfer starts.
+        second.cancel_external();
+        assert_eq!(first.exchange(0x42), 0x24);
+        wait_for(&second, |remote| remote.received.is_some());
+
+        assert_eq!(second.poll_external(0x11), None);
+        assert_eq!(second.poll_external(0x11), None);
+    }
+}
@@ -5,20 +5,32 @@ use crate::memory::gbmemory::GBMemory;
 use crate::memory::init::{init_memory, init_registers};
 use crate::memory::ram::RamController;
 use crate::memory::registers::Registers;
+use crate::memory::unusable::UnusableMemory;
+use crate::model::HardwareModel;
 use crate::serial::SerialTransfer;
+use crate::sgb::SuperGameBoy;
 use crate::sound::SoundController;
 use crate::timer::Timer;
 use crate::video::controller::VideoController;
 use crate::video::renderer::CoreNonCgbRenderer;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
 
 pub struct EmulatorState {
     pub memory: GBMemory,
     pub registers: Registers,
     pub renderer: CoreNonCgbRenderer,
+    save_path: Option<PathBuf>,
 }
 
 impl EmulatorState {
     pub fn new(cartridge: Cartridge) -> Self {
+        let model = HardwareModel::default_for(cartridge.cgb_flag);
+        Self::new_with_model(cartridge, model)
+    }
+
+    pub fn new_with_model(cartridge: Cartridge, model: HardwareModel) -> Self {
         let is_cgb = cartridge.cgb_flag.use_cgb();
         let mut memory = GBMemory::new(
             cartridge.memory_controller,
@@ -29,16 +41,30 @@ impl EmulatorState {
             SoundController::default(),
             Timer::new(),
             CGBRegisters::new(is_cgb),
+            UnusableMemory::new(model),
         );
         init_memory(cartridge.cgb_flag, &mut memory);
         let mut registers = Registers::new();
         init_registers(cartridge.cgb_flag, &mut registers);
 
         memory.init();
+        // The Super Game Boy only accepts commands from the games declaring its support.
+        if model.is_sgb() && cartridge.header.sgb_flag {
+            memory.sgb = Some(SuperGameBoy::new());
+        }
         Self {
             memory,
             registers,
             renderer: CoreNonCgbRenderer::new(),
+            save_path: cartridge.save_path,
+        }
+    }
+
+    /// Writes the cartridge battery backed memory next to the rom, if the cartridge has a battery.
+    pub fn save_battery(&self) -> io::Result<()> {
+        match &self.save_path {
+            Some(path) => fs::write(path, self.memory.save_battery()),
+            None => Ok(()),
         }
     }
 }
\ No newline at end of file
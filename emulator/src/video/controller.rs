@@ -1,4 +1,5 @@
 use crate::interrupts::Interrupt;
+use crate::memory::OPEN_BUS_VALUE;
 use crate::video::memory::{LcdControl, LcdStatus};
 use macros::BitAccessor;
 
@@ -290,7 +291,8 @@ impl VideoController {
             OBJ_PALETTE_DATA_1_ADDRESS => self.obj_palette_data_1.value = value,
             LCD_WINDOWS_Y_ADDRESS => self.coordinates.window_position_y = value,
             LCD_WINDOWS_X_ADDRESS => self.coordinates.window_position_x = value,
-            _ => panic!("Address {} is not valid for the video controller", address),
+            // The OAM DMA register is handled by the memory.
+            _ => {}
         }
     }
 
@@ -307,7 +309,8 @@ impl VideoController {
             OBJ_PALETTE_DATA_1_ADDRESS => self.obj_palette_data_1.value,
             LCD_WINDOWS_Y_ADDRESS => self.coordinates.window_position_y,
             LCD_WINDOWS_X_ADDRESS => self.coordinates.window_position_x,
-            _ => panic!("Address {} is not valid for the video controller", address),
+            // The OAM DMA register is handled by the memory.
+            _ => OPEN_BUS_VALUE,
         }
     }
 
@@ -335,6 +338,17 @@ impl VideoController {
         self.triggers.should_scanline
     }
 
+    /// Indicates that the OAM cannot be accessed by the CPU (mode 2 and 3).
+    ///
+    /// See: https://gbdev.io/pandocs/Rendering.html#ppu-modes
+    pub fn is_oam_blocked(&self) -> bool {
+        self.control.read_lcd_enable() == 1
+            && matches!(
+                self.status.read_mode(),
+                MODE_2_SEARCH_OAM_VALUE | MODE_3_TRANSFER_VALUE
+            )
+    }
+
     pub fn should_update_frame(&self) -> bool {
         // See: https://gbdev.io/pandocs/Rendering.html?highlight=frame%20update#ppu-modes
         self.triggers.should_update_frame
@@ -401,6 +415,7 @@ impl Default for VideoController {
 }
 
 #[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
 mod tests {
     use super::*;
 
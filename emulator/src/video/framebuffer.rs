@@ -0,0 +1,126 @@
+use crate::video::renderer::{Color, WHITE};
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+const NB_BYTES_PER_PIXEL: usize = 4;
+
+/// Picture of a whole frame, given to the frontend once it is complete.
+///
+/// Each pixel is stored twice: as the index of its color in the palette used to draw it, and as
+/// RGBA bytes ready to be uploaded to a texture or saved.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FrameBuffer {
+    width: usize,
+    height: usize,
+    indices: Vec<u8>,
+    rgba: Vec<u8>,
+}
+
+impl FrameBuffer {
+    /// Creates a white frame, every pixel using the color 0.
+    pub fn new(width: usize, height: usize) -> Self {
+        let mut rgba = Vec::with_capacity(width * height * NB_BYTES_PER_PIXEL);
+        for _ in 0..width * height {
+            rgba.extend_from_slice(&get_bytes(&WHITE));
+        }
+        Self {
+            width,
+            height,
+            indices: vec![0; width * height],
+            rgba,
+        }
+    }
+
+    pub fn get_width(&self) -> usize {
+        self.width
+    }
+
+    pub fn get_height(&self) -> usize {
+        self.height
+    }
+
+    pub(crate) fn set_pixel(&mut self, x: usize, y: usize, index: u8, color: &Color) {
+        let position = y * self.width + x;
+        self.indices[position] = index;
+        self.rgba[position * NB_BYTES_PER_PIXEL..(position + 1) * NB_BYTES_PER_PIXEL]
+            .copy_from_slice(&get_bytes(color));
+    }
+
+    pub fn get_index(&self, x: usize, y: usize) -> u8 {
+        self.indices[y * self.width + x]
+    }
+
+    pub fn get_color(&self, x: usize, y: usize) -> Color {
+        let position = (y * self.width + x) * NB_BYTES_PER_PIXEL;
+        Color {
+            red: self.rgba[position],
+            green: self.rgba[position + 1],
+            blue: self.rgba[position + 2],
+            alpha: self.rgba[position + 3],
+        }
+    }
+
+    /// Palette index of each pixel, line by line.
+    pub fn get_indices(&self) -> &[u8] {
+        &self.indices
+    }
+
+    /// Red, green, blue and alpha bytes of each pixel, line by line.
+    pub fn get_rgba(&self) -> &[u8] {
+        &self.rgba
+    }
+
+    pub fn save_png(&self, path: &Path) -> Result<(), String> {
+        let file = File::create(path).map_err(|error| error.to_string())?;
+        let mut encoder =
+            png::Encoder::new(BufWriter::new(file), self.width as u32, self.height as u32);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder
+            .write_header()
+            .and_then(|mut writer| writer.write_image_data(&self.rgba))
+            .map_err(|error| error.to_string())
+    }
+}
+
+fn get_bytes(color: &Color) -> [u8; NB_BYTES_PER_PIXEL] {
+    [color.red, color.green, color.blue, color.alpha]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::video::renderer::BLACK;
+    use std::env;
+    use std::fs;
+    use std::io::BufReader;
+
+    #[test]
+    fn pixels_are_stored_as_indices_and_rgba() {
+        let mut frame = FrameBuffer::new(4, 2);
+        frame.set_pixel(1, 1, 3, &BLACK);
+
+        assert_eq!(frame.get_index(1, 1), 3);
+        assert_eq!(frame.get_color(1, 1), BLACK);
+        assert_eq!(frame.get_color(0, 0), WHITE);
+        assert_eq!(frame.get_indices(), [0, 0, 0, 0, 0, 3, 0, 0]);
+        assert_eq!(&frame.get_rgba()[20..24], [0, 0, 0, 255]);
+        assert_ne!(frame, FrameBuffer::new(4, 2));
+    }
+
+    #[test]
+    fn frame_is_saved_to_png() {
+        let mut frame = FrameBuffer::new(2, 2);
+        frame.set_pixel(1, 0, 3, &BLACK);
+        let path = env::temp_dir().join(format!("framebuffer_test_{}.png", std::process::id()));
+        frame.save_png(&path).unwrap();
+
+        let decoder = png::Decoder::new(BufReader::new(File::open(&path).unwrap()));
+        let mut reader = decoder.read_info().unwrap();
+        let mut pixels = vec![0; reader.output_buffer_size().unwrap()];
+        reader.next_frame(&mut pixels).unwrap();
+        assert_eq!(pixels, frame.get_rgba());
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -1,4 +1,5 @@
 pub(crate) mod controller;
+pub(crate) mod framebuffer;
 pub(crate) mod memory;
 pub(crate) mod renderer;
 pub(crate) mod sprite;
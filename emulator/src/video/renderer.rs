@@ -1,4 +1,5 @@
 use crate::video::controller::VideoController;
+use crate::video::framebuffer::FrameBuffer;
 use crate::video::sprite::{get_intersected_sprites, get_pixel_value_from_sprite, SpriteSize, SPRITE_X_OFFSET, SPRITE_Y_OFFSET};
 use crate::video::tile::{
     get_pixel_value_from_tile, get_tile_address, get_vram_tile_offset_from_area,
@@ -23,8 +24,6 @@ pub const WHITE: Color = Color {
     blue: 0xFF,
 };
 
-const BLANK_LINE: [Color; SCREEN_WIDTH as usize] = [WHITE; SCREEN_WIDTH as usize];
-
 pub const LIGHT_GRAY: Color = Color {
     alpha: 255,
     red: 170,
@@ -46,6 +45,12 @@ pub const BLACK: Color = Color {
     blue: 0,
 };
 
+/// Colors of the 4 shades selected by the non-cgb palettes.
+pub const DMG_COLORS: [Color; 4] = [WHITE, LIGHT_GRAY, DARK_GRAY, BLACK];
+
+/// Line of shades, the shade 0 being white.
+const BLANK_LINE: [u8; SCREEN_WIDTH as usize] = [0; SCREEN_WIDTH as usize];
+
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
 pub struct Color {
     pub alpha: u8,
@@ -54,51 +59,50 @@ pub struct Color {
     pub blue: u8,
 }
 
-pub trait Screen: Send {
-    fn write_pixel(&mut self, x: usize, y: usize, color: &Color);
-    fn update_frame(&mut self);
-}
-
 /// This class provides the common implementation of the video renderer for non-cgb mode.
 ///
-/// The final renderer only has to provide a write_pixel function and update the frame.
+/// The lines are drawn in a framebuffer holding the shade of each pixel, the frontend receives it
+/// once the frame is complete.
 pub struct CoreNonCgbRenderer {
     window_y: usize,
+    frame: FrameBuffer,
 }
 
 impl CoreNonCgbRenderer {
     pub fn new() -> Self {
-        Self { window_y: 0 }
+        Self {
+            window_y: 0,
+            frame: FrameBuffer::new(SCREEN_WIDTH as usize, SCREEN_HEIGHT as usize),
+        }
     }
 
-    pub fn scanline<PixelWriter>(&mut self, video: &VideoController, mut writer: PixelWriter)
-    where
-        PixelWriter: FnMut(usize, usize, &Color),
-    {
+    pub fn get_frame(&self) -> &FrameBuffer {
+        &self.frame
+    }
+
+    pub fn scanline(&mut self, video: &VideoController) {
         if video.get_control().read_lcd_enable() == 0 {
             return;
         }
 
-        let mut y_colors: [Color; SCREEN_WIDTH as usize] =
+        let mut y_shades: [u8; SCREEN_WIDTH as usize] =
             if video.get_control().read_bg_window_enable() != 0 {
                 self.render_background_window(video)
             } else {
                 BLANK_LINE
             };
         if video.get_control().read_obj_enable() != 0 {
-            self.render_sprites(video, &mut y_colors);
+            self.render_sprites(video, &mut y_shades);
         }
 
         let y = (video.get_coordinates().y - 1) as usize;
-        for (x, color) in y_colors.iter().enumerate() {
-            writer(x, y, color);
+        for (x, shade) in y_shades.iter().enumerate() {
+            self.frame
+                .set_pixel(x, y, *shade, &DMG_COLORS[*shade as usize]);
         }
     }
 
-    fn render_background_window(
-        &mut self,
-        video: &VideoController,
-    ) -> [Color; SCREEN_WIDTH as usize] {
+    fn render_background_window(&mut self, video: &VideoController) -> [u8; SCREEN_WIDTH as usize] {
         // Information from: https://gbdev.io/pandocs/pixel_fifo.html#get-tile
         let y = (video.get_coordinates().y - 1) as usize;
         // Information from: https://gbdev.io/pandocs/Scrolling.html#ff4aff4b--wy-wx-window-y-position-x-position-plus-7
@@ -111,11 +115,11 @@ impl CoreNonCgbRenderer {
             window_enabled && ((video.get_coordinates().window_position_y as usize) <= y);
         let background_enabled = video.get_control().read_bg_window_enable() != 0;
 
-        let mut result = [WHITE; SCREEN_WIDTH as usize];
+        let mut result = [0; SCREEN_WIDTH as usize];
         for (x, cell) in result.iter_mut().enumerate() {
             let window_x = (video.get_coordinates().window_position_x as usize)
                 .saturating_sub(WINDOW_X_OFFSET);
-            let color = if window_enabled_for_y && (window_x <= x) {
+            let shade = if window_enabled_for_y && (window_x <= x) {
                 self.get_window_pixel(video, x - window_x, self.window_y)
             } else if background_enabled {
                 self.get_background_pixel(
@@ -124,9 +128,9 @@ impl CoreNonCgbRenderer {
                     y + video.get_coordinates().scroll_y as usize,
                 )
             } else {
-                WHITE
+                0
             };
-            *cell = color;
+            *cell = shade;
         }
 
         if y == (SCREEN_HEIGHT - 1) as usize {
@@ -138,14 +142,14 @@ impl CoreNonCgbRenderer {
         result
     }
 
-    fn get_window_pixel(&mut self, video: &VideoController, x: usize, y: usize) -> Color {
+    fn get_window_pixel(&mut self, video: &VideoController, x: usize, y: usize) -> u8 {
         let tile_map_offset =
             get_vram_tile_offset_from_area(video.get_control().read_window_tile_map_area());
 
         self.get_tile_pixel(video, x, y, tile_map_offset)
     }
 
-    fn get_background_pixel(&mut self, video: &VideoController, x: usize, y: usize) -> Color {
+    fn get_background_pixel(&mut self, video: &VideoController, x: usize, y: usize) -> u8 {
         let tile_map_offset =
             get_vram_tile_offset_from_area(video.get_control().read_bg_tile_map_area());
 
@@ -158,7 +162,7 @@ impl CoreNonCgbRenderer {
         x: usize,
         y: usize,
         tile_map_offset: usize,
-    ) -> Color {
+    ) -> u8 {
         let tile_map_x = x / 8;
         let tile_map_y = y / 8;
 
@@ -176,13 +180,13 @@ impl CoreNonCgbRenderer {
         let color_index =
             get_pixel_value_from_tile(video.get_vram(), tile_address, tile_x, tile_y);
 
-        get_non_cgb_color(color_index, video.get_bg_palette_data().value)
+        get_non_cgb_shade(color_index, video.get_bg_palette_data().value)
     }
 
     fn render_sprites(
         &mut self,
         video: &VideoController,
-        y_colors: &mut [Color; SCREEN_WIDTH as usize],
+        y_shades: &mut [u8; SCREEN_WIDTH as usize],
     ) {
         let y = (video.get_coordinates().y - 1) as usize;
 
@@ -198,7 +202,7 @@ impl CoreNonCgbRenderer {
         // Information from: https://gbdev.io/pandocs/OAM.html#drawing-priority
         sprites.sort_by_key(|sprite| sprite.x);
 
-        // Let the sprite with smallest x, overwrite the last color.
+        // Let the sprite with smallest x, overwrite the last shade.
         for sprite in sprites.iter().rev() {
             let palette = match sprite.read_non_cgb_palette() {
                 0 => video.get_obj_palette_data_0(),
@@ -214,11 +218,11 @@ impl CoreNonCgbRenderer {
                 let color_index =
                     get_pixel_value_from_sprite(video.get_vram(), sprite, sprite_x, sprite_y);
                 if color_index != 0 {
-                    let color = get_non_cgb_color(color_index, palette.value);
+                    let shade = get_non_cgb_shade(color_index, palette.value);
                     let x = (sprite.x + sprite_x) - SPRITE_X_OFFSET;
-                    // If the color is not white and bg_window_over_obj is set do nothing.
-                    if !(y_colors[x] != WHITE && sprite.read_bg_window_over_obj() != 0) {
-                        y_colors[x] = color;
+                    // If the shade is not white and bg_window_over_obj is set do nothing.
+                    if !(y_shades[x] != 0 && sprite.read_bg_window_over_obj() != 0) {
+                        y_shades[x] = shade;
                     }
                 };
             }
@@ -232,18 +236,9 @@ impl Default for CoreNonCgbRenderer {
     }
 }
 
+/// Returns the shade of the color index, the index of the color in DMG_COLORS.
+///
 /// Information from https://gbdev.io/pandocs/Palettes.html#ff47--bgp-non-cgb-mode-only-bg-palette-data
-fn get_non_cgb_color(index: u8, palette: u8) -> Color {
-    let color = (palette >> (index * 2)) & 0b11;
-
-    match color {
-        0 => WHITE,
-        1 => LIGHT_GRAY,
-        2 => DARK_GRAY,
-        3 => BLACK,
-        _ => panic!(
-            "Non CGB color only supports value in range [0..=3], got: {}",
-            index
-        ),
-    }
+fn get_non_cgb_shade(index: u8, palette: u8) -> u8 {
+    (palette >> (index * 2)) & 0b11
 }
@@ -0,0 +1,72 @@
+//! This file assumes that the blargg test roms have been downloaded and unpacked in a folder
+//! called blargg in this folder. Expected structure:
+//! tests/
+//!   | blargg/
+//!     | cpu_instrs/
+//!       | cpu_instrs.gb
+//!     ...
+//!   blargg_test.rs
+//! Download link can be found on the gb-test-roms GitHub page: https://github.com/retrio/gb-test-roms
+
+use emulator::cartridge::load_cartridge;
+use emulator::debugger::NoOpDebugger;
+use emulator::emulator::update_next_instruction;
+use emulator::gui::NoOpFrontend;
+use emulator::serial::SerialCapture;
+use emulator::state::EmulatorState;
+use std::path::Path;
+
+// Do not let the emulator run for more than 2 minutes.
+const MAX_NB_CYCLES: u64 = 4_000_000 * 120;
+// The captured text is checked once per frame duration, even when the LCD is off.
+const CHECK_INTERVAL_CYCLES: u64 = 70224;
+
+/// The test roms write their results over the serial port, ending with "Passed" or "Failed".
+fn run_serial_test(path: &Path) {
+    let cartridge = load_cartridge(path).expect("Unable to load cartridge");
+    let mut state = EmulatorState::new(cartridge);
+    let capture = SerialCapture::new();
+    state.memory.set_serial_device(Box::new(capture.clone()));
+    let mut frontend = NoOpFrontend::new();
+    let mut debugger = NoOpDebugger::new();
+
+    let mut nb_cycles = 0;
+    let mut next_check = 0;
+    let mut text = String::new();
+    while nb_cycles < MAX_NB_CYCLES && !text.contains("Passed") && !text.contains("Failed") {
+        nb_cycles += update_next_instruction(&mut state, &mut frontend, &mut debugger)
+            .unwrap_or_else(|fault| panic!("Failed test: {:?}, {}", path, fault))
+            .nb_cycles;
+        if nb_cycles >= next_check {
+            text = capture.get_text();
+            next_check += CHECK_INTERVAL_CYCLES;
+        }
+    }
+
+    assert!(text.contains("Passed"), "Failed test: {:?}\n{}", path, text);
+}
+
+#[test]
+fn cpu_instrs() {
+    run_serial_test(Path::new("./tests/blargg/cpu_instrs/cpu_instrs.gb"));
+}
+
+#[test]
+fn instr_timing() {
+    run_serial_test(Path::new("./tests/blargg/instr_timing/instr_timing.gb"));
+}
+
+#[test]
+fn mem_timing() {
+    run_serial_test(Path::new("./tests/blargg/mem_timing/mem_timing.gb"));
+}
+
+#[test]
+fn halt_bug() {
+    run_serial_test(Path::new("./tests/blargg/halt_bug.gb"));
+}
+
+#[test]
+fn dmg_sound() {
+    run_serial_test(Path::new("./tests/blargg/dmg_sound/dmg_sound.gb"));
+}
@@ -0,0 +1,43 @@
+//! Fixtures shared by the tests running small programs instead of rom files.
+
+// Each test crate only uses some of the fixtures.
+#![allow(dead_code)]
+
+use emulator::cartridge::Cartridge;
+use emulator::state::EmulatorState;
+
+const ROM_SIZE: usize = 0x8000;
+const PROGRAM_ADDRESS: usize = 0x150;
+const CGB_FLAG_ADDRESS: usize = 0x143;
+const CGB_ONLY_FLAG: u8 = 0xC0;
+const CARTRIDGE_TYPE_ADDRESS: usize = 0x147;
+/// About a quarter of a second.
+pub const NB_CYCLES: u64 = 1_000_000;
+
+/// Creates a rom without memory controller running the program after the header.
+pub fn create_rom(program: &[u8]) -> Vec<u8> {
+    let mut rom = vec![0u8; ROM_SIZE];
+    // NOP, JP 0x0150 to skip the header.
+    rom[0x100..0x104].copy_from_slice(&[0x00, 0xC3, 0x50, 0x01]);
+    rom[PROGRAM_ADDRESS..PROGRAM_ADDRESS + program.len()].copy_from_slice(program);
+    rom
+}
+
+/// Creates a console running the program after the header.
+pub fn create_state(program: &[u8]) -> EmulatorState {
+    EmulatorState::new(Cartridge::from_bytes(create_rom(program)).unwrap())
+}
+
+/// Creates a CGB console running the program after the header.
+pub fn create_cgb_state(program: &[u8]) -> EmulatorState {
+    let mut rom = create_rom(program);
+    rom[CGB_FLAG_ADDRESS] = CGB_ONLY_FLAG;
+    EmulatorState::new(Cartridge::from_bytes(rom).unwrap())
+}
+
+/// Creates a console with the given cartridge type running the program after the header.
+pub fn create_state_with_type(program: &[u8], cartridge_type: u8) -> EmulatorState {
+    let mut rom = create_rom(program);
+    rom[CARTRIDGE_TYPE_ADDRESS] = cartridge_type;
+    EmulatorState::new(Cartridge::from_bytes(rom).unwrap())
+}
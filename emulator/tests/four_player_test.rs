@@ -0,0 +1,49 @@
+//! Runs four consoles connected by the Four Player Adapter, without any rom file.
+
+mod common;
+
+use common::{create_state, NB_CYCLES};
+use emulator::four_player::{AdapterPhase, FourPlayerSession, MAX_PLAYERS};
+use emulator::gui::NoOpFrontend;
+use emulator::state::EmulatorState;
+
+/// Creates a console answering every ping of the adapter with 0x88 and storing the last status
+/// byte received in B.
+fn create_ping_state() -> EmulatorState {
+    let program = [
+        0x3E, 0x88, // LD A, 0x88
+        0xE0, 0x01, // LDH (SB), A
+        0x3E, 0x80, // LD A, 0x80
+        0xE0, 0x02, // LDH (SC), A
+        0xF0, 0x02, // LDH A, (SC)
+        0xCB, 0x7F, // BIT 7, A
+        0x20, 0xFA, // JR NZ, -6
+        0xF0, 0x01, // LDH A, (SB)
+        0xFE, 0xFE, // CP 0xFE
+        0x28, 0xEC, // JR Z, -20
+        0x47, // LD B, A
+        0x18, 0xE9, // JR -23
+    ];
+    create_state(&program)
+}
+
+#[test]
+fn four_consoles_answer_the_pings() {
+    let states = (0..MAX_PLAYERS).map(|_| create_ping_state()).collect();
+    let mut session = FourPlayerSession::new(states).unwrap();
+    let mut frontends: Vec<NoOpFrontend> = (0..MAX_PLAYERS).map(|_| NoOpFrontend::new()).collect();
+
+    session.run_for(NB_CYCLES, &mut frontends).unwrap();
+
+    assert_eq!(session.adapter.get_connected_players(), [true; MAX_PLAYERS]);
+    assert_eq!(session.adapter.get_phase(), AdapterPhase::Ping);
+    for (player, state) in session.states.iter().enumerate() {
+        assert_eq!(state.registers.b, 0xF0 | (player as u8 + 1));
+    }
+}
+
+#[test]
+fn session_needs_at_least_two_consoles() {
+    let states = vec![create_ping_state()];
+    assert!(FourPlayerSession::new(states).is_err());
+}
@@ -0,0 +1,70 @@
+//! Runs two consoles connected by the virtual link cable, without any rom file.
+
+mod common;
+
+use common::{create_cgb_state, NB_CYCLES};
+use emulator::gui::NoOpFrontend;
+use emulator::link::LinkedEmulators;
+use emulator::state::EmulatorState;
+
+/// Creates a console sending `value` over the link cable and storing the received byte in B.
+/// The transfer starts after a loop of `delay` iterations.
+fn create_state(value: u8, control: u8, delay: u8) -> EmulatorState {
+    let program = [
+        0x0E, delay, // LD C, delay
+        0x0D,  // DEC C
+        0x20, 0xFD, // JR NZ, -3
+        0x3E, value, // LD A, value
+        0xE0, 0x01, // LDH (SB), A
+        0x3E, control, // LD A, control
+        0xE0, 0x02, // LDH (SC), A
+        0xF0, 0x02, // LDH A, (SC)
+        0xCB, 0x7F, // BIT 7, A
+        0x20, 0xFA, // JR NZ, -6
+        0xF0, 0x01, // LDH A, (SB)
+        0x47, // LD B, A
+        0x18, 0xFE, // JR -2
+    ];
+    common::create_state(&program)
+}
+
+#[test]
+fn linked_consoles_exchange_a_byte() {
+    // The first console clocks the transfer once the second one waits for its clock.
+    let mut linked = LinkedEmulators::new(
+        create_state(0x42, 0x81, 0x10),
+        create_state(0x24, 0x80, 0x01),
+    );
+    let mut frontends = [NoOpFrontend::new(), NoOpFrontend::new()];
+
+    linked.run_for(NB_CYCLES, &mut frontends).unwrap();
+
+    assert_eq!(linked.states[0].registers.b, 0x24);
+    assert_eq!(linked.states[1].registers.b, 0x42);
+    let [first, second] = linked.get_cycles();
+    assert!(first.abs_diff(second) < 64);
+}
+
+#[test]
+fn linked_cgb_consoles_see_the_infrared_light() {
+    let emitter = [
+        0x3E, 0x01, // LD A, 0x01
+        0xE0, 0x56, // LDH (RP), A
+        0x18, 0xFE, // JR -2
+    ];
+    let receiver = [
+        0x3E, 0xC0, // LD A, 0xC0
+        0xE0, 0x56, // LDH (RP), A
+        0xF0, 0x56, // LDH A, (RP)
+        0xCB, 0x4F, // BIT 1, A
+        0x20, 0xFA, // JR NZ, -6
+        0x06, 0x42, // LD B, 0x42
+        0x18, 0xFE, // JR -2
+    ];
+    let mut linked = LinkedEmulators::new(create_cgb_state(&emitter), create_cgb_state(&receiver));
+    let mut frontends = [NoOpFrontend::new(), NoOpFrontend::new()];
+
+    linked.run_for(NB_CYCLES, &mut frontends).unwrap();
+
+    assert_eq!(linked.states[1].registers.b, 0x42);
+}
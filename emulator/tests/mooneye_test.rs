@@ -10,7 +10,7 @@
 use emulator::cartridge::load_cartridge;
 use emulator::debugger::Debugger;
 use emulator::emulator::update_next_instruction;
-use emulator::gui::NoOpGui;
+use emulator::gui::NoOpFrontend;
 use emulator::state::EmulatorState;
 use std::path::Path;
 
@@ -47,11 +47,12 @@ fn is_success(state: &EmulatorState) -> bool {
 fn run_acceptance_test(path: &Path) {
     let cartridge = load_cartridge(path).expect("Unable to load cartridge");
     let mut state = EmulatorState::new(cartridge);
-    let mut gui = NoOpGui::new();
+    let mut frontend = NoOpFrontend::new();
     let mut debugger = MooneyeDebugger::new();
 
     for _ in 0..MAX_NB_CYCLES {
-        update_next_instruction(&mut state, &mut gui, &mut debugger);
+        update_next_instruction(&mut state, &mut frontend, &mut debugger)
+            .unwrap_or_else(|fault| panic!("Failed acceptance test: {:?}, {}", path, fault));
         if debugger.completed {
             break;
         }
@@ -0,0 +1,60 @@
+//! Drives the accelerometer of a MBC7 cartridge with a tilt script, without any rom file.
+
+mod common;
+
+use common::{create_state_with_type, NB_CYCLES};
+use emulator::debugger::NoOpDebugger;
+use emulator::emulator::update_next_instruction;
+use emulator::gui::script::TiltScript;
+use emulator::gui::NoOpFrontend;
+use emulator::state::EmulatorState;
+
+const MBC7_TYPE: u8 = 0x22;
+
+/// Creates a MBC7 console latching the accelerometer in a loop and storing the high byte of X
+/// in B.
+fn create_accelerometer_state() -> EmulatorState {
+    let program = [
+        0x3E, 0x0A, // LD A, 0x0A
+        0xEA, 0x00, 0x00, // LD (0x0000), A
+        0x3E, 0x40, // LD A, 0x40
+        0xEA, 0x00, 0x40, // LD (0x4000), A
+        0x3E, 0x55, // LD A, 0x55
+        0xEA, 0x00, 0xA0, // LD (0xA000), A
+        0x3E, 0xAA, // LD A, 0xAA
+        0xEA, 0x10, 0xA0, // LD (0xA010), A
+        0xFA, 0x30, 0xA0, // LD A, (0xA030)
+        0x47, // LD B, A
+        0x18, 0xF0, // JR -16
+    ];
+    create_state_with_type(&program, MBC7_TYPE)
+}
+
+fn run(frontend: &mut NoOpFrontend) -> EmulatorState {
+    let mut state = create_accelerometer_state();
+    let mut debugger = NoOpDebugger::new();
+    let mut nb_cycles = 0;
+    while nb_cycles < NB_CYCLES {
+        nb_cycles += update_next_instruction(&mut state, frontend, &mut debugger)
+            .unwrap()
+            .nb_cycles;
+    }
+    state
+}
+
+#[test]
+fn accelerometer_follows_the_tilt_script() {
+    let script = TiltScript::parse("# frame x y\n5 1 0\n").unwrap();
+
+    let state = run(&mut NoOpFrontend::new().with_tilt_script(script));
+
+    // Full tilt to the right reads 0x81D0 + 0x70.
+    assert_eq!(state.registers.b, 0x82);
+}
+
+#[test]
+fn accelerometer_is_centered_without_script() {
+    let state = run(&mut NoOpFrontend::new());
+
+    assert_eq!(state.registers.b, 0x81);
+}
@@ -18,11 +18,6 @@ pub const ONLY_USE_REGISTER: UsedFnParams = UsedFnParams {
     memory: false,
     argument: false,
 };
-pub const ONLY_USE_MEMORY: UsedFnParams = UsedFnParams {
-    register: false,
-    memory: true,
-    argument: false,
-};
 pub const USE_REGISTER_AND_MEMORY: UsedFnParams = UsedFnParams {
     register: true,
     memory: true,
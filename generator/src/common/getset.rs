@@ -1,5 +1,4 @@
 use crate::common::register::get_register_from_name;
-use crate::instruction;
 use crate::instruction::{Argument, ArgumentType};
 use crate::interface::{Code, Expression, Language, Type};
 
@@ -87,14 +86,3 @@ pub fn create_get_code_no_address(language: &Language, argument: &Argument) -> E
         ),
     }
 }
-
-pub fn get_flag_from_name(language: &Language, name: &str) -> Expression {
-    match name.to_lowercase().as_str() {
-        instruction::FLAG_NAME_CARRY => language.registers.flags.get_carry_flag(),
-        instruction::FLAG_NAME_ZERO => language.registers.flags.get_zero_flag(),
-        instruction::FLAG_NAME_NON_CARRY => language.registers.flags.get_non_carry_flag(),
-        instruction::FLAG_NAME_NON_ZERO => language.registers.flags.get_non_zero_flag(),
-
-        _ => panic!("No flag for name {}", name),
-    }
-}
@@ -19,7 +19,7 @@ use crate::common::register::{
 };
 use crate::instruction;
 use crate::instruction::{Argument, FlagAction, Instruction, InstructionType, RegisterName};
-use crate::interface::{Code, Function, IntFormat, Language, Type};
+use crate::interface::{Code, Function, IntFormat, Language, Type, NB_CYCLE_TYPE};
 
 fn create_nop(instruction: &Instruction, language: &Language) -> Function {
     create_function(
@@ -30,15 +30,22 @@ fn create_nop(instruction: &Instruction, language: &Language) -> Function {
     )
 }
 
+/// The opcodes not defined on the Game Boy lock up the CPU until the console is reset.
+/// The program counter is left on the opcode.
 fn create_unknown(instruction: &Instruction, language: &Language) -> Function {
     create_function_custom(
         instruction,
         language,
-        NO_USED_PARAMS,
+        ONLY_USE_REGISTER,
         language
-            .statements
-            .stop_with_message(&format!("Unknown opcode 0x{:X}", instruction.value)),
-        FunctionDetails::default(),
+            .registers
+            .locked_up
+            .set(&language.statements.bool_literal(true)),
+        FunctionDetails {
+            doc: None,
+            pc_increment: None,
+            return_value: Some(language.decimal_literal(4, NB_CYCLE_TYPE)),
+        },
     )
 }
 
@@ -126,32 +126,6 @@ impl Memory for MemoryImpl {
         )
     }
 
-    fn get_signed(&self, address: &Expression) -> Expression {
-        assert_eq!(
-            address.type_,
-            Type::Uint16,
-            "Address type should be uint16 and not: {:?}",
-            address.type_
-        );
-        Expression::new(
-            format!("{}.read_signed({})", MEMORY_VAR_NAME, address.text),
-            Type::Int8,
-        )
-    }
-
-    fn get_16_bits(&self, address: &Expression) -> Expression {
-        assert_eq!(
-            address.type_,
-            Type::Uint16,
-            "Address type should be uint16 and not: {:?}",
-            address.type_
-        );
-        Expression::new(
-            format!("{}.read_16_bits({})", MEMORY_VAR_NAME, address.text),
-            Type::Uint16,
-        )
-    }
-
     fn set_8_bits(&self, address: &Expression, value: &Expression) -> Code {
         assert_eq!(
             address.type_,
@@ -290,12 +264,6 @@ impl Statements for StatementsImpl {
         }
     }
 
-    fn single_if(&self, condition: &Expression, code: &Code) -> Code {
-        Code::from_str(&format!("if {} {{", condition.text))
-            .append(code.clone().indent(INDENT))
-            .append_line("}}".to_string())
-    }
-
     fn if_else(&self, condition: &Expression, true_code: &Code, false_code: &Code) -> Code {
         Code::from_str(&format!("if {} {{", condition.text))
             .append(true_code.clone().indent(INDENT))
@@ -343,7 +311,7 @@ impl Statements for StatementsImpl {
 
         definition.iappend_line("}\n".to_string());
 
-        Function::new(String::from(name), signature, definition)
+        Function::new(String::from(name), definition)
     }
 
     fn get_function_by_opcode(
@@ -371,7 +339,7 @@ impl Statements for StatementsImpl {
             .append_line(format!("{}}}", INDENT))
             .append_line(String::from("}"));
 
-        Function::new(name, signature, code)
+        Function::new(name, code)
     }
 }
 
@@ -633,6 +601,10 @@ pub fn get_rust_language() -> Language {
             name: "stopped".to_string(),
             type_: Type::Bool,
         }),
+        locked_up: Box::new(AttributeRegister {
+            name: "locked_up".to_string(),
+            type_: Type::Bool,
+        }),
         ime_flag: Box::new(AttributeRegister {
             name: "ime_flag".to_string(),
             type_: Type::Bool,
@@ -60,11 +60,11 @@ impl Instruction {
     pub fn is_two_bytes_op(&self) -> bool {
         self.first_argument
             .as_ref()
-            .map_or(false, |arg| arg.get_value_nb_bytes() > 1)
+            .is_some_and(|arg| arg.get_value_nb_bytes() > 1)
             || self
                 .second_argument
                 .as_ref()
-                .map_or(false, |arg| arg.get_value_nb_bytes() > 1)
+                .is_some_and(|arg| arg.get_value_nb_bytes() > 1)
     }
 }
 
@@ -153,12 +153,6 @@ impl FlagAction {
 }
 
 pub const REGISTER_NAME_A: &str = "a";
-pub const REGISTER_NAME_B: &str = "b";
-pub const REGISTER_NAME_C: &str = "c";
-pub const REGISTER_NAME_D: &str = "d";
-pub const REGISTER_NAME_E: &str = "e";
-pub const REGISTER_NAME_H: &str = "h";
-pub const REGISTER_NAME_L: &str = "l";
 
 pub const FLAG_NAME_CARRY: &str = "c";
 pub const FLAG_NAME_ZERO: &str = "z";
@@ -164,6 +164,7 @@ pub struct Registers {
     pub program_counter: Box<dyn Register>,
     pub halted: Box<dyn Register>,
     pub stopped: Box<dyn Register>,
+    pub locked_up: Box<dyn Register>,
     pub ime_flag: Box<dyn Register>,
     pub flags: Box<dyn Flags>,
 }
@@ -171,8 +172,6 @@ pub struct Registers {
 pub trait Memory {
     fn name(&self) -> String;
     fn get(&self, address: &Expression) -> Expression;
-    fn get_signed(&self, address: &Expression) -> Expression;
-    fn get_16_bits(&self, address: &Expression) -> Expression;
     fn set_8_bits(&self, address: &Expression, value: &Expression) -> Code;
     fn set_16_bits(&self, address: &Expression, value: &Expression) -> Code;
 }
@@ -192,17 +191,12 @@ impl Parameter {
 #[derive(Debug)]
 pub struct Function {
     pub name: String,
-    pub signature: String,
     pub definition: Code,
 }
 
 impl Function {
-    pub fn new(name: String, signature: String, definition: Code) -> Self {
-        Self {
-            name,
-            signature,
-            definition,
-        }
+    pub fn new(name: String, definition: Code) -> Self {
+        Self { name, definition }
     }
 }
 
@@ -228,7 +222,6 @@ pub trait Statements {
     fn int_literal(&self, value: i64, type_: Type, format: IntFormat) -> Expression;
     fn bool_literal(&self, value: bool) -> Expression;
     fn variable(&self, name: &str, code: &Expression) -> Variable;
-    fn single_if(&self, condition: &Expression, code: &Code) -> Code;
     fn if_else(&self, condition: &Expression, true_code: &Code, false_code: &Code) -> Code;
     fn stop_with_message(&self, message: &str) -> Code;
     fn return_value(&self, value: &Expression) -> Code;